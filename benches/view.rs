@@ -0,0 +1,73 @@
+//! Compares viewing an image with `HVIFView` against parsing all of it with `hvif_image`
+//!
+//! Run with `cargo bench`. This bench has no harness, so that it builds on stable Rust.
+
+extern crate hvif_rs;
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use hvif_rs::HVIFView;
+use hvif_rs::parser::hvif_image;
+use hvif_rs::types::*;
+
+const BEE_ICON: &[u8] = include_bytes!("../data/beeicon.hvif");
+const ITERATIONS: u32 = 200_000;
+
+/// Runs a closure many times, printing the average time each run took
+fn bench<F: FnMut()>(name: &str, mut run: F)
+{
+  for _ in 0..ITERATIONS / 10 {
+    run();
+  }
+  let start = Instant::now();
+  for _ in 0..ITERATIONS {
+    run();
+  }
+  let nanos = start.elapsed().as_nanos() / ITERATIONS as u128;
+  println!("{:<32} {:>8} ns/iter", name, nanos);
+}
+
+fn point_sum(command: HVIFPointCommand) -> f32
+{
+  match command {
+    HVIFPointCommand::HLine { x } => x,
+    HVIFPointCommand::VLine { y } => y,
+    HVIFPointCommand::Line { point } => point.x + point.y,
+    HVIFPointCommand::Curve { point, .. } => point.x + point.y,
+  }
+}
+
+fn main()
+{
+  bench("hvif_image", || {
+    black_box(hvif_image(black_box(BEE_ICON)));
+  });
+  bench("HVIFView::new", || {
+    black_box(HVIFView::new(black_box(BEE_ICON)).unwrap());
+  });
+
+  // Counting what an indexer would, the shapes and their paths
+  bench("hvif_image, counting shapes", || {
+    let image = hvif_image(black_box(BEE_ICON)).unwrap().1;
+    black_box(image.shapes.iter().map(|shape| shape.path_indices.len()).sum::<usize>());
+  });
+  bench("HVIFView, counting shapes", || {
+    let view = HVIFView::new(black_box(BEE_ICON)).unwrap();
+    black_box(view.shapes().map(|shape| shape.path_indices().len()).sum::<usize>());
+  });
+
+  // Visiting every point
+  bench("hvif_image, visiting points", || {
+    let image = hvif_image(black_box(BEE_ICON)).unwrap().1;
+    black_box(image.paths.iter().flat_map(|path| path.points.iter().cloned()).map(point_sum).sum::<f32>());
+  });
+  bench("HVIFView, visiting points", || {
+    let view = HVIFView::new(black_box(BEE_ICON)).unwrap();
+    black_box(view.paths().flat_map(|path| path.points()).map(point_sum).sum::<f32>());
+  });
+
+  bench("HVIFView::to_image", || {
+    black_box(HVIFView::new(black_box(BEE_ICON)).unwrap().to_image());
+  });
+}
@@ -0,0 +1,572 @@
+//! The `hvif` command-line tool, for inspecting and converting HVIF images
+//!
+//! `hvif info <file>...` summarizes images, and `hvif dump [--json] <file>` prints everything in one.
+//! `hvif render` draws images as PNG, and `hvif convert` moves images between HVIF and SVG,
+//! choosing formats by file extension. Both convert every image in a directory tree when given directories.
+extern crate hvif_rs;
+#[cfg(feature = "serde")]
+extern crate serde_json;
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use hvif_rs::types::*;
+use hvif_rs::parser::{parse_layout, HVIFEntryLayout};
+use hvif_rs::{imsg, png, render, svg, writer};
+
+const USAGE: &str = "\
+usage: hvif info <file>...
+       hvif dump [--json] <file>
+       hvif render [-s <size>] -o <output> <input>
+       hvif convert [--to <format>] [-s <size>] <input> <output>
+
+commands:
+  info     the number of styles, paths and shapes, the bytes each section takes, and the flags used
+  dump     every style, path and shape, as a tree or as JSON
+  render   draws an HVIF or SVG image as a PNG image, 64 pixels wide unless given a size
+  convert  converts between HVIF, SVG and PNG, with formats chosen by file extension
+
+Given a directory as input, render and convert write every image found under it into the
+output directory, keeping the layout of subdirectories. Convert then needs --to hvif, svg or png.";
+
+/// The size, in pixels, that images are rendered at unless told otherwise
+const DEFAULT_SIZE: u32 = 64;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
+/// The formats images can be read from or written to
+enum Format {
+  HVIF,
+  SVG,
+  PNG,
+}
+
+impl Format {
+  fn from_extension(extension: &str) -> Option<Format>
+  {
+    match extension.to_ascii_lowercase().as_str() {
+      "hvif" => Some(Format::HVIF),
+      "svg" => Some(Format::SVG),
+      "png" => Some(Format::PNG),
+      _ => None,
+    }
+  }
+
+  fn of(path: &Path) -> Option<Format>
+  {
+    path.extension().and_then(|extension| extension.to_str()).and_then(Format::from_extension)
+  }
+
+  fn extension(self) -> &'static str
+  {
+    match self {
+      Format::HVIF => "hvif",
+      Format::SVG => "svg",
+      Format::PNG => "png",
+    }
+  }
+}
+
+fn main()
+{
+  let args: Vec<String> = env::args().skip(1).collect();
+  let result = match args.first().map(String::as_str) {
+    Some("info") if args.len() > 1 => args[1..].iter().try_for_each(|path| info(path)),
+    Some("dump") => match &args[1..] {
+      [path] => dump(path, false),
+      [flag, path] if flag == "--json" => dump(path, true),
+      _ => usage(),
+    },
+    Some("render") => render_command(&args[1..]),
+    Some("convert") => convert_command(&args[1..]),
+    Some("help") | Some("--help") | Some("-h") => {
+      println!("{}", USAGE);
+      Ok(())
+    },
+    _ => usage(),
+  };
+  if let Err(message) = result {
+    eprintln!("hvif: {}", message);
+    process::exit(1);
+  }
+}
+
+fn usage() -> Result<(), String>
+{
+  eprintln!("{}", USAGE);
+  process::exit(2);
+}
+
+fn render_command(args: &[String]) -> Result<(), String>
+{
+  let mut size = DEFAULT_SIZE;
+  let mut output = None;
+  let mut input = None;
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "-s" | "--size" => size = parse_size(args.next())?,
+      "-o" | "--output" => output = args.next(),
+      _ if input.is_none() => input = Some(arg),
+      _ => return usage(),
+    }
+  }
+  match (input, output) {
+    (Some(input), Some(output)) => convert(Path::new(input), Path::new(output), Some(Format::PNG), size),
+    _ => usage(),
+  }
+}
+
+fn convert_command(args: &[String]) -> Result<(), String>
+{
+  let mut size = DEFAULT_SIZE;
+  let mut to = None;
+  let mut paths = Vec::new();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "-s" | "--size" => size = parse_size(args.next())?,
+      "-t" | "--to" => match args.next().and_then(|format| Format::from_extension(format)) {
+        Some(format) => to = Some(format),
+        None => return Err("--to needs one of hvif, svg or png".to_string()),
+      },
+      _ => paths.push(arg),
+    }
+  }
+  match paths[..] {
+    [input, output] => convert(Path::new(input), Path::new(output), to, size),
+    _ => usage(),
+  }
+}
+
+fn parse_size(arg: Option<&String>) -> Result<u32, String>
+{
+  match arg.and_then(|size| size.parse().ok()) {
+    Some(size) if size > 0 => Ok(size),
+    _ => Err("the size must be a positive number of pixels".to_string()),
+  }
+}
+
+/// Converts a file, or every image under a directory, into the given format or the one of the output's extension
+fn convert(input: &Path, output: &Path, to: Option<Format>, size: u32) -> Result<(), String>
+{
+  if !input.is_dir() {
+    let to = match to.or_else(|| Format::of(output)) {
+      Some(to) => to,
+      None => return Err(format!("{}: unknown format, expected .hvif, .svg or .png", output.display())),
+    };
+    return convert_file(input, output, to, size)
+  }
+
+  let to = match to {
+    Some(to) => to,
+    None => return Err("converting a directory needs --to hvif, svg or png".to_string()),
+  };
+  let mut failures = 0;
+  for file in image_files(input)? {
+    // Mirror the file's place under the input directory in the output directory
+    let relative = file.strip_prefix(input).unwrap_or(&file);
+    let target = output.join(relative).with_extension(to.extension());
+    let converted = match target.parent() {
+      Some(parent) => fs::create_dir_all(parent).map_err(|err| format!("{}: {}", parent.display(), err)),
+      None => Ok(()),
+    }.and_then(|_| convert_file(&file, &target, to, size));
+    if let Err(message) = converted {
+      eprintln!("hvif: {}", message);
+      failures += 1;
+    }
+  }
+  match failures {
+    0 => Ok(()),
+    _ => Err(format!("{} files could not be converted", failures)),
+  }
+}
+
+/// Every HVIF and SVG file under a directory, in sorted order
+fn image_files(directory: &Path) -> Result<Vec<PathBuf>, String>
+{
+  let entries = fs::read_dir(directory).map_err(|err| format!("{}: {}", directory.display(), err))?;
+  // Whether an entry is a directory is told without following symlinks
+  let mut paths: Vec<(PathBuf, bool)> = entries
+    .filter_map(|entry| entry.ok().and_then(|entry| entry.file_type().ok().map(|file_type| (entry.path(), file_type.is_dir()))))
+    .collect();
+  paths.sort();
+
+  let mut files = Vec::new();
+  for (path, is_dir) in paths {
+    if is_dir {
+      files.extend(image_files(&path)?);
+    } else if path.is_dir() {
+      // Symlinked directories are skipped, as they can lead back up the tree
+      continue
+    } else if let Some(Format::HVIF) | Some(Format::SVG) = Format::of(&path) {
+      files.push(path);
+    }
+  }
+  Ok(files)
+}
+
+fn convert_file(input: &Path, output: &Path, to: Format, size: u32) -> Result<(), String>
+{
+  let image = load(input)?;
+  let bytes = match to {
+    Format::HVIF => writer::hvif_image(&image).map_err(|err| format!("{}: {}", output.display(), err))?,
+    Format::SVG => svg::svg_image(&image).into_bytes(),
+    Format::PNG => png::png_image(&render::render(&image, size)),
+  };
+  fs::write(output, bytes).map_err(|err| format!("{}: {}", output.display(), err))
+}
+
+/// Reads an HVIF or SVG image, reporting anything an SVG image loses on the way
+fn load(path: &Path) -> Result<HVIFImage, String>
+{
+  let bytes = fs::read(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+  match Format::of(path) {
+    Some(Format::SVG) => {
+      let text = String::from_utf8(bytes).map_err(|_| format!("{}: not UTF-8 text", path.display()))?;
+      let import = svg::parse(&text).map_err(|err| format!("{}: {}", path.display(), err))?;
+      for note in &import.notes {
+        eprintln!("hvif: {}: {}", path.display(), note);
+      }
+      Ok(import.image)
+    },
+    Some(Format::PNG) => Err(format!("{}: PNG images cannot be read", path.display())),
+    // Icon-O-Matic documents have no extension of their own, and are told apart by their magic number
+    _ if bytes.starts_with(b"IMSG") => imsg::parse(&bytes).map_err(|err| format!("{}: {}", path.display(), err)),
+    // Anything else is taken to be HVIF, as icons stored in attributes have no extension
+    _ => hvif_rs::parse(&bytes).map_err(|err| format!("{}: {}", path.display(), err)),
+  }
+}
+
+fn read(path: &str) -> Result<Vec<u8>, String>
+{
+  fs::read(path).map_err(|err| format!("{}: {}", path, err))
+}
+
+fn read_image(path: &str) -> Result<HVIFImage, String>
+{
+  let bytes = read(path)?;
+  hvif_rs::parse(&bytes).map_err(|err| format!("{}: {}", path, err))
+}
+
+fn info(path: &str) -> Result<(), String>
+{
+  let bytes = read(path)?;
+  let layout = parse_layout(&bytes).map_err(|err| format!("{}: {}", path, err))?;
+
+  println!("{}: {} bytes", path, bytes.len());
+  println!("  {:<8}{:>5} {:>7} bytes", "magic", "", 4);
+  println!("  {:<8}{:>5} {:>7} bytes", "styles", layout.style_entries.len(), layout.styles.len());
+  println!("  {:<8}{:>5} {:>7} bytes", "paths", layout.path_entries.len(), layout.paths.len());
+  println!("  {:<8}{:>5} {:>7} bytes", "shapes", layout.shape_entries.len(), layout.shapes.len());
+
+  let gradient_flags = [
+    ("transform", HVIF_GRADIENT_FLAG_TRANSFORM),
+    ("no alpha", HVIF_GRADIENT_FLAG_NO_ALPHA),
+    ("16 bit colors", HVIF_GRADIENT_FLAG_COLORS_16_BIT),
+    ("grays", HVIF_GRADIENT_FLAG_GRAYS),
+  ];
+  let path_flags = [
+    ("closed", HVIF_PATH_FLAG_CLOSED),
+    ("uses commands", HVIF_PATH_FLAG_USES_COMMANDS),
+    ("no curves", HVIF_PATH_FLAG_NO_CURVES),
+  ];
+  let shape_flags = [
+    ("transform", HVIF_SHAPE_FLAG_TRANSFORM),
+    ("hinting", HVIF_SHAPE_FLAG_HINTING),
+    ("lod scale", HVIF_SHAPE_FLAG_LOD_SCALE),
+    ("has transformers", HVIF_SHAPE_FLAG_HAS_TRANSFORMERS),
+    ("translation", HVIF_SHAPE_FLAG_TRANSLATION),
+  ];
+  println!("  flags used");
+  info_flags("gradient", &layout.style_entries, &gradient_flags);
+  info_flags("path", &layout.path_entries, &path_flags);
+  info_flags("shape", &layout.shape_entries, &shape_flags);
+  Ok(())
+}
+
+/// Prints how many entries have each flag set, leaving out flags no entry has
+fn info_flags(kind: &str, entries: &[HVIFEntryLayout], flags: &[(&str, HVIFFlag)])
+{
+  for &(name, flag) in flags {
+    let count = entries.iter().filter(|entry| flag.is_set_on(entry.flags)).count();
+    if count > 0 {
+      println!("    {} {}: {}", kind, name, count);
+    }
+  }
+}
+
+fn dump(path: &str, json: bool) -> Result<(), String>
+{
+  let image = read_image(path)?;
+  match json {
+    true  => println!("{}", json_image(&image)),
+    false => print!("{}", tree_image(&image)),
+  }
+  Ok(())
+}
+
+fn tree_image(image: &HVIFImage) -> String
+{
+  let mut out = String::new();
+  let _ = writeln!(out, "styles ({})", image.styles.len());
+  for (index, style) in image.styles.iter().enumerate() {
+    let _ = write!(out, "  {}: ", index);
+    match *style {
+      HVIFStyle::SolidColor { red, green, blue, alpha } =>
+        { let _ = writeln!(out, "solid color #{:02x}{:02x}{:02x}{:02x}", red, green, blue, alpha); },
+      HVIFStyle::SolidColorNoAlpha { red, green, blue } =>
+        { let _ = writeln!(out, "solid color #{:02x}{:02x}{:02x}", red, green, blue); },
+      HVIFStyle::SolidGray { value, alpha } => { let _ = writeln!(out, "solid gray {} alpha {}", value, alpha); },
+      HVIFStyle::SolidGrayNoAlpha { value } => { let _ = writeln!(out, "solid gray {}", value); },
+      HVIFStyle::Gradient(ref gradient) => {
+        let _ = writeln!(out, "{} gradient", gradient_type_name(gradient.gradient_type));
+        if let Some(matrix) = gradient.transform {
+          let _ = writeln!(out, "       transform {}", tree_matrix(matrix));
+        }
+        for color in &gradient.colors {
+          let _ = writeln!(out, "       stop {:3} #{:02x}{:02x}{:02x}{:02x}",
+                           color.stop_offset, color.red, color.green, color.blue, color.alpha);
+        }
+      },
+    }
+  }
+
+  let _ = writeln!(out, "paths ({})", image.paths.len());
+  for (index, path) in image.paths.iter().enumerate() {
+    let _ = writeln!(out, "  {}: {}, {} points", index, if path.closed { "closed" } else { "open" }, path.points.len());
+    for command in &path.points {
+      let _ = match *command {
+        HVIFPointCommand::HLine { x } => writeln!(out, "       hline {}", x),
+        HVIFPointCommand::VLine { y } => writeln!(out, "       vline {}", y),
+        HVIFPointCommand::Line { point } => writeln!(out, "       line {}", tree_point(point)),
+        HVIFPointCommand::Curve { point_in, point, point_out } =>
+          writeln!(out, "       curve {} in {} out {}", tree_point(point), tree_point(point_in), tree_point(point_out)),
+      };
+    }
+  }
+
+  let _ = writeln!(out, "shapes ({})", image.shapes.len());
+  for (index, shape) in image.shapes.iter().enumerate() {
+    let paths: Vec<String> = shape.path_indices.iter().map(|path| path.to_string()).collect();
+    let _ = writeln!(out, "  {}: style {}, paths [{}]", index, shape.style_index, paths.join(", "));
+    for modifier in &shape.modifiers {
+      let _ = match *modifier {
+        HVIFShapeModifier::HVIFTransformMatrix(matrix) => writeln!(out, "       transform {}", tree_matrix(matrix)),
+        HVIFShapeModifier::HVIFTranslation(offset) => writeln!(out, "       translation {}", tree_point(offset)),
+        HVIFShapeModifier::HVIFHinting => writeln!(out, "       hinting"),
+        HVIFShapeModifier::HVIFLODScale { min, max } => writeln!(out, "       lod scale {} to {}", min, max),
+        HVIFShapeModifier::HVIFTransformerList(ref transformers) => {
+          let _ = writeln!(out, "       transformers");
+          for transformer in transformers {
+            let _ = match *transformer {
+              HVIFTransformer::Affine(matrix) => writeln!(out, "         affine {}", tree_matrix(matrix)),
+              HVIFTransformer::Contour { width, line_join, miter_limit } =>
+                writeln!(out, "         contour width {}, {} join, miter limit {}",
+                         width, line_join_name(line_join), miter_limit),
+              HVIFTransformer::Perspective => writeln!(out, "         perspective"),
+              HVIFTransformer::Stroke { width, line_join, line_cap, miter_limit } =>
+                writeln!(out, "         stroke width {}, {} join, {} cap, miter limit {}",
+                         width, line_join_name(line_join), line_cap_name(line_cap), miter_limit),
+            };
+          }
+          Ok(())
+        },
+      };
+    }
+  }
+  out
+}
+
+fn tree_point(point: HVIFPoint) -> String
+{
+  format!("({}, {})", point.x, point.y)
+}
+
+fn tree_matrix(matrix: HVIFMatrix) -> String
+{
+  let values: [f32; 6] = matrix.into();
+  let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+  format!("[{}]", values.join(", "))
+}
+
+fn gradient_type_name(gradient_type: HVIFGradientType) -> &'static str
+{
+  match gradient_type {
+    HVIFGradientType::Linear => "linear",
+    HVIFGradientType::Circular => "circular",
+    HVIFGradientType::Diamond => "diamond",
+    HVIFGradientType::Conic => "conic",
+    HVIFGradientType::XY => "xy",
+    HVIFGradientType::SqrtXY => "sqrt_xy",
+  }
+}
+
+fn line_join_name(line_join: HVIFLineJoin) -> &'static str
+{
+  match line_join {
+    HVIFLineJoin::Miter => "miter",
+    HVIFLineJoin::MiterRevert => "miter_revert",
+    HVIFLineJoin::Round => "round",
+    HVIFLineJoin::Bevel => "bevel",
+    HVIFLineJoin::MiterRound => "miter_round",
+  }
+}
+
+fn line_cap_name(line_cap: HVIFLineCap) -> &'static str
+{
+  match line_cap {
+    HVIFLineCap::Butt => "butt",
+    HVIFLineCap::Square => "square",
+    HVIFLineCap::Round => "round",
+  }
+}
+
+/// Writes an image as JSON, in the schema documented in `hvif_rs::types`
+#[cfg(feature = "serde")]
+fn json_image(image: &HVIFImage) -> String
+{
+  serde_json::to_string(image).expect("images always serialize")
+}
+
+/// Writes an image as JSON without serde, in the same schema
+///
+/// Images read from HVIF have no names, so the `names` object is never written.
+#[cfg(not(feature = "serde"))]
+fn json_image(image: &HVIFImage) -> String
+{
+  let styles: Vec<String> = image.styles.iter().map(json_style).collect();
+  let paths: Vec<String> = image.paths.iter().map(json_path).collect();
+  let shapes: Vec<String> = image.shapes.iter().map(json_shape).collect();
+  format!("{{\"styles\":[{}],\"paths\":[{}],\"shapes\":[{}]}}", styles.join(","), paths.join(","), shapes.join(","))
+}
+
+#[cfg(not(feature = "serde"))]
+fn json_style(style: &HVIFStyle) -> String
+{
+  match *style {
+    HVIFStyle::SolidColor { red, green, blue, alpha } =>
+      format!("{{\"type\":\"solid_color\",\"red\":{},\"green\":{},\"blue\":{},\"alpha\":{}}}", red, green, blue, alpha),
+    HVIFStyle::SolidColorNoAlpha { red, green, blue } =>
+      format!("{{\"type\":\"solid_color_no_alpha\",\"red\":{},\"green\":{},\"blue\":{}}}", red, green, blue),
+    HVIFStyle::SolidGray { value, alpha } => format!("{{\"type\":\"solid_gray\",\"value\":{},\"alpha\":{}}}", value, alpha),
+    HVIFStyle::SolidGrayNoAlpha { value } => format!("{{\"type\":\"solid_gray_no_alpha\",\"value\":{}}}", value),
+    HVIFStyle::Gradient(ref gradient) => {
+      let transform = match gradient.transform {
+        Some(matrix) => json_matrix(matrix),
+        None => "null".to_string(),
+      };
+      let colors: Vec<String> = gradient.colors.iter().map(|color| {
+        format!("{{\"stop_offset\":{},\"alpha\":{},\"red\":{},\"green\":{},\"blue\":{}}}",
+                color.stop_offset, color.alpha, color.red, color.green, color.blue)
+      }).collect();
+      format!("{{\"type\":\"gradient\",\"gradient_type\":\"{}\",\"transform\":{},\"colors\":[{}]}}",
+              gradient_type_name(gradient.gradient_type), transform, colors.join(","))
+    },
+  }
+}
+
+#[cfg(not(feature = "serde"))]
+fn json_path(path: &HVIFPath) -> String
+{
+  let points: Vec<String> = path.points.iter().map(|command| match *command {
+    HVIFPointCommand::HLine { x } => format!("{{\"type\":\"hline\",\"x\":{}}}", json_number(x)),
+    HVIFPointCommand::VLine { y } => format!("{{\"type\":\"vline\",\"y\":{}}}", json_number(y)),
+    HVIFPointCommand::Line { point } => format!("{{\"type\":\"line\",\"point\":{}}}", json_point(point)),
+    HVIFPointCommand::Curve { point_in, point, point_out } =>
+      format!("{{\"type\":\"curve\",\"point_in\":{},\"point\":{},\"point_out\":{}}}",
+              json_point(point_in), json_point(point), json_point(point_out)),
+  }).collect();
+  format!("{{\"closed\":{},\"points\":[{}]}}", path.closed, points.join(","))
+}
+
+#[cfg(not(feature = "serde"))]
+fn json_shape(shape: &HVIFShape) -> String
+{
+  let paths: Vec<String> = shape.path_indices.iter().map(|path| path.to_string()).collect();
+  let modifiers: Vec<String> = shape.modifiers.iter().map(|modifier| match *modifier {
+    HVIFShapeModifier::HVIFTransformMatrix(matrix) => format!("{{\"type\":\"transform_matrix\",\"matrix\":{}}}", json_matrix(matrix)),
+    HVIFShapeModifier::HVIFTranslation(offset) => format!("{{\"type\":\"translation\",\"offset\":{}}}", json_point(offset)),
+    HVIFShapeModifier::HVIFHinting => "{\"type\":\"hinting\"}".to_string(),
+    HVIFShapeModifier::HVIFLODScale { min, max } =>
+      format!("{{\"type\":\"lod_scale\",\"min\":{},\"max\":{}}}", json_number(min), json_number(max)),
+    HVIFShapeModifier::HVIFTransformerList(ref transformers) => {
+      let transformers: Vec<String> = transformers.iter().map(json_transformer).collect();
+      format!("{{\"type\":\"transformer_list\",\"transformers\":[{}]}}", transformers.join(","))
+    },
+  }).collect();
+  format!("{{\"style_index\":{},\"path_indices\":[{}],\"modifiers\":[{}]}}",
+          shape.style_index, paths.join(","), modifiers.join(","))
+}
+
+#[cfg(not(feature = "serde"))]
+fn json_transformer(transformer: &HVIFTransformer) -> String
+{
+  match *transformer {
+    HVIFTransformer::Affine(matrix) => format!("{{\"type\":\"affine\",\"matrix\":{}}}", json_matrix(matrix)),
+    HVIFTransformer::Contour { width, line_join, miter_limit } =>
+      format!("{{\"type\":\"contour\",\"width\":{},\"line_join\":\"{}\",\"miter_limit\":{}}}",
+              json_number(width), line_join_name(line_join), miter_limit),
+    HVIFTransformer::Perspective => "{\"type\":\"perspective\"}".to_string(),
+    HVIFTransformer::Stroke { width, line_join, line_cap, miter_limit } =>
+      format!("{{\"type\":\"stroke\",\"width\":{},\"line_join\":\"{}\",\"line_cap\":\"{}\",\"miter_limit\":{}}}",
+              json_number(width), line_join_name(line_join), line_cap_name(line_cap), miter_limit),
+  }
+}
+
+#[cfg(not(feature = "serde"))]
+fn json_point(point: HVIFPoint) -> String
+{
+  format!("{{\"x\":{},\"y\":{}}}", json_number(point.x), json_number(point.y))
+}
+
+/// A matrix as its six values, in storage order
+#[cfg(not(feature = "serde"))]
+fn json_matrix(matrix: HVIFMatrix) -> String
+{
+  let values: [f32; 6] = matrix.into();
+  let values: Vec<String> = values.iter().map(|&value| json_number(value)).collect();
+  format!("[{}]", values.join(","))
+}
+
+/// A number written as serde writes it, always with a fraction, or null for the infinities and NaN that JSON cannot hold
+#[cfg(not(feature = "serde"))]
+fn json_number(value: f32) -> String
+{
+  match value.is_finite() {
+    true  => format!("{:?}", value),
+    false => "null".to_string(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn finds_hvif_and_svg_files_under_a_directory() {
+    let directory = env::temp_dir().join(format!("hvif-image-files-{}", process::id()));
+    let files = ["a.hvif", "b.SVG", "c.png", "dir.hvif/f.hvif", "noext", "notes.txt", "sub/d.hvif", "sub/deeper/e.svg"];
+    for file in &files {
+      let path = directory.join(file);
+      fs::create_dir_all(path.parent().unwrap()).unwrap();
+      fs::write(path, b"").unwrap();
+    }
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&directory, directory.join("sub/loop.hvif")).unwrap();
+
+    let found = image_files(&directory);
+    let missing = image_files(&directory.join("missing"));
+    fs::remove_dir_all(&directory).unwrap();
+
+    // Directories are searched whatever their name, in sorted order, but not through symlinks,
+    // and extensions match in any case
+    let found: Vec<PathBuf> = found.unwrap().iter().map(|path| path.strip_prefix(&directory).unwrap().to_path_buf()).collect();
+    let expected: Vec<PathBuf> = ["a.hvif", "b.SVG", "dir.hvif/f.hvif", "sub/d.hvif", "sub/deeper/e.svg"].iter().map(PathBuf::from).collect();
+    assert_eq!(found, expected);
+    assert!(missing.is_err());
+  }
+}
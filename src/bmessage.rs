@@ -0,0 +1,612 @@
+//! Reading and writing flattened BMessages, the archive format Haiku keeps documents and settings in
+//!
+//! A message has a four character code telling what it is, and named fields, each holding one or more values
+//! of a single type. A flattened message has a header, a hash table to look fields up by name, a table of fields,
+//! and a data area holding the name and items of each field. Numbers are stored in the byte order
+//! of the machine that flattened the message, which the format code at its start tells apart.
+//! Items of fixed size types are packed one after another, while each item of other types,
+//! such as strings and nested messages, is preceded by its size.
+//! Messages are written in little endian byte order, as flattened on the x86 machines Haiku mostly runs on.
+
+use std::fmt;
+
+use bytes::{slice, ByteOrder};
+
+/// The format code of messages flattened by Haiku, `'HMF1'`
+const MESSAGE_FORMAT_HAIKU: u32 = 0x484d_4631;
+/// The size of the header, before the hash table
+const MESSAGE_HEADER_SIZE: usize = 48;
+/// The size of one entry of the field table
+const FIELD_HEADER_SIZE: usize = 24;
+/// The size of the hash table of messages Haiku flattens
+const MESSAGE_HASH_TABLE_SIZE: usize = 5;
+/// The message is valid, the only flag a flattened message keeps
+const MESSAGE_FLAG_VALID: u32 = 0x0001;
+/// The field is valid
+const FIELD_FLAG_VALID: u16 = 0x0001;
+/// The field holds items of a fixed size, packed without sizes
+const FIELD_FLAG_FIXED_SIZE: u16 = 0x0002;
+/// How deeply messages may nest, far deeper than any real message needs
+const MAX_MESSAGE_DEPTH: usize = 256;
+
+/// The type code of booleans, `'BOOL'`
+pub const B_BOOL_TYPE: u32 = 0x424f_4f4c;
+/// The type code of 8 bit integers, `'BYTE'`
+pub const B_INT8_TYPE: u32 = 0x4259_5445;
+/// The type code of 16 bit integers, `'SHRT'`
+pub const B_INT16_TYPE: u32 = 0x5348_5254;
+/// The type code of 32 bit integers, `'LONG'`
+pub const B_INT32_TYPE: u32 = 0x4c4f_4e47;
+/// The type code of 64 bit integers, `'LLNG'`
+pub const B_INT64_TYPE: u32 = 0x4c4c_4e47;
+/// The type code of unsigned 8 bit integers, `'UBYT'`
+pub const B_UINT8_TYPE: u32 = 0x5542_5954;
+/// The type code of unsigned 16 bit integers, `'USHT'`
+pub const B_UINT16_TYPE: u32 = 0x5553_4854;
+/// The type code of unsigned 32 bit integers, `'ULNG'`
+pub const B_UINT32_TYPE: u32 = 0x554c_4e47;
+/// The type code of unsigned 64 bit integers, `'ULLG'`
+pub const B_UINT64_TYPE: u32 = 0x554c_4c47;
+/// The type code of 32 bit floats, `'FLOT'`
+pub const B_FLOAT_TYPE: u32 = 0x464c_4f54;
+/// The type code of 64 bit floats, `'DBLE'`
+pub const B_DOUBLE_TYPE: u32 = 0x4442_4c45;
+/// The type code of zero terminated strings, `'CSTR'`
+pub const B_STRING_TYPE: u32 = 0x4353_5452;
+/// The type code of points, two 32 bit floats, `'BPNT'`
+pub const B_POINT_TYPE: u32 = 0x4250_4e54;
+/// The type code of rectangles, four 32 bit floats, `'RECT'`
+pub const B_RECT_TYPE: u32 = 0x5245_4354;
+/// The type code of colors, four bytes of red, green, blue and alpha, `'RGBC'`
+pub const B_RGB_COLOR_TYPE: u32 = 0x5247_4243;
+/// The type code of nested messages, `'MSGG'`
+pub const B_MESSAGE_TYPE: u32 = 0x4d53_4747;
+/// The type code of raw bytes, `'RAWT'`
+pub const B_RAW_TYPE: u32 = 0x5241_5754;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// Reasons a message can fail to be read, or a value fail to be added to one
+pub enum BMessageError {
+  /// The input does not start with the format code of a flattened message
+  NotMessage,
+  /// The message ended before it was complete
+  Truncated {
+    #[doc = "Byte offset, from the start of the outermost message, of the data that was cut short"] offset: usize
+  },
+  /// The message is malformed, such as a field whose items do not fill its data
+  Malformed {
+    #[doc = "Byte offset, from the start of the outermost message, of the malformed data"] offset: usize
+  },
+  /// A value was added to a field that holds values of another type
+  WrongType {
+    #[doc = "The type code of the field"] field_type: u32,
+    #[doc = "The type code of the value"] value_type: u32
+  },
+}
+
+impl fmt::Display for BMessageError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      BMessageError::NotMessage => write!(f, "not a flattened message"),
+      BMessageError::Truncated { offset } => write!(f, "message cut short at byte {}", offset),
+      BMessageError::Malformed { offset } => write!(f, "malformed message at byte {}", offset),
+      BMessageError::WrongType { field_type, value_type } =>
+        write!(f, "a field of type {:#010x} cannot hold a value of type {:#010x}", field_type, value_type),
+    }
+  }
+}
+
+impl ::std::error::Error for BMessageError {}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A single value of a field
+///
+/// Items whose size does not match their type, such as several doubles archived as one item, are kept as data,
+/// with numbers in little endian byte order whatever order the message was flattened in.
+pub enum BMessageValue {
+  /// A boolean
+  Bool(bool),
+  /// An 8 bit integer
+  Int8(i8),
+  /// A 16 bit integer
+  Int16(i16),
+  /// A 32 bit integer
+  Int32(i32),
+  /// A 64 bit integer
+  Int64(i64),
+  /// An unsigned 8 bit integer
+  UInt8(u8),
+  /// An unsigned 16 bit integer
+  UInt16(u16),
+  /// An unsigned 32 bit integer
+  UInt32(u32),
+  /// An unsigned 64 bit integer
+  UInt64(u64),
+  /// A 32 bit float
+  Float(f32),
+  /// A 64 bit float
+  Double(f64),
+  /// A string, stored with a zero byte ending it
+  String(String),
+  /// A point
+  Point {
+    #[doc = "The x-coordinate"] x: f32,
+    #[doc = "The y-coordinate"] y: f32
+  },
+  /// A rectangle, by the coordinates of its edges
+  Rect {
+    #[doc = "The x-coordinate of the left edge"] left: f32,
+    #[doc = "The y-coordinate of the top edge"] top: f32,
+    #[doc = "The x-coordinate of the right edge"] right: f32,
+    #[doc = "The y-coordinate of the bottom edge"] bottom: f32
+  },
+  /// A color
+  Color {
+    #[doc = "red channel"] red: u8,
+    #[doc = "green channel"] green: u8,
+    #[doc = "blue channel"] blue: u8,
+    #[doc = "alpha channel"] alpha: u8
+  },
+  /// A nested message
+  Message(BMessage),
+  /// Raw bytes
+  Raw(Vec<u8>),
+  /// Bytes of any other type
+  Data {
+    #[doc = "The type code of the data"] type_code: u32,
+    #[doc = "The bytes of the data"] data: Vec<u8>
+  },
+}
+
+impl BMessageValue {
+  /// The type code of the value
+  pub fn type_code(&self) -> u32
+  {
+    match *self {
+      BMessageValue::Bool(_) => B_BOOL_TYPE,
+      BMessageValue::Int8(_) => B_INT8_TYPE,
+      BMessageValue::Int16(_) => B_INT16_TYPE,
+      BMessageValue::Int32(_) => B_INT32_TYPE,
+      BMessageValue::Int64(_) => B_INT64_TYPE,
+      BMessageValue::UInt8(_) => B_UINT8_TYPE,
+      BMessageValue::UInt16(_) => B_UINT16_TYPE,
+      BMessageValue::UInt32(_) => B_UINT32_TYPE,
+      BMessageValue::UInt64(_) => B_UINT64_TYPE,
+      BMessageValue::Float(_) => B_FLOAT_TYPE,
+      BMessageValue::Double(_) => B_DOUBLE_TYPE,
+      BMessageValue::String(_) => B_STRING_TYPE,
+      BMessageValue::Point { .. } => B_POINT_TYPE,
+      BMessageValue::Rect { .. } => B_RECT_TYPE,
+      BMessageValue::Color { .. } => B_RGB_COLOR_TYPE,
+      BMessageValue::Message(_) => B_MESSAGE_TYPE,
+      BMessageValue::Raw(_) => B_RAW_TYPE,
+      BMessageValue::Data { type_code, .. } => type_code,
+    }
+  }
+
+  /// Reads an item of a type, in a byte order, that starts at an offset within the outermost message
+  /// and is held by as many messages as the depth
+  fn from_item(type_code: u32, item: &[u8], order: ByteOrder, start: usize, depth: usize) -> Result<BMessageValue, BMessageError>
+  {
+    let u16_at = |offset: usize| order.u16(item, offset).unwrap_or(0);
+    let u32_at = |offset: usize| order.u32(item, offset).unwrap_or(0);
+    let u64_at = |offset: usize| order.u64(item, offset).unwrap_or(0);
+    let float_at = |offset: usize| f32::from_bits(u32_at(offset));
+    Ok(match (type_code, item.len()) {
+      (B_BOOL_TYPE, 1) => BMessageValue::Bool(item[0] != 0),
+      (B_INT8_TYPE, 1) => BMessageValue::Int8(item[0] as i8),
+      (B_INT16_TYPE, 2) => BMessageValue::Int16(u16_at(0) as i16),
+      (B_INT32_TYPE, 4) => BMessageValue::Int32(u32_at(0) as i32),
+      (B_INT64_TYPE, 8) => BMessageValue::Int64(u64_at(0) as i64),
+      (B_UINT8_TYPE, 1) => BMessageValue::UInt8(item[0]),
+      (B_UINT16_TYPE, 2) => BMessageValue::UInt16(u16_at(0)),
+      (B_UINT32_TYPE, 4) => BMessageValue::UInt32(u32_at(0)),
+      (B_UINT64_TYPE, 8) => BMessageValue::UInt64(u64_at(0)),
+      (B_FLOAT_TYPE, 4) => BMessageValue::Float(float_at(0)),
+      (B_DOUBLE_TYPE, 8) => BMessageValue::Double(f64::from_bits(u64_at(0))),
+      (B_STRING_TYPE, _) => {
+        let end = item.iter().position(|&byte| byte == 0).unwrap_or(item.len());
+        BMessageValue::String(String::from_utf8_lossy(&item[..end]).into_owned())
+      },
+      (B_POINT_TYPE, 8) => BMessageValue::Point { x: float_at(0), y: float_at(4) },
+      (B_RECT_TYPE, 16) => BMessageValue::Rect { left: float_at(0), top: float_at(4), right: float_at(8), bottom: float_at(12) },
+      (B_RGB_COLOR_TYPE, 4) => BMessageValue::Color { red: item[0], green: item[1], blue: item[2], alpha: item[3] },
+      (B_MESSAGE_TYPE, _) => BMessageValue::Message(BMessage::unflatten_at(item, start, depth)?),
+      (B_RAW_TYPE, _) => BMessageValue::Raw(item.to_vec()),
+      (type_code, _) => {
+        let mut data = item.to_vec();
+        // Numbers kept as data are brought into little endian order one by one, as Haiku swaps them
+        if let (ByteOrder::Big, Some(size)) = (order, message_number_size(type_code)) {
+          for number in data.chunks_mut(size) {
+            number.reverse();
+          }
+        }
+        BMessageValue::Data { type_code, data }
+      },
+    })
+  }
+
+  /// Writes the value as an item, in little endian byte order
+  fn to_item(&self) -> Vec<u8>
+  {
+    match *self {
+      BMessageValue::Bool(value) => vec![value as u8],
+      BMessageValue::Int8(value) => vec![value as u8],
+      BMessageValue::Int16(value) => value.to_le_bytes().to_vec(),
+      BMessageValue::Int32(value) => value.to_le_bytes().to_vec(),
+      BMessageValue::Int64(value) => value.to_le_bytes().to_vec(),
+      BMessageValue::UInt8(value) => vec![value],
+      BMessageValue::UInt16(value) => value.to_le_bytes().to_vec(),
+      BMessageValue::UInt32(value) => value.to_le_bytes().to_vec(),
+      BMessageValue::UInt64(value) => value.to_le_bytes().to_vec(),
+      BMessageValue::Float(value) => value.to_bits().to_le_bytes().to_vec(),
+      BMessageValue::Double(value) => value.to_bits().to_le_bytes().to_vec(),
+      BMessageValue::String(ref value) => {
+        let mut item = value.as_bytes().to_vec();
+        item.push(0);
+        item
+      },
+      BMessageValue::Point { x, y } => message_floats(&[x, y]),
+      BMessageValue::Rect { left, top, right, bottom } => message_floats(&[left, top, right, bottom]),
+      BMessageValue::Color { red, green, blue, alpha } => vec![red, green, blue, alpha],
+      BMessageValue::Message(ref message) => message.flatten(),
+      BMessageValue::Raw(ref data) | BMessageValue::Data { ref data, .. } => data.clone(),
+    }
+  }
+}
+
+/// Floats in little endian byte order, one after another
+fn message_floats(values: &[f32]) -> Vec<u8>
+{
+  values.iter().flat_map(|value| value.to_bits().to_le_bytes().to_vec()).collect()
+}
+
+/// The size of each number of a type whose numbers Haiku swaps between byte orders
+fn message_number_size(type_code: u32) -> Option<usize>
+{
+  match type_code {
+    B_INT16_TYPE | B_UINT16_TYPE => Some(2),
+    B_INT32_TYPE | B_UINT32_TYPE | B_FLOAT_TYPE | B_POINT_TYPE | B_RECT_TYPE => Some(4),
+    B_INT64_TYPE | B_UINT64_TYPE | B_DOUBLE_TYPE => Some(8),
+    _ => None,
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A message, holding its fields in the order they were first added to
+pub struct BMessage {
+  /// The four character code telling what the message is
+  pub what: u32,
+  fields: Vec<BMessageField>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A named field of a message, with every value it holds
+struct BMessageField {
+  name: String,
+  type_code: u32,
+  values: Vec<BMessageValue>,
+}
+
+impl BMessage {
+  /// A message without fields
+  pub fn new(what: u32) -> BMessage
+  {
+    BMessage { what, fields: Vec::new() }
+  }
+
+  /// Adds a value to the field with a name, adding the field first if the message does not have it
+  ///
+  /// As with Haiku, every value of a field has the same type.
+  pub fn add(&mut self, name: &str, value: BMessageValue) -> Result<(), BMessageError>
+  {
+    let type_code = value.type_code();
+    match self.fields.iter_mut().find(|field| field.name == name) {
+      Some(ref field) if field.type_code != type_code =>
+        Err(BMessageError::WrongType { field_type: field.type_code, value_type: type_code }),
+      Some(field) => {
+        field.values.push(value);
+        Ok(())
+      },
+      None => {
+        self.fields.push(BMessageField { name: name.to_string(), type_code, values: vec![value] });
+        Ok(())
+      },
+    }
+  }
+
+  /// Removes the field with a name, giving back its values
+  pub fn remove(&mut self, name: &str) -> Option<Vec<BMessageValue>>
+  {
+    let position = self.fields.iter().position(|field| field.name == name)?;
+    Some(self.fields.remove(position).values)
+  }
+
+  /// The name, type code and values of every field, in order
+  pub fn fields(&self) -> impl Iterator<Item = (&str, u32, &[BMessageValue])>
+  {
+    self.fields.iter().map(|field| (field.name.as_str(), field.type_code, &field.values[..]))
+  }
+
+  /// Every value of the field with a name, which is empty if the message has no such field
+  pub fn values(&self, name: &str) -> &[BMessageValue]
+  {
+    self.fields.iter().find(|field| field.name == name).map_or(&[], |field| &field.values[..])
+  }
+
+  /// One value of the field with a name
+  pub fn find(&self, name: &str, index: usize) -> Option<&BMessageValue>
+  {
+    self.values(name).get(index)
+  }
+
+  /// A boolean value
+  pub fn find_bool(&self, name: &str, index: usize) -> Option<bool>
+  {
+    match self.find(name, index) {
+      Some(&BMessageValue::Bool(value)) => Some(value),
+      _ => None,
+    }
+  }
+
+  /// A 32 bit integer value
+  pub fn find_int32(&self, name: &str, index: usize) -> Option<i32>
+  {
+    match self.find(name, index) {
+      Some(&BMessageValue::Int32(value)) => Some(value),
+      _ => None,
+    }
+  }
+
+  /// A 32 bit float value
+  pub fn find_float(&self, name: &str, index: usize) -> Option<f32>
+  {
+    match self.find(name, index) {
+      Some(&BMessageValue::Float(value)) => Some(value),
+      _ => None,
+    }
+  }
+
+  /// A 64 bit float value
+  pub fn find_double(&self, name: &str, index: usize) -> Option<f64>
+  {
+    match self.find(name, index) {
+      Some(&BMessageValue::Double(value)) => Some(value),
+      _ => None,
+    }
+  }
+
+  /// A string value
+  pub fn find_string(&self, name: &str, index: usize) -> Option<&str>
+  {
+    match self.find(name, index) {
+      Some(BMessageValue::String(value)) => Some(value),
+      _ => None,
+    }
+  }
+
+  /// A point value, as its x and y coordinates
+  pub fn find_point(&self, name: &str, index: usize) -> Option<(f32, f32)>
+  {
+    match self.find(name, index) {
+      Some(&BMessageValue::Point { x, y }) => Some((x, y)),
+      _ => None,
+    }
+  }
+
+  /// A color value, as its red, green, blue and alpha channels
+  pub fn find_color(&self, name: &str, index: usize) -> Option<[u8; 4]>
+  {
+    match self.find(name, index) {
+      Some(&BMessageValue::Color { red, green, blue, alpha }) => Some([red, green, blue, alpha]),
+      _ => None,
+    }
+  }
+
+  /// A nested message value
+  pub fn find_message(&self, name: &str, index: usize) -> Option<&BMessage>
+  {
+    match self.find(name, index) {
+      Some(BMessageValue::Message(message)) => Some(message),
+      _ => None,
+    }
+  }
+
+  /// The bytes of a value of a type kept as data, or of raw bytes
+  pub fn find_data(&self, name: &str, type_code: u32, index: usize) -> Option<&[u8]>
+  {
+    match self.find(name, index) {
+      Some(BMessageValue::Raw(data)) if type_code == B_RAW_TYPE => Some(data),
+      Some(&BMessageValue::Data { type_code: data_type, ref data }) if data_type == type_code => Some(data),
+      _ => None,
+    }
+  }
+
+  /// Reads a flattened message, and every message nested in it
+  pub fn unflatten(data: &[u8]) -> Result<BMessage, BMessageError>
+  {
+    BMessage::unflatten_at(data, 0, 0)
+  }
+
+  /// Reads a flattened message held by as many messages as its depth, that starts at an offset
+  /// within the outermost message, which the offsets of errors are counted from
+  fn unflatten_at(data: &[u8], start: usize, depth: usize) -> Result<BMessage, BMessageError>
+  {
+    if depth > MAX_MESSAGE_DEPTH {
+      return Err(BMessageError::Malformed { offset: start })
+    }
+    let truncated = |offset: usize| BMessageError::Truncated { offset: start + offset };
+    let malformed = |offset: usize| BMessageError::Malformed { offset: start + offset };
+    let order = [ByteOrder::Little, ByteOrder::Big].iter().cloned()
+      .find(|&order| order.u32(data, 0) == Some(MESSAGE_FORMAT_HAIKU))
+      .ok_or(BMessageError::NotMessage)?;
+    let u32_at = |offset: usize| order.u32(data, offset).map(|value| value as usize).ok_or_else(|| truncated(offset));
+
+    let what = u32_at(4)? as u32;
+    let data_size = u32_at(36)?;
+    let field_count = u32_at(40)?;
+    let hash_table_size = u32_at(44)?;
+    let fields_offset = hash_table_size.checked_mul(4)
+      .and_then(|size| size.checked_add(MESSAGE_HEADER_SIZE))
+      .ok_or_else(|| malformed(44))?;
+    let data_offset = field_count.checked_mul(FIELD_HEADER_SIZE)
+      .and_then(|size| size.checked_add(fields_offset))
+      .ok_or_else(|| malformed(40))?;
+    let area = slice(data, data_offset, data_size).ok_or_else(|| truncated(data.len()))?;
+
+    let mut message = BMessage::new(what);
+    for index in 0..field_count {
+      let header = fields_offset + index * FIELD_HEADER_SIZE;
+      let flags = order.u16(data, header).ok_or_else(|| truncated(header))?;
+      let name_length = order.u16(data, header + 2).ok_or_else(|| truncated(header + 2))? as usize;
+      let type_code = u32_at(header + 4)? as u32;
+      let count = u32_at(header + 8)?;
+      let size = u32_at(header + 12)?;
+      let offset = u32_at(header + 16)?;
+
+      let name = slice(area, offset, name_length).ok_or_else(|| malformed(header + 16))?;
+      let name = &name[..name.iter().position(|&byte| byte == 0).unwrap_or(name.len())];
+      let values = offset.checked_add(name_length)
+        .and_then(|start| slice(area, start, size))
+        .ok_or_else(|| malformed(header + 12))?;
+      let values_offset = data_offset + offset + name_length;
+
+      let name = String::from_utf8_lossy(name).into_owned();
+      let mut field = BMessageField { name, type_code, values: Vec::new() };
+      // Haiku removes a field along with its last item, so every field holds one at least
+      if count == 0 {
+        return Err(malformed(header + 8))
+      }
+      if flags & FIELD_FLAG_FIXED_SIZE != 0 {
+        // Packed items take up at least a byte each, so that they can be told apart
+        if size == 0 || size % count != 0 {
+          return Err(malformed(header + 8))
+        }
+        let item_size = size / count;
+        for (position, item) in values.chunks(item_size).enumerate() {
+          let item_offset = start + values_offset + position * item_size;
+          field.values.push(BMessageValue::from_item(type_code, item, order, item_offset, depth + 1)?);
+        }
+      } else {
+        let mut position = 0;
+        for _ in 0..count {
+          let item_size = order.u32(values, position).ok_or_else(|| malformed(values_offset + position))? as usize;
+          let item = slice(values, position + 4, item_size).ok_or_else(|| malformed(values_offset + position))?;
+          let item_offset = start + values_offset + position + 4;
+          field.values.push(BMessageValue::from_item(type_code, item, order, item_offset, depth + 1)?);
+          position += 4 + item_size;
+        }
+      }
+      message.fields.push(field);
+    }
+    Ok(message)
+  }
+
+  /// Flattens the message, and every message nested in it
+  ///
+  /// Fields are chained into the hash table as Haiku does it, so that Haiku can find them by name.
+  /// Strings, nested messages, empty data and data of differing sizes are written with the size of each item.
+  pub fn flatten(&self) -> Vec<u8>
+  {
+    // Each field goes at the end of the chain of its bucket
+    let mut hash_table = [-1i32; MESSAGE_HASH_TABLE_SIZE];
+    let mut next_fields = vec![-1i32; self.fields.len()];
+    for (index, field) in self.fields.iter().enumerate() {
+      let bucket = message_hash_name(&field.name) as usize % MESSAGE_HASH_TABLE_SIZE;
+      match hash_table[bucket] {
+        -1 => hash_table[bucket] = index as i32,
+        first => {
+          let mut last = first as usize;
+          while next_fields[last] >= 0 {
+            last = next_fields[last] as usize;
+          }
+          next_fields[last] = index as i32;
+        },
+      }
+    }
+
+    let mut headers = Vec::with_capacity(self.fields.len() * FIELD_HEADER_SIZE);
+    let mut data = Vec::new();
+    for (field, next) in self.fields.iter().zip(&next_fields) {
+      let items: Vec<Vec<u8>> = field.values.iter().map(BMessageValue::to_item).collect();
+      let fixed_size = match field.type_code {
+        B_STRING_TYPE | B_MESSAGE_TYPE => false,
+        _ => !items[0].is_empty() && items.iter().all(|item| item.len() == items[0].len()),
+      };
+
+      let offset = data.len();
+      data.extend_from_slice(field.name.as_bytes());
+      data.push(0);
+      let values_offset = data.len();
+      for item in &items {
+        if !fixed_size {
+          data.extend_from_slice(&(item.len() as u32).to_le_bytes());
+        }
+        data.extend_from_slice(item);
+      }
+
+      let flags = match fixed_size {
+        true  => FIELD_FLAG_VALID | FIELD_FLAG_FIXED_SIZE,
+        false => FIELD_FLAG_VALID,
+      };
+      headers.extend_from_slice(&flags.to_le_bytes());
+      headers.extend_from_slice(&((field.name.len() + 1) as u16).to_le_bytes());
+      headers.extend_from_slice(&field.type_code.to_le_bytes());
+      headers.extend_from_slice(&(items.len() as u32).to_le_bytes());
+      headers.extend_from_slice(&((data.len() - values_offset) as u32).to_le_bytes());
+      headers.extend_from_slice(&(offset as u32).to_le_bytes());
+      headers.extend_from_slice(&next.to_le_bytes());
+    }
+
+    let mut out = Vec::with_capacity(MESSAGE_HEADER_SIZE + 4 * MESSAGE_HASH_TABLE_SIZE + headers.len() + data.len());
+    out.extend_from_slice(&MESSAGE_FORMAT_HAIKU.to_le_bytes());
+    out.extend_from_slice(&self.what.to_le_bytes());
+    out.extend_from_slice(&MESSAGE_FLAG_VALID.to_le_bytes());
+    // The target, specifier, area and reply fields only mean something to messages being delivered
+    for _ in 0..6 {
+      out.extend_from_slice(&(-1i32).to_le_bytes());
+    }
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(&(self.fields.len() as u32).to_le_bytes());
+    out.extend_from_slice(&(MESSAGE_HASH_TABLE_SIZE as u32).to_le_bytes());
+    for bucket in &hash_table {
+      out.extend_from_slice(&bucket.to_le_bytes());
+    }
+    out.extend_from_slice(&headers);
+    out.extend_from_slice(&data);
+    out
+  }
+}
+
+/// Haiku's hash of a field name, which picks the bucket of the hash table the field is chained into
+///
+/// Each byte is taken as a signed character, as Haiku does.
+fn message_hash_name(name: &str) -> u32
+{
+  let mut result: u32 = 0;
+  for &byte in name.as_bytes() {
+    result = (result << 7) ^ (result >> 24);
+    result ^= byte as i8 as i32 as u32;
+  }
+  result ^ (result << 12)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn limits_how_deeply_messages_nest() {
+    let nested = |depth: usize| {
+      let mut message = BMessage::new(0);
+      for _ in 0..depth {
+        let mut outer = BMessage::new(0);
+        outer.add("message", BMessageValue::Message(message)).unwrap();
+        message = outer;
+      }
+      message.flatten()
+    };
+    assert!(BMessage::unflatten(&nested(MAX_MESSAGE_DEPTH)).is_ok());
+    assert!(matches!(BMessage::unflatten(&nested(MAX_MESSAGE_DEPTH + 1)), Err(BMessageError::Malformed { .. })));
+  }
+}
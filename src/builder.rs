@@ -0,0 +1,173 @@
+//! A builder for HVIF images, referring to styles and paths by handle rather than by index
+//!
+//! Handles are only handed out for styles and paths that were added, and carry the builder they came from,
+//! so the shapes of a built image always refer to entries that exist. The limits of the format are enforced
+//! as entries are added.
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use types::*;
+
+use validate::{validate_conflicting, HVIFDiagnostic, HVIFSeverity};
+use writer::HVIF_MAX_LIST_LENGTH;
+
+/// The id of the next builder, which tells the handles of different builders apart
+static NEXT_BUILDER_ID: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+/// A style added to an `IconBuilder`
+pub struct StyleHandle(usize, u8);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+/// A path added to an `IconBuilder`
+pub struct PathHandle(usize, u8);
+
+impl StyleHandle {
+  /// The index of the style in the built image
+  pub fn index(&self) -> u8 { self.1 }
+}
+
+impl PathHandle {
+  /// The index of the path in the built image
+  pub fn index(&self) -> u8 { self.1 }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// Reasons an entry cannot be added to an `IconBuilder`
+pub enum HVIFBuildError {
+  /// The image, or one of its entries, would hold more entries of a kind than the format allows
+  TooMany {
+    #[doc = "What kind of entries there are too many of"] what: &'static str
+  },
+  /// A handle refers to an entry the builder does not hold, as it came from another builder
+  UnknownHandle,
+  /// A shape was given modifiers that the format cannot store together,
+  /// such as two matrices, or both a matrix and a translation
+  ConflictingModifiers,
+  /// Validating the built image found an error, the first of which is given
+  Invalid(HVIFDiagnostic),
+}
+
+impl fmt::Display for HVIFBuildError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      HVIFBuildError::TooMany { what } => write!(f, "cannot add more than {} {}", HVIF_MAX_LIST_LENGTH, what),
+      HVIFBuildError::UnknownHandle => write!(f, "the handle does not belong to this builder"),
+      HVIFBuildError::ConflictingModifiers => write!(f, "the shape has modifiers that cannot be written together"),
+      HVIFBuildError::Invalid(diagnostic) => write!(f, "invalid image, {}", diagnostic),
+    }
+  }
+}
+
+impl ::std::error::Error for HVIFBuildError {}
+
+#[derive(Debug)]
+/// Builds an HVIF image from styles, paths, and shapes drawing paths with a style
+pub struct IconBuilder {
+  id: usize,
+  styles: Vec<HVIFStyle>,
+  paths: Vec<HVIFPath>,
+  shapes: Vec<HVIFShape>,
+}
+
+impl Default for IconBuilder {
+  fn default() -> IconBuilder {
+    IconBuilder::new()
+  }
+}
+
+impl IconBuilder {
+  /// Starts an empty image
+  pub fn new() -> IconBuilder
+  {
+    IconBuilder {
+      id: NEXT_BUILDER_ID.fetch_add(1, Ordering::Relaxed),
+      styles: Vec::new(),
+      paths: Vec::new(),
+      shapes: Vec::new(),
+    }
+  }
+
+  /// Adds a style, returning its handle
+  pub fn style(&mut self, style: HVIFStyle) -> Result<StyleHandle, HVIFBuildError>
+  {
+    let index = builder_index(self.styles.len(), "styles")?;
+    if let HVIFStyle::Gradient(ref gradient) = style {
+      builder_limit(gradient.colors.len(), "colors in a gradient")?;
+    }
+    self.styles.push(style);
+    Ok(StyleHandle(self.id, index))
+  }
+
+  /// Adds a path, returning its handle
+  pub fn path(&mut self, path: HVIFPath) -> Result<PathHandle, HVIFBuildError>
+  {
+    let index = builder_index(self.paths.len(), "paths")?;
+    builder_limit(path.points.len(), "points in a path")?;
+    self.paths.push(path);
+    Ok(PathHandle(self.id, index))
+  }
+
+  /// Adds a shape drawing paths with a style, on top of the shapes added before it
+  pub fn shape(&mut self, style: StyleHandle, paths: &[PathHandle]) -> Result<(), HVIFBuildError>
+  {
+    self.shape_with_modifiers(style, paths, Vec::new())
+  }
+
+  /// Adds a shape drawing paths with a style, changed by the given modifiers, on top of the shapes added before it
+  pub fn shape_with_modifiers(&mut self, style: StyleHandle, paths: &[PathHandle], modifiers: Vec<HVIFShapeModifier>)
+    -> Result<(), HVIFBuildError>
+  {
+    builder_index(self.shapes.len(), "shapes")?;
+    builder_limit(paths.len(), "paths in a shape")?;
+    for modifier in &modifiers {
+      if let HVIFShapeModifier::HVIFTransformerList(ref transformers) = *modifier {
+        builder_limit(transformers.len(), "transformers in a shape")?;
+      }
+    }
+    if style.0 != self.id || paths.iter().any(|path| path.0 != self.id) {
+      return Err(HVIFBuildError::UnknownHandle)
+    }
+    if validate_conflicting(&modifiers) {
+      return Err(HVIFBuildError::ConflictingModifiers)
+    }
+
+    self.shapes.push(HVIFShape {
+      style_index: style.1,
+      path_indices: paths.iter().map(|path| path.1).collect(),
+      modifiers,
+    });
+    Ok(())
+  }
+
+  /// Finishes the image, after validating it as the writer does
+  ///
+  /// Images with errors are refused, while warnings, such as styles no shape uses, are left to the caller.
+  pub fn build(self) -> Result<HVIFImage, HVIFBuildError>
+  {
+    let image = HVIFImage { styles: self.styles, paths: self.paths, shapes: self.shapes, names: HVIFNames::default() };
+    match image.validate().into_iter().find(|diagnostic| diagnostic.severity == HVIFSeverity::Error) {
+      Some(diagnostic) => Err(HVIFBuildError::Invalid(diagnostic)),
+      None => Ok(image),
+    }
+  }
+}
+
+/// The index the next entry of a list would take, if the list has room for it
+fn builder_index(length: usize, what: &'static str) -> Result<u8, HVIFBuildError>
+{
+  match length < HVIF_MAX_LIST_LENGTH {
+    true  => Ok(length as u8),
+    false => Err(HVIFBuildError::TooMany { what }),
+  }
+}
+
+/// Checks that a list fits in the format, whose lengths are stored in one byte
+fn builder_limit(length: usize, what: &'static str) -> Result<(), HVIFBuildError>
+{
+  match length <= HVIF_MAX_LIST_LENGTH {
+    true  => Ok(()),
+    false => Err(HVIFBuildError::TooMany { what }),
+  }
+}
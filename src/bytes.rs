@@ -0,0 +1,49 @@
+//! Reading numbers out of byte slices, in either byte order, for the container formats icons are kept in
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// The order of the bytes of numbers in a file
+pub(crate) enum ByteOrder {
+  Little,
+  Big,
+}
+
+impl ByteOrder {
+  /// The two bytes at an offset, or nothing if the data ends first
+  pub fn u16(self, data: &[u8], offset: usize) -> Option<u16>
+  {
+    let bytes = data.get(offset..offset.checked_add(2)?)?;
+    let bytes = [bytes[0], bytes[1]];
+    Some(match self {
+      ByteOrder::Little => u16::from_le_bytes(bytes),
+      ByteOrder::Big => u16::from_be_bytes(bytes),
+    })
+  }
+
+  /// The four bytes at an offset, or nothing if the data ends first
+  pub fn u32(self, data: &[u8], offset: usize) -> Option<u32>
+  {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+    let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+    Some(match self {
+      ByteOrder::Little => u32::from_le_bytes(bytes),
+      ByteOrder::Big => u32::from_be_bytes(bytes),
+    })
+  }
+
+  /// The eight bytes at an offset, or nothing if the data ends first
+  pub fn u64(self, data: &[u8], offset: usize) -> Option<u64>
+  {
+    let low = self.u32(data, offset)? as u64;
+    let high = self.u32(data, offset + 4)? as u64;
+    Some(match self {
+      ByteOrder::Little => (high << 32) | low,
+      ByteOrder::Big => (low << 32) | high,
+    })
+  }
+}
+
+/// The bytes in a range of a slice, or nothing if the slice ends first
+pub(crate) fn slice(data: &[u8], offset: usize, length: usize) -> Option<&[u8]>
+{
+  data.get(offset..offset.checked_add(length)?)
+}
@@ -0,0 +1,125 @@
+//! Errors raised while reading HVIF images
+
+use std::error;
+use std::fmt;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// The reasons an HVIF image can fail to parse
+///
+/// Every error carries the byte offset, from the start of the image, of the data that caused it
+pub enum HVIFError {
+  /// The image does not begin with the `ncif` magic number
+  BadMagic {
+    #[doc = "Byte offset of the magic number"] offset: usize
+  },
+  /// A style has a type other than the five known style types
+  UnknownStyleType {
+    #[doc = "Byte offset of the style type"] offset: usize,
+    #[doc = "The unknown style type"] style_type: u8
+  },
+  /// A gradient has a type other than the six known gradient types
+  UnknownGradientType {
+    #[doc = "Byte offset of the gradient type"] offset: usize,
+    #[doc = "The unknown gradient type"] gradient_type: u8
+  },
+  /// A shape has a transformer other than the four known transformer types
+  UnknownTransformer {
+    #[doc = "Byte offset of the transformer type"] offset: usize,
+    #[doc = "The unknown transformer type"] transformer_type: u8
+  },
+  /// A contour or stroke transformer has a line join other than the five known line joins
+  ///
+  /// A stroke stores its line join in the low four bits of the byte at the offset
+  UnknownLineJoin {
+    #[doc = "Byte offset of the line join"] offset: usize,
+    #[doc = "The unknown line join"] line_join: u8
+  },
+  /// A stroke transformer has a line cap other than the three known line caps
+  ///
+  /// The line cap is stored in the high four bits of the byte at the offset
+  UnknownLineCap {
+    #[doc = "Byte offset of the line cap"] offset: usize,
+    #[doc = "The unknown line cap"] line_cap: u8
+  },
+  /// A shape has a type other than the path source shape type
+  BadShapeType {
+    #[doc = "Byte offset of the shape type"] offset: usize,
+    #[doc = "The unexpected shape type"] shape_type: u8
+  },
+  /// A shape refers to a style or path that the image does not have
+  IndexOutOfRange {
+    #[doc = "Byte offset of the index"] offset: usize,
+    #[doc = "What the index refers to, either a style or a path"] kind: HVIFIndexKind,
+    #[doc = "The out of range index"] index: u8,
+    #[doc = "How many entries of that kind the image has"] count: usize
+  },
+  /// The image ended before it was complete
+  Truncated {
+    #[doc = "Byte offset of the end of the input"] offset: usize,
+    #[doc = "How many more bytes are needed, if known"] needed: Option<usize>
+  },
+  /// The image is malformed in some other way
+  Malformed {
+    #[doc = "Byte offset of the malformed data"] offset: usize
+  },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// The kinds of entries a shape can refer to by index
+pub enum HVIFIndexKind {
+  /// An index into the image's styles
+  Style,
+  /// An index into the image's paths
+  Path,
+}
+
+impl HVIFError {
+  /// The byte offset, from the start of the image, at which the error occurred
+  pub fn offset(&self) -> usize {
+    match *self {
+      HVIFError::BadMagic { offset } |
+      HVIFError::UnknownStyleType { offset, .. } |
+      HVIFError::UnknownGradientType { offset, .. } |
+      HVIFError::UnknownTransformer { offset, .. } |
+      HVIFError::UnknownLineJoin { offset, .. } |
+      HVIFError::UnknownLineCap { offset, .. } |
+      HVIFError::BadShapeType { offset, .. } |
+      HVIFError::IndexOutOfRange { offset, .. } |
+      HVIFError::Truncated { offset, .. } |
+      HVIFError::Malformed { offset } => offset
+    }
+  }
+}
+
+impl fmt::Display for HVIFError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      HVIFError::BadMagic { offset } =>
+        write!(f, "missing ncif magic number at byte {}", offset),
+      HVIFError::UnknownStyleType { offset, style_type } =>
+        write!(f, "unknown style type {} at byte {}", style_type, offset),
+      HVIFError::UnknownGradientType { offset, gradient_type } =>
+        write!(f, "unknown gradient type {} at byte {}", gradient_type, offset),
+      HVIFError::UnknownTransformer { offset, transformer_type } =>
+        write!(f, "unknown transformer type {} at byte {}", transformer_type, offset),
+      HVIFError::UnknownLineJoin { offset, line_join } =>
+        write!(f, "unknown line join {} at byte {}", line_join, offset),
+      HVIFError::UnknownLineCap { offset, line_cap } =>
+        write!(f, "unknown line cap {} at byte {}", line_cap, offset),
+      HVIFError::BadShapeType { offset, shape_type } =>
+        write!(f, "unexpected shape type {} at byte {}", shape_type, offset),
+      HVIFError::IndexOutOfRange { offset, kind, index, count } => {
+        let what = match kind { HVIFIndexKind::Style => "style", HVIFIndexKind::Path => "path" };
+        write!(f, "{} index {} at byte {} is out of range, the image has {} {}s", what, index, offset, count, what)
+      },
+      HVIFError::Truncated { offset, needed: Some(needed) } =>
+        write!(f, "image truncated at byte {}, {} more bytes needed", offset, needed),
+      HVIFError::Truncated { offset, needed: None } =>
+        write!(f, "image truncated at byte {}", offset),
+      HVIFError::Malformed { offset } =>
+        write!(f, "malformed data at byte {}", offset),
+    }
+  }
+}
+
+impl error::Error for HVIFError {}
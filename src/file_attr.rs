@@ -0,0 +1,129 @@
+//! Reading and writing icons kept in file attributes on Linux
+//!
+//! Haiku keeps a file's icon in its `BEOS:ICON` attribute. Haiku's own tools, when run on Linux, keep
+//! attributes as extended attributes named `user.haiku.` and the attribute name, with the value preceded
+//! by the attribute's four byte type code in the byte order of the machine. Copies made by other tools may
+//! keep the attribute as `user.BEOS:ICON`, and may leave out the type code.
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use xattr_sys;
+
+use error::HVIFError;
+use parser::parse;
+use types::*;
+use writer::{hvif_image, HVIFWriteError};
+
+/// The extended attribute Haiku's tools keep a file's icon in
+pub const HVIF_ICON_ATTR: &str = "user.haiku.BEOS:ICON";
+
+/// Another name for the icon attribute, used by tools that keep the Haiku attribute name as it is
+pub const HVIF_ICON_ATTR_PLAIN: &str = "user.BEOS:ICON";
+
+#[derive(Debug)]
+/// Reasons an icon can fail to be read from or written to a file attribute
+pub enum HVIFAttrError {
+  /// The attribute could not be read or written
+  Io(io::Error),
+  /// The attribute is marked with a type code other than that of vector icons
+  WrongType {
+    #[doc = "The type code the attribute is marked with"] type_code: u32
+  },
+  /// The attribute does not hold a valid image, with offsets counted from the start of the image after the type code
+  Parse(HVIFError),
+  /// The image could not be written
+  Write(HVIFWriteError),
+}
+
+impl fmt::Display for HVIFAttrError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      HVIFAttrError::Io(ref err) => write!(f, "{}", err),
+      HVIFAttrError::WrongType { type_code } =>
+        write!(f, "the icon attribute has type {:#010x}, not a vector icon", type_code),
+      HVIFAttrError::Parse(err) => write!(f, "{}", err),
+      HVIFAttrError::Write(err) => write!(f, "{}", err),
+    }
+  }
+}
+
+impl error::Error for HVIFAttrError {}
+
+impl From<io::Error> for HVIFAttrError {
+  fn from(err: io::Error) -> Self { HVIFAttrError::Io(err) }
+}
+
+/// Reads the icon kept in a file's `BEOS:ICON` attribute, or nothing if the file has none
+pub fn read_icon_from_file_attr<P: AsRef<Path>>(path: P) -> Result<Option<HVIFImage>, HVIFAttrError>
+{
+  let path = path.as_ref();
+  let value = match xattr_sys::get(path, HVIF_ICON_ATTR)? {
+    Some(value) => Some(value),
+    None => xattr_sys::get(path, HVIF_ICON_ATTR_PLAIN)?,
+  };
+  match value {
+    Some(value) => file_attr_image(&value).map(Some),
+    None => Ok(None),
+  }
+}
+
+/// Writes an image into a file's `BEOS:ICON` attribute, marked with the vector icon type code as Haiku's tools do
+pub fn write_icon_to_file_attr<P: AsRef<Path>>(path: P, image: &HVIFImage) -> Result<(), HVIFAttrError>
+{
+  let data = hvif_image(image).map_err(HVIFAttrError::Write)?;
+  let mut value = Vec::with_capacity(data.len() + 4);
+  value.extend_from_slice(&HVIF_VECTOR_ICON_TYPE.to_ne_bytes());
+  value.extend_from_slice(&data);
+  xattr_sys::set(path, HVIF_ICON_ATTR, &value)?;
+  Ok(())
+}
+
+/// Parses an attribute value, after the type code preceding it, if any
+fn file_attr_image(value: &[u8]) -> Result<HVIFImage, HVIFAttrError>
+{
+  // Without a type code the value starts with the image's magic number
+  if value.starts_with(b"ncif") || value.len() < 4 {
+    return parse(value).map_err(HVIFAttrError::Parse)
+  }
+
+  // The type code was written in the byte order of the machine that wrote it, which may not be this one
+  let type_bytes = [value[0], value[1], value[2], value[3]];
+  if u32::from_le_bytes(type_bytes) != HVIF_VECTOR_ICON_TYPE && u32::from_be_bytes(type_bytes) != HVIF_VECTOR_ICON_TYPE {
+    return Err(HVIFAttrError::WrongType { type_code: u32::from_ne_bytes(type_bytes) })
+  }
+  parse(&value[4..]).map_err(HVIFAttrError::Parse)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const BEE_ICON: &[u8] = include_bytes!("../data/beeicon.hvif");
+
+  fn prefixed(type_bytes: [u8; 4]) -> Vec<u8> {
+    let mut value = type_bytes.to_vec();
+    value.extend_from_slice(BEE_ICON);
+    value
+  }
+
+  #[test]
+  fn reads_values_with_a_type_code_in_either_byte_order_or_none() {
+    let expected = format!("{:?}", parse(BEE_ICON).unwrap());
+    for value in &[prefixed(HVIF_VECTOR_ICON_TYPE.to_le_bytes()), prefixed(HVIF_VECTOR_ICON_TYPE.to_be_bytes()), BEE_ICON.to_vec()] {
+      assert_eq!(format!("{:?}", file_attr_image(value).unwrap()), expected);
+    }
+  }
+
+  #[test]
+  fn rejects_values_of_other_types() {
+    let type_code = u32::from_ne_bytes(*b"MIMS");
+    assert!(matches!(file_attr_image(&prefixed(*b"MIMS")), Err(HVIFAttrError::WrongType { type_code: code }) if code == type_code));
+    // Offsets count from the start of the image, after the type code
+    let value = prefixed(HVIF_VECTOR_ICON_TYPE.to_le_bytes());
+    assert!(matches!(file_attr_image(&value[..6]), Err(HVIFAttrError::Parse(HVIFError::Truncated { offset: 2, .. }))));
+    assert!(matches!(file_attr_image(b"nc"), Err(HVIFAttrError::Parse(_))));
+  }
+}
@@ -0,0 +1,415 @@
+//! Reader for Haiku packages, far enough to find the icons of the files they hold
+//!
+//! A package (version 2 of the format) has a header, followed by a heap of data that is compressed in chunks
+//! of 64 KiB, each with zlib or stored as it is. The heap holds the contents of the package's files, then its
+//! table of contents, then its package attributes. The table of contents is a tree of attributes, in which each
+//! directory entry names a file and holds its attributes, among them the `BEOS:ICON` attribute holding its icon.
+//! Numbers in the header and the table of contents are big-endian.
+
+use std::fmt;
+
+use bytes::{slice, ByteOrder};
+use error::HVIFError;
+use parser::parse;
+use types::*;
+use zlib;
+
+/// The magic number at the start of packages, `hpkg`
+const PACKAGE_MAGIC: u32 = 0x6870_6b67;
+/// The version of the package format that can be read
+const PACKAGE_VERSION: u16 = 2;
+/// The size of the package header
+const PACKAGE_HEADER_SIZE: usize = 80;
+
+/// Heap compression methods
+const COMPRESSION_NONE: u16 = 0;
+const COMPRESSION_ZLIB: u16 = 1;
+
+/// Attribute value types
+const ATTRIBUTE_TYPE_INT: u64 = 1;
+const ATTRIBUTE_TYPE_UINT: u64 = 2;
+const ATTRIBUTE_TYPE_STRING: u64 = 3;
+const ATTRIBUTE_TYPE_RAW: u64 = 4;
+
+/// Attribute value encodings, which depend on the type
+const ENCODING_STRING_INLINE: u64 = 0;
+const ENCODING_STRING_TABLE: u64 = 1;
+const ENCODING_RAW_INLINE: u64 = 0;
+const ENCODING_RAW_HEAP: u64 = 1;
+
+/// The ids of the attributes of the table of contents that lead to icons
+const ATTRIBUTE_ID_DIRECTORY_ENTRY: u64 = 0;
+const ATTRIBUTE_ID_FILE_ATTRIBUTE: u64 = 11;
+const ATTRIBUTE_ID_FILE_ATTRIBUTE_TYPE: u64 = 12;
+const ATTRIBUTE_ID_DATA: u64 = 13;
+
+/// The file attribute Haiku keeps icons in
+const ICON_ATTRIBUTE: &str = "BEOS:ICON";
+
+/// How deeply attributes may nest in the table of contents, far deeper than any directory tree
+const MAX_TOC_DEPTH: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Reasons a package can fail to be read
+pub enum HVIFPackageError {
+  /// The input does not begin with the `hpkg` magic number
+  NotPackage,
+  /// The package is of a version of the format other than version 2
+  UnsupportedVersion {
+    #[doc = "The version of the package"] version: u16
+  },
+  /// The package's heap is compressed with a method other than zlib
+  UnsupportedCompression {
+    #[doc = "The compression method of the heap"] compression: u16
+  },
+  /// The package ended before it was complete
+  Truncated {
+    #[doc = "Byte offset, from the start of the package, of the data that was cut short"] offset: usize
+  },
+  /// A chunk of the heap could not be decompressed
+  BadChunk {
+    #[doc = "The index of the chunk"] chunk: usize
+  },
+  /// The table of contents is malformed
+  MalformedTOC {
+    #[doc = "Byte offset, from the start of the table of contents, of the malformed data"] offset: usize
+  },
+  /// A file's icon attribute does not hold a valid image
+  Icon {
+    #[doc = "The path of the file, within the package"] path: String,
+    #[doc = "Why the image failed to parse"] error: HVIFError
+  },
+}
+
+impl fmt::Display for HVIFPackageError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      HVIFPackageError::NotPackage => write!(f, "not a Haiku package"),
+      HVIFPackageError::UnsupportedVersion { version } => write!(f, "unsupported package version {}", version),
+      HVIFPackageError::UnsupportedCompression { compression } =>
+        write!(f, "unsupported heap compression {}", compression),
+      HVIFPackageError::Truncated { offset } => write!(f, "package cut short at byte {}", offset),
+      HVIFPackageError::BadChunk { chunk } => write!(f, "heap chunk {} could not be decompressed", chunk),
+      HVIFPackageError::MalformedTOC { offset } => write!(f, "malformed table of contents at byte {}", offset),
+      HVIFPackageError::Icon { ref path, error } => write!(f, "icon of {}: {}", path, error),
+    }
+  }
+}
+
+impl ::std::error::Error for HVIFPackageError {}
+
+#[derive(Debug)]
+/// The icon of a file in a package
+pub struct HVIFPackageIcon {
+  /// The path of the file within the package, with its directories separated by slashes
+  pub path: String,
+  /// The icon
+  pub image: HVIFImage,
+}
+
+/// Reads the icon of every file in a package that has one, in the order of the table of contents
+pub fn package_icons(input: &[u8]) -> Result<Vec<HVIFPackageIcon>, HVIFPackageError>
+{
+  let order = ByteOrder::Big;
+  if order.u32(input, 0) != Some(PACKAGE_MAGIC) {
+    return Err(HVIFPackageError::NotPackage)
+  }
+  let truncated = |offset: usize| HVIFPackageError::Truncated { offset };
+  let u16_at = |offset: usize| order.u16(input, offset).ok_or_else(|| truncated(offset));
+  let u32_at = |offset: usize| order.u32(input, offset).map(|value| value as usize).ok_or_else(|| truncated(offset));
+  let u64_at = |offset: usize| order.u64(input, offset).map(|value| value as usize).ok_or_else(|| truncated(offset));
+
+  let header_size = u16_at(4)? as usize;
+  let version = u16_at(6)?;
+  if version != PACKAGE_VERSION {
+    return Err(HVIFPackageError::UnsupportedVersion { version })
+  }
+  if header_size < PACKAGE_HEADER_SIZE {
+    return Err(truncated(header_size))
+  }
+  let heap = PackageHeap::new(input, header_size, u16_at(18)?, u32_at(20)?, u64_at(24)?, u64_at(32)?)?;
+
+  // The table of contents comes just before the package attributes, at the end of the heap
+  let attributes_length = u32_at(40)?;
+  let toc_length = u64_at(56)?;
+  let toc_strings_length = u64_at(64)?;
+  let toc_strings_count = u64_at(72)?;
+  let toc_offset = heap.uncompressed_size.checked_sub(attributes_length)
+    .and_then(|end| end.checked_sub(toc_length))
+    .ok_or(HVIFPackageError::MalformedTOC { offset: 0 })?;
+  let toc = heap.read(toc_offset, toc_length)?;
+
+  let mut reader = TOCReader { toc: &toc, position: 0, strings: Vec::with_capacity(toc_strings_count.min(toc.len())) };
+  for _ in 0..toc_strings_count {
+    let string = reader.inline_string()?;
+    reader.strings.push(string);
+  }
+  reader.position = toc_strings_length;
+  let tree = reader.attributes(0)?;
+
+  let mut icons = Vec::new();
+  package_entries(&heap, &tree, "", &mut icons)?;
+  Ok(icons)
+}
+
+/// Collects the icons of the directory entries among some attributes, and of the entries within them
+fn package_entries(heap: &PackageHeap, attributes: &[TOCAttribute], directory: &str, icons: &mut Vec<HVIFPackageIcon>)
+  -> Result<(), HVIFPackageError>
+{
+  for entry in attributes.iter().filter(|attribute| attribute.id == ATTRIBUTE_ID_DIRECTORY_ENTRY) {
+    let name = match entry.value {
+      TOCValue::String(ref name) => name,
+      _ => continue,
+    };
+    let path = match directory.is_empty() {
+      true  => name.clone(),
+      false => format!("{}/{}", directory, name),
+    };
+
+    let icon = entry.children.iter().find(|attribute| {
+      attribute.id == ATTRIBUTE_ID_FILE_ATTRIBUTE && attribute.value == TOCValue::String(ICON_ATTRIBUTE.to_string())
+    });
+    if let Some(icon) = icon {
+      // Attributes with another type than vector icons, such as the bitmap icons of BeOS, are passed over
+      let is_vector_icon = icon.children.iter()
+        .filter(|attribute| attribute.id == ATTRIBUTE_ID_FILE_ATTRIBUTE_TYPE)
+        .all(|attribute| attribute.value == TOCValue::Int(HVIF_VECTOR_ICON_TYPE as u64));
+      let data = icon.children.iter().filter(|attribute| attribute.id == ATTRIBUTE_ID_DATA).filter_map(|attribute| {
+        match attribute.value {
+          TOCValue::Inline(ref data) => Some(Ok(data.clone())),
+          TOCValue::Heap { offset, size } => Some(heap.read(offset, size)),
+          _ => None,
+        }
+      }).next();
+      if let (true, Some(data)) = (is_vector_icon, data) {
+        let image = parse(&data?).map_err(|error| HVIFPackageError::Icon { path: path.clone(), error })?;
+        icons.push(HVIFPackageIcon { path: path.clone(), image });
+      }
+    }
+
+    package_entries(heap, &entry.children, &path, icons)?;
+  }
+  Ok(())
+}
+
+/// The heap of a package, decompressing the chunks that are read from
+struct PackageHeap<'a> {
+  input: &'a [u8],
+  compression: u16,
+  chunk_size: usize,
+  uncompressed_size: usize,
+  /// The offset in the input and the compressed size of each chunk
+  chunks: Vec<(usize, usize)>,
+}
+
+impl<'a> PackageHeap<'a> {
+  fn new(input: &'a [u8], offset: usize, compression: u16, chunk_size: usize, compressed_size: usize, uncompressed_size: usize)
+    -> Result<PackageHeap<'a>, HVIFPackageError>
+  {
+    if compression != COMPRESSION_NONE && compression != COMPRESSION_ZLIB {
+      return Err(HVIFPackageError::UnsupportedCompression { compression })
+    }
+    if chunk_size == 0 {
+      return Err(HVIFPackageError::Truncated { offset: 20 })
+    }
+    let end = offset.checked_add(compressed_size).filter(|&end| end <= input.len());
+    let end = end.ok_or(HVIFPackageError::Truncated { offset: input.len() })?;
+
+    // Uncompressed heaps are a plain run of chunks, while compressed ones end with a table of the sizes of
+    // every chunk but the last, each stored less one
+    let chunk_count = uncompressed_size.div_ceil(chunk_size);
+    // Every chunk takes up at least a byte of the heap, which bounds how many there can be
+    let stored_size = match compression {
+      COMPRESSION_NONE => uncompressed_size,
+      _ => chunk_count,
+    };
+    if stored_size > compressed_size {
+      return Err(HVIFPackageError::Truncated { offset: end })
+    }
+    let mut chunks = Vec::with_capacity(chunk_count);
+    match compression {
+      COMPRESSION_NONE => {
+        for index in 0..chunk_count {
+          chunks.push((offset + index * chunk_size, chunk_size.min(uncompressed_size - index * chunk_size)));
+        }
+      },
+      _ => {
+        let table_size = chunk_count.saturating_sub(1).checked_mul(2);
+        let table = table_size.and_then(|size| end.checked_sub(size)).filter(|&table| table >= offset);
+        let table = table.ok_or(HVIFPackageError::Truncated { offset: end })?;
+        let mut chunk_offset = offset;
+        for index in 0..chunk_count {
+          let size = match index + 1 == chunk_count {
+            true  => table.checked_sub(chunk_offset).ok_or(HVIFPackageError::BadChunk { chunk: index })?,
+            false => ByteOrder::Big.u16(input, table + index * 2).unwrap_or(0) as usize + 1,
+          };
+          chunks.push((chunk_offset, size));
+          chunk_offset = chunk_offset.checked_add(size).ok_or(HVIFPackageError::BadChunk { chunk: index })?;
+        }
+      },
+    }
+    Ok(PackageHeap {
+      input, compression, chunk_size, uncompressed_size, chunks,
+    })
+  }
+
+  /// Reads a run of the uncompressed heap
+  fn read(&self, offset: usize, length: usize) -> Result<Vec<u8>, HVIFPackageError>
+  {
+    let end = offset.checked_add(length).filter(|&end| end <= self.uncompressed_size);
+    let end = end.ok_or(HVIFPackageError::Truncated { offset: self.input.len() })?;
+    // The length is only trusted as far as the input could hold it
+    let mut out = Vec::with_capacity(length.min(self.input.len()));
+    let mut position = offset;
+    while position < end {
+      let index = position / self.chunk_size;
+      let chunk = self.chunk(index)?;
+      let start = position - index * self.chunk_size;
+      let stop = chunk.len().min(end - index * self.chunk_size);
+      if start >= stop {
+        return Err(HVIFPackageError::BadChunk { chunk: index })
+      }
+      out.extend_from_slice(&chunk[start..stop]);
+      position = index * self.chunk_size + stop;
+    }
+    Ok(out)
+  }
+
+  fn chunk(&self, index: usize) -> Result<Vec<u8>, HVIFPackageError>
+  {
+    let (offset, size) = self.chunks[index];
+    let data = slice(self.input, offset, size).ok_or(HVIFPackageError::Truncated { offset })?;
+    let uncompressed_size = self.chunk_size.min(self.uncompressed_size - index * self.chunk_size);
+    // Chunks that compression would not shrink are stored as they are
+    if self.compression == COMPRESSION_NONE || size == uncompressed_size {
+      return Ok(data.to_vec())
+    }
+    match zlib::decompress(data) {
+      Some(chunk) if chunk.len() == uncompressed_size => Ok(chunk),
+      _ => Err(HVIFPackageError::BadChunk { chunk: index }),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// The value of an attribute in the table of contents
+enum TOCValue {
+  /// Signed and unsigned numbers alike
+  Int(u64),
+  String(String),
+  /// Data stored in the table of contents itself
+  Inline(Vec<u8>),
+  /// Data stored elsewhere in the heap
+  Heap { offset: usize, size: usize },
+}
+
+#[derive(Debug)]
+/// An attribute in the table of contents, with the attributes it holds
+struct TOCAttribute {
+  id: u64,
+  value: TOCValue,
+  children: Vec<TOCAttribute>,
+}
+
+struct TOCReader<'a> {
+  toc: &'a [u8],
+  position: usize,
+  strings: Vec<String>,
+}
+
+impl<'a> TOCReader<'a> {
+  fn malformed(&self) -> HVIFPackageError
+  {
+    HVIFPackageError::MalformedTOC { offset: self.position }
+  }
+
+  fn byte(&mut self) -> Result<u8, HVIFPackageError>
+  {
+    let byte = *self.toc.get(self.position).ok_or_else(|| self.malformed())?;
+    self.position += 1;
+    Ok(byte)
+  }
+
+  fn bytes(&mut self, length: usize) -> Result<&'a [u8], HVIFPackageError>
+  {
+    let bytes = slice(self.toc, self.position, length).ok_or_else(|| self.malformed())?;
+    self.position += length;
+    Ok(bytes)
+  }
+
+  /// An unsigned LEB128 number, seven bits to a byte from the least significant, while the high bit is set
+  fn number(&mut self) -> Result<u64, HVIFPackageError>
+  {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+      let byte = self.byte()?;
+      value |= ((byte & 0x7f) as u64) << shift;
+      if byte & 0x80 == 0 {
+        return Ok(value)
+      }
+    }
+    Err(self.malformed())
+  }
+
+  /// A string ended by a zero byte
+  fn inline_string(&mut self) -> Result<String, HVIFPackageError>
+  {
+    let length = self.toc[self.position.min(self.toc.len())..].iter().position(|&byte| byte == 0).ok_or_else(|| self.malformed())?;
+    let string = String::from_utf8_lossy(self.bytes(length)?).into_owned();
+    self.position += 1;
+    Ok(string)
+  }
+
+  /// A list of attributes, ended by a zero tag, held by as many attributes as its depth
+  fn attributes(&mut self, depth: usize) -> Result<Vec<TOCAttribute>, HVIFPackageError>
+  {
+    if depth > MAX_TOC_DEPTH {
+      return Err(self.malformed())
+    }
+    let mut attributes = Vec::new();
+    loop {
+      // Each tag packs the id above six bits, a three bit type, a two bit encoding, and whether children follow,
+      // plus one so that zero can end the list, as HPKG_ATTRIBUTE_TAG_COMPOSE in Haiku's HPKGDefsPrivate.h does
+      let tag = match self.number()? {
+        0 => return Ok(attributes),
+        tag => tag - 1,
+      };
+      let (id, value_type, encoding, has_children) = (tag >> 6, (tag >> 3) & 0x7, (tag >> 1) & 0x3, tag & 1 == 1);
+      let value = self.value(value_type, encoding)?;
+      let children = match has_children {
+        true  => self.attributes(depth + 1)?,
+        false => Vec::new(),
+      };
+      attributes.push(TOCAttribute { id, value, children });
+    }
+  }
+
+  fn value(&mut self, value_type: u64, encoding: u64) -> Result<TOCValue, HVIFPackageError>
+  {
+    match (value_type, encoding) {
+      // Numbers take one, two, four or eight bytes
+      (ATTRIBUTE_TYPE_INT, _) | (ATTRIBUTE_TYPE_UINT, _) => {
+        let bytes = self.bytes(1 << encoding)?;
+        Ok(TOCValue::Int(bytes.iter().fold(0u64, |value, &byte| (value << 8) | byte as u64)))
+      },
+      (ATTRIBUTE_TYPE_STRING, ENCODING_STRING_INLINE) => Ok(TOCValue::String(self.inline_string()?)),
+      (ATTRIBUTE_TYPE_STRING, ENCODING_STRING_TABLE) => {
+        let index = self.number()? as usize;
+        match self.strings.get(index) {
+          Some(string) => Ok(TOCValue::String(string.clone())),
+          None => Err(self.malformed()),
+        }
+      },
+      (ATTRIBUTE_TYPE_RAW, ENCODING_RAW_INLINE) => {
+        let size = self.number()? as usize;
+        Ok(TOCValue::Inline(self.bytes(size)?.to_vec()))
+      },
+      (ATTRIBUTE_TYPE_RAW, ENCODING_RAW_HEAP) => {
+        let size = self.number()? as usize;
+        let offset = self.number()? as usize;
+        Ok(TOCValue::Heap { offset, size })
+      },
+      _ => Err(self.malformed()),
+    }
+  }
+}
@@ -0,0 +1,27 @@
+//! Conversion between HVIF images and Icon-O-Matic documents
+//!
+//! Icon-O-Matic keeps the icons it edits in its own format: the magic number `IMSG`, followed by a flattened
+//! BMessage archiving the icon. Unlike HVIF, which Icon-O-Matic exports to, the archive keeps the names of
+//! the styles, paths and shapes, and stores coordinates and transformations at full precision.
+pub mod reader;
+pub mod writer;
+
+pub use self::reader::{parse, IMSGError};
+pub use self::writer::imsg_image;
+
+/// The magic number at the start of Icon-O-Matic documents
+const IMSG_MAGIC: &[u8] = b"IMSG";
+
+/// The archive code of affine transformers, `'affn'`, which is the `what` of their messages
+const IMSG_AFFINE_TRANSFORMER: u32 = 0x6166_666e;
+/// The archive code of contour transformers, `'cntr'`
+const IMSG_CONTOUR_TRANSFORMER: u32 = 0x636e_7472;
+/// The archive code of perspective transformers, `'prsp'`
+const IMSG_PERSPECTIVE_TRANSFORMER: u32 = 0x7072_7370;
+/// The archive code of stroke transformers, `'strk'`
+const IMSG_STROKE_TRANSFORMER: u32 = 0x7374_726b;
+
+/// The smallest scale a shape shows at, unless it says otherwise
+const IMSG_DEFAULT_MIN_VISIBILITY_SCALE: f32 = 0.0;
+/// The largest scale a shape shows at, unless it says otherwise
+const IMSG_DEFAULT_MAX_VISIBILITY_SCALE: f32 = 4.0;
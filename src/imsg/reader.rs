@@ -0,0 +1,281 @@
+//! Reader for Icon-O-Matic documents
+//!
+//! The archive holds a message of paths, one of styles and one of shapes, each holding a nested message per entry.
+//! A path keeps each point with its two control points, a style keeps its color or a gradient, and a shape keeps
+//! the indices of its paths and style, its transformation, its transformers and the scales it shows between.
+//! Coordinates are narrowed to 32 bit floats, and transformations to HVIF's matrices, as HVIF images hold them.
+//! Shapes without a style, such as the reference images newer versions of Icon-O-Matic can hold, are left out.
+
+use std::fmt;
+
+use types::*;
+
+use bmessage::{BMessage, BMessageError, B_DOUBLE_TYPE};
+use error::HVIFIndexKind;
+use writer::HVIF_MAX_LIST_LENGTH;
+
+use imsg::*;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// Reasons an Icon-O-Matic document can fail to be read
+pub enum IMSGError {
+  /// The document does not begin with the `IMSG` magic number
+  NotIMSG,
+  /// The archive is not a well-formed flattened message
+  Malformed {
+    #[doc = "Byte offset, from the start of the document, of the malformed data"] offset: usize
+  },
+  /// An entry lacks a field it cannot do without, such as the color of a style
+  Missing {
+    #[doc = "The name of the missing field"] field: &'static str
+  },
+  /// The document has more entries of a kind than an HVIF image can hold
+  TooMany {
+    #[doc = "What there are too many of"] what: &'static str
+  },
+  /// A shape refers to a style or path that the document does not have
+  IndexOutOfRange {
+    #[doc = "What the index refers to, either a style or a path"] kind: HVIFIndexKind,
+    #[doc = "The out of range index"] index: i32
+  },
+  /// A gradient has a type other than the six known gradient types
+  UnknownGradientType {
+    #[doc = "The unknown gradient type"] gradient_type: i32
+  },
+  /// A transformer has an archive code other than the four known transformer codes
+  UnknownTransformer {
+    #[doc = "The unknown archive code"] what: u32
+  },
+}
+
+impl fmt::Display for IMSGError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      IMSGError::NotIMSG => write!(f, "the document is not an Icon-O-Matic document"),
+      IMSGError::Malformed { offset } => write!(f, "malformed message at byte {}", offset),
+      IMSGError::Missing { field } => write!(f, "missing field \"{}\"", field),
+      IMSGError::TooMany { what } => write!(f, "too many {} for an HVIF image", what),
+      IMSGError::IndexOutOfRange { kind, index } => write!(f, "{:?} index {} out of range", kind, index),
+      IMSGError::UnknownGradientType { gradient_type } => write!(f, "unknown gradient type {}", gradient_type),
+      IMSGError::UnknownTransformer { what } => write!(f, "unknown transformer {:#010x}", what),
+    }
+  }
+}
+
+impl ::std::error::Error for IMSGError {}
+
+/// Reads an Icon-O-Matic document into an HVIF image, along with the names of its entries
+pub fn parse(input: &[u8]) -> Result<HVIFImage, IMSGError>
+{
+  if !input.starts_with(IMSG_MAGIC) {
+    return Err(IMSGError::NotIMSG)
+  }
+  let archive = BMessage::unflatten(&input[IMSG_MAGIC.len()..]).map_err(|error| {
+    let offset = match error {
+      BMessageError::Truncated { offset } | BMessageError::Malformed { offset } => offset,
+      _ => 0,
+    };
+    IMSGError::Malformed { offset: IMSG_MAGIC.len() + offset }
+  })?;
+
+  let mut image = HVIFImage { styles: Vec::new(), paths: Vec::new(), shapes: Vec::new(), names: HVIFNames::default() };
+  for path in imsg_entries(&archive, "paths", "path") {
+    image.names.paths.push(imsg_name(path));
+    image.paths.push(imsg_path(path));
+  }
+  for style in imsg_entries(&archive, "styles", "style") {
+    image.names.styles.push(imsg_name(style));
+    image.styles.push(imsg_style(style)?);
+  }
+  for shape in imsg_entries(&archive, "shapes", "shape") {
+    if shape.find_int32("style ref", 0).is_none() {
+      continue
+    }
+    image.names.shapes.push(imsg_name(shape));
+    image.shapes.push(imsg_shape(shape, image.styles.len(), image.paths.len())?);
+  }
+
+  if image.styles.len() > HVIF_MAX_LIST_LENGTH {
+    return Err(IMSGError::TooMany { what: "styles" })
+  }
+  if image.paths.len() > HVIF_MAX_LIST_LENGTH {
+    return Err(IMSGError::TooMany { what: "paths" })
+  }
+  if image.shapes.len() > HVIF_MAX_LIST_LENGTH {
+    return Err(IMSGError::TooMany { what: "shapes" })
+  }
+  Ok(image)
+}
+
+/// The messages of the entries of one kind, held by the message of that kind, which may be left out when empty
+fn imsg_entries<'a>(archive: &'a BMessage, list: &str, entry: &'a str) -> impl Iterator<Item = &'a BMessage>
+{
+  let entries = archive.find_message(list, 0);
+  (0..entries.map_or(0, |entries| entries.values(entry).len()))
+    .filter_map(move |index| entries.and_then(|entries| entries.find_message(entry, index)))
+}
+
+/// The name of an entry, which is empty if it has none
+fn imsg_name(entry: &BMessage) -> String
+{
+  entry.find_string("name", 0).unwrap_or("").to_string()
+}
+
+/// Reads a path, making a straight line of every point whose control points lie on it
+fn imsg_path(path: &BMessage) -> HVIFPath
+{
+  let point_at = |name: &str, index: usize| path.find_point(name, index).map(|(x, y)| HVIFPoint { x, y });
+  let mut points = Vec::new();
+  for index in 0..path.values("point").len() {
+    let point = match point_at("point", index) {
+      Some(point) => point,
+      None => continue,
+    };
+    let point_in = point_at("point in", index).unwrap_or(point);
+    let point_out = point_at("point out", index).unwrap_or(point);
+    let straight = imsg_same_point(point_in, point) && imsg_same_point(point_out, point);
+    points.push(match straight {
+      true  => HVIFPointCommand::Line { point },
+      false => HVIFPointCommand::Curve { point_in, point, point_out },
+    });
+  }
+  HVIFPath { closed: path.find_bool("path closed", 0).unwrap_or(false), points }
+}
+
+fn imsg_same_point(a: HVIFPoint, b: HVIFPoint) -> bool
+{
+  a.x == b.x && a.y == b.y
+}
+
+/// Reads a style, choosing the most compact kind of solid color that holds its color, as Icon-O-Matic exports it
+fn imsg_style(style: &BMessage) -> Result<HVIFStyle, IMSGError>
+{
+  if let Some(gradient) = style.find_message("gradient", 0) {
+    return Ok(HVIFStyle::Gradient(imsg_gradient(gradient)?))
+  }
+  let [red, green, blue, alpha] = style.find_color("color", 0).ok_or(IMSGError::Missing { field: "color" })?;
+  let gray = red == green && green == blue;
+  Ok(match (gray, alpha) {
+    (true, 255) => HVIFStyle::SolidGrayNoAlpha { value: red },
+    (true, _) => HVIFStyle::SolidGray { value: red, alpha },
+    (false, 255) => HVIFStyle::SolidColorNoAlpha { red, green, blue },
+    (false, _) => HVIFStyle::SolidColor { red, green, blue, alpha },
+  })
+}
+
+/// Reads a gradient, whose stops are offsets from zero to one
+fn imsg_gradient(gradient: &BMessage) -> Result<HVIFGradient, IMSGError>
+{
+  let type_number = gradient.find_int32("type", 0).unwrap_or(0);
+  let gradient_type = match type_number {
+    0..=255 => gradient_type_from_u8(type_number as u8),
+    _ => None,
+  }.ok_or(IMSGError::UnknownGradientType { gradient_type: type_number })?;
+
+  let mut colors = Vec::new();
+  for index in 0..gradient.values("color").len() {
+    let [red, green, blue, alpha] = imsg_stop_color(gradient, index).ok_or(IMSGError::Missing { field: "color" })?;
+    let offset = gradient.find_float("offset", index).ok_or(IMSGError::Missing { field: "offset" })?;
+    colors.push(HVIFGradientColor {
+      stop_offset: (offset.clamp(0.0, 1.0) * 255.0).round() as u8,
+      alpha, red, green, blue,
+    });
+  }
+  Ok(HVIFGradient { gradient_type, transform: imsg_transformation(gradient), colors })
+}
+
+/// The color of a gradient stop, which Haiku archives as the bytes of its `rgb_color` taken as a 32 bit integer
+///
+/// The bytes are unpacked as red, green, blue and alpha in the memory order of the x86 machines Haiku mostly runs on.
+fn imsg_stop_color(gradient: &BMessage, index: usize) -> Option<[u8; 4]>
+{
+  gradient.find_int32("color", index).map(i32::to_le_bytes)
+}
+
+/// The transformation of a gradient, shape or affine transformer, if it has one other than the identity
+///
+/// It is archived as six doubles in one item, which is kept as data.
+fn imsg_transformation(message: &BMessage) -> Option<HVIFMatrix>
+{
+  let data = message.find_data("transformation", B_DOUBLE_TYPE, 0)?;
+  if data.len() != 48 {
+    return None
+  }
+  let mut values = [0.0; 6];
+  for (value, double) in values.iter_mut().zip(data.chunks(8)) {
+    let bytes = [double[0], double[1], double[2], double[3], double[4], double[5], double[6], double[7]];
+    *value = f64::from_le_bytes(bytes) as f32;
+  }
+  match values == [1.0, 0.0, 0.0, 1.0, 0.0, 0.0] {
+    true  => None,
+    false => Some(HVIFMatrix::from(values)),
+  }
+}
+
+/// Reads a shape, whose modifiers are ordered as the HVIF parser orders them
+///
+/// A transformation that only moves the shape becomes a translation, and scales other than the defaults become
+/// a level of detail range.
+fn imsg_shape(shape: &BMessage, style_count: usize, path_count: usize) -> Result<HVIFShape, IMSGError>
+{
+  let index = |index: i32, kind: HVIFIndexKind, count: usize| match index >= 0 && (index as usize) < count {
+    true  => Ok(index as u8),
+    false => Err(IMSGError::IndexOutOfRange { kind, index }),
+  };
+  let style_index = index(shape.find_int32("style ref", 0).unwrap_or(-1), HVIFIndexKind::Style, style_count)?;
+  let path_indices = (0..shape.values("path ref").len())
+    .map(|position| index(shape.find_int32("path ref", position).unwrap_or(-1), HVIFIndexKind::Path, path_count))
+    .collect::<Result<Vec<u8>, IMSGError>>()?;
+
+  let mut modifiers = Vec::new();
+  if shape.find_bool("hinting", 0).unwrap_or(false) {
+    modifiers.push(HVIFShapeModifier::HVIFHinting);
+  }
+  if let Some(matrix) = imsg_transformation(shape) {
+    let values: [f32; 6] = matrix.into();
+    modifiers.push(match values[..4] == [1.0, 0.0, 0.0, 1.0] {
+      true  => HVIFShapeModifier::HVIFTranslation(HVIFPoint { x: values[4], y: values[5] }),
+      false => HVIFShapeModifier::HVIFTransformMatrix(matrix),
+    });
+  }
+  let min = shape.find_float("min visibility scale", 0).unwrap_or(IMSG_DEFAULT_MIN_VISIBILITY_SCALE);
+  let max = shape.find_float("max visibility scale", 0).unwrap_or(IMSG_DEFAULT_MAX_VISIBILITY_SCALE);
+  if min != IMSG_DEFAULT_MIN_VISIBILITY_SCALE || max != IMSG_DEFAULT_MAX_VISIBILITY_SCALE {
+    modifiers.push(HVIFShapeModifier::HVIFLODScale { min, max });
+  }
+  let transformers = (0..shape.values("transformer").len())
+    .filter_map(|index| shape.find_message("transformer", index))
+    .map(imsg_transformer)
+    .collect::<Result<Vec<HVIFTransformer>, IMSGError>>()?;
+  if !transformers.is_empty() {
+    modifiers.push(HVIFShapeModifier::HVIFTransformerList(transformers));
+  }
+
+  Ok(HVIFShape { style_index, path_indices, modifiers })
+}
+
+/// Reads a transformer, told apart by the archive code of its message
+fn imsg_transformer(transformer: &BMessage) -> Result<HVIFTransformer, IMSGError>
+{
+  let width = transformer.find_double("width", 0).unwrap_or(1.0) as f32;
+  let line_join = transformer.find_int32("line join", 0)
+    .and_then(|join| line_join_from_u8(join as u8))
+    .unwrap_or(HVIFLineJoin::Miter);
+  let line_cap = transformer.find_int32("line cap", 0)
+    .and_then(|cap| line_cap_from_u8(cap as u8))
+    .unwrap_or(HVIFLineCap::Butt);
+  let miter_limit = transformer.find_double("miter limit", 0).unwrap_or(4.0).clamp(0.0, 255.0).round() as u8;
+
+  match transformer.what {
+    IMSG_AFFINE_TRANSFORMER => {
+      let identity = HVIFMatrix::from([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+      Ok(HVIFTransformer::Affine(imsg_transformation(transformer).unwrap_or(identity)))
+    },
+    IMSG_CONTOUR_TRANSFORMER => Ok(HVIFTransformer::Contour { width, line_join, miter_limit }),
+    IMSG_PERSPECTIVE_TRANSFORMER => Ok(HVIFTransformer::Perspective),
+    IMSG_STROKE_TRANSFORMER => Ok(HVIFTransformer::Stroke {
+      width, line_join, line_cap, miter_limit
+    }),
+    what => Err(IMSGError::UnknownTransformer { what }),
+  }
+}
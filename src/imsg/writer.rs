@@ -0,0 +1,226 @@
+//! Writer for Icon-O-Matic documents
+//!
+//! Every entry is archived with the fields Icon-O-Matic itself writes, so that it opens the document for editing.
+//! A style with a gradient also keeps a solid color, that of the gradient's first stop, for when the gradient
+//! is taken off. A point's control points are marked as connected when they lie on a line through the point,
+//! so that moving one in Icon-O-Matic moves the other.
+
+use types::*;
+
+use bmessage::{BMessage, BMessageValue, B_DOUBLE_TYPE};
+
+use imsg::*;
+
+/// The transformation that leaves everything as it is, in storage order
+const IMSG_IDENTITY: [f64; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+/// Icon-O-Matic's smooth interpolation between the colors of a gradient, which it gives every gradient by default
+const IMSG_INTERPOLATION_SMOOTH: i32 = 1;
+
+/// Writes an entire HVIF image as an Icon-O-Matic document, naming its entries after the image's names
+pub fn imsg_image(image: &HVIFImage) -> Vec<u8>
+{
+  let mut paths = BMessage::new(0);
+  for (index, path) in image.paths.iter().enumerate() {
+    imsg_add(&mut paths, "path", BMessageValue::Message(imsg_path(path, imsg_name(&image.names.paths, index))));
+  }
+  let mut styles = BMessage::new(0);
+  for (index, style) in image.styles.iter().enumerate() {
+    imsg_add(&mut styles, "style", BMessageValue::Message(imsg_style(style, imsg_name(&image.names.styles, index))));
+  }
+  let mut shapes = BMessage::new(0);
+  for (index, shape) in image.shapes.iter().enumerate() {
+    imsg_add(&mut shapes, "shape", BMessageValue::Message(imsg_shape(shape, imsg_name(&image.names.shapes, index))));
+  }
+
+  let mut archive = BMessage::new(0);
+  imsg_add(&mut archive, "paths", BMessageValue::Message(paths));
+  imsg_add(&mut archive, "styles", BMessageValue::Message(styles));
+  imsg_add(&mut archive, "shapes", BMessageValue::Message(shapes));
+
+  let mut out = IMSG_MAGIC.to_vec();
+  out.extend_from_slice(&archive.flatten());
+  out
+}
+
+/// Adds a value to an archive, which never gives one name values of two types, so that adding cannot fail
+fn imsg_add(message: &mut BMessage, name: &str, value: BMessageValue)
+{
+  let added = message.add(name, value);
+  debug_assert!(added.is_ok(), "field {} given values of two types", name);
+}
+
+/// A color, from its red, green, blue and alpha channels
+fn imsg_color([red, green, blue, alpha]: [u8; 4]) -> BMessageValue
+{
+  BMessageValue::Color { red, green, blue, alpha }
+}
+
+/// The color of a gradient stop, which Haiku archives as the bytes of its `rgb_color` taken as a 32 bit integer
+fn imsg_stop_color(channels: [u8; 4]) -> BMessageValue
+{
+  BMessageValue::Int32(i32::from_le_bytes(channels))
+}
+
+/// Six doubles in one item, as transformations are archived, which is kept as data
+fn imsg_doubles(values: [f64; 6]) -> BMessageValue
+{
+  let data = values.iter().flat_map(|value| value.to_le_bytes().to_vec()).collect();
+  BMessageValue::Data { type_code: B_DOUBLE_TYPE, data }
+}
+
+/// The name of an entry, which is empty if it has none
+fn imsg_name(names: &[String], index: usize) -> &str
+{
+  names.get(index).map_or("", String::as_str)
+}
+
+/// A message for an entry, with its name if it has one
+fn imsg_entry(what: u32, name: &str) -> BMessage
+{
+  let mut message = BMessage::new(what);
+  if !name.is_empty() {
+    imsg_add(&mut message, "name", BMessageValue::String(name.to_string()));
+  }
+  message
+}
+
+fn imsg_path(path: &HVIFPath, name: &str) -> BMessage
+{
+  let mut message = imsg_entry(0, name);
+  let vertices = path.vertices();
+  for vertex in &vertices {
+    imsg_add(&mut message, "point", BMessageValue::Point { x: vertex.point.x, y: vertex.point.y });
+  }
+  for vertex in &vertices {
+    imsg_add(&mut message, "point in", BMessageValue::Point { x: vertex.point_in.x, y: vertex.point_in.y });
+  }
+  for vertex in &vertices {
+    imsg_add(&mut message, "point out", BMessageValue::Point { x: vertex.point_out.x, y: vertex.point_out.y });
+  }
+  for vertex in &vertices {
+    imsg_add(&mut message, "connected", BMessageValue::Bool(imsg_connected(vertex)));
+  }
+  imsg_add(&mut message, "path closed", BMessageValue::Bool(path.closed));
+  message
+}
+
+/// Whether both control points of a vertex lie away from it, on opposite sides of a line through it
+fn imsg_connected(vertex: &HVIFVertex) -> bool
+{
+  let (in_x, in_y) = (vertex.point_in.x - vertex.point.x, vertex.point_in.y - vertex.point.y);
+  let (out_x, out_y) = (vertex.point_out.x - vertex.point.x, vertex.point_out.y - vertex.point.y);
+  let (in_length, out_length) = (in_x.hypot(in_y), out_x.hypot(out_y));
+  if in_length == 0.0 || out_length == 0.0 {
+    return false
+  }
+  let cross = (in_x * out_y - in_y * out_x) / (in_length * out_length);
+  let dot = in_x * out_x + in_y * out_y;
+  cross.abs() < 1.0e-3 && dot < 0.0
+}
+
+fn imsg_style(style: &HVIFStyle, name: &str) -> BMessage
+{
+  let mut message = imsg_entry(0, name);
+  let color = match *style {
+    HVIFStyle::SolidColor { red, green, blue, alpha } => [red, green, blue, alpha],
+    HVIFStyle::SolidColorNoAlpha { red, green, blue } => [red, green, blue, 255],
+    HVIFStyle::SolidGray { value, alpha } => [value, value, value, alpha],
+    HVIFStyle::SolidGrayNoAlpha { value } => [value, value, value, 255],
+    HVIFStyle::Gradient(ref gradient) =>
+      gradient.colors.first().map_or([0, 0, 0, 255], |color| [color.red, color.green, color.blue, color.alpha]),
+  };
+  imsg_add(&mut message, "color", imsg_color(color));
+  if let HVIFStyle::Gradient(ref gradient) = *style {
+    imsg_add(&mut message, "gradient", BMessageValue::Message(imsg_gradient(gradient)));
+  }
+  message
+}
+
+fn imsg_gradient(gradient: &HVIFGradient) -> BMessage
+{
+  let mut message = BMessage::new(0);
+  imsg_add(&mut message, "transformation", imsg_doubles(gradient.transform.map_or(IMSG_IDENTITY, imsg_matrix)));
+  for color in &gradient.colors {
+    imsg_add(&mut message, "color", imsg_stop_color([color.red, color.green, color.blue, color.alpha]));
+    imsg_add(&mut message, "offset", BMessageValue::Float(color.stop_offset as f32 / 255.0));
+  }
+  imsg_add(&mut message, "type", BMessageValue::Int32(gradient.gradient_type as i32));
+  imsg_add(&mut message, "interpolation", BMessageValue::Int32(IMSG_INTERPOLATION_SMOOTH));
+  imsg_add(&mut message, "inherit transformation", BMessageValue::Bool(true));
+  message
+}
+
+fn imsg_matrix(matrix: HVIFMatrix) -> [f64; 6]
+{
+  let values: [f32; 6] = matrix.into();
+  let mut doubles = [0.0; 6];
+  for (double, &value) in doubles.iter_mut().zip(&values) {
+    *double = value as f64;
+  }
+  doubles
+}
+
+/// Archives a shape, whose transformation is its matrix or its translation
+fn imsg_shape(shape: &HVIFShape, name: &str) -> BMessage
+{
+  let mut message = imsg_entry(0, name);
+  let mut transformation = IMSG_IDENTITY;
+  let mut hinting = false;
+  let (mut min, mut max) = (IMSG_DEFAULT_MIN_VISIBILITY_SCALE, IMSG_DEFAULT_MAX_VISIBILITY_SCALE);
+  let mut transformers = Vec::new();
+  for modifier in &shape.modifiers {
+    match *modifier {
+      HVIFShapeModifier::HVIFTransformMatrix(matrix) => transformation = imsg_matrix(matrix),
+      HVIFShapeModifier::HVIFTranslation(offset) => {
+        transformation = [1.0, 0.0, 0.0, 1.0, offset.x as f64, offset.y as f64];
+      },
+      HVIFShapeModifier::HVIFHinting => hinting = true,
+      HVIFShapeModifier::HVIFLODScale { min: shape_min, max: shape_max } => {
+        min = shape_min;
+        max = shape_max;
+      },
+      HVIFShapeModifier::HVIFTransformerList(ref list) => transformers.extend(list.iter().map(imsg_transformer)),
+    }
+  }
+
+  imsg_add(&mut message, "transformation", imsg_doubles(transformation));
+  imsg_add(&mut message, "hinting", BMessageValue::Bool(hinting));
+  imsg_add(&mut message, "min visibility scale", BMessageValue::Float(min));
+  imsg_add(&mut message, "max visibility scale", BMessageValue::Float(max));
+  for transformer in transformers {
+    imsg_add(&mut message, "transformer", BMessageValue::Message(transformer));
+  }
+  for &path_index in &shape.path_indices {
+    imsg_add(&mut message, "path ref", BMessageValue::Int32(path_index as i32));
+  }
+  imsg_add(&mut message, "style ref", BMessageValue::Int32(shape.style_index as i32));
+  message
+}
+
+/// Archives a transformer, told apart by the archive code of its message
+fn imsg_transformer(transformer: &HVIFTransformer) -> BMessage
+{
+  match *transformer {
+    HVIFTransformer::Affine(matrix) => {
+      let mut message = BMessage::new(IMSG_AFFINE_TRANSFORMER);
+      imsg_add(&mut message, "transformation", imsg_doubles(imsg_matrix(matrix)));
+      message
+    },
+    HVIFTransformer::Contour { width, line_join, miter_limit } => {
+      let mut message = BMessage::new(IMSG_CONTOUR_TRANSFORMER);
+      imsg_add(&mut message, "width", BMessageValue::Double(width as f64));
+      imsg_add(&mut message, "line join", BMessageValue::Int32(line_join as i32));
+      imsg_add(&mut message, "miter limit", BMessageValue::Double(miter_limit as f64));
+      message
+    },
+    HVIFTransformer::Perspective => BMessage::new(IMSG_PERSPECTIVE_TRANSFORMER),
+    HVIFTransformer::Stroke { width, line_join, line_cap, miter_limit } => {
+      let mut message = BMessage::new(IMSG_STROKE_TRANSFORMER);
+      imsg_add(&mut message, "width", BMessageValue::Double(width as f64));
+      imsg_add(&mut message, "line cap", BMessageValue::Int32(line_cap as i32));
+      imsg_add(&mut message, "line join", BMessageValue::Int32(line_join as i32));
+      imsg_add(&mut message, "miter limit", BMessageValue::Double(miter_limit as f64));
+      message
+    },
+  }
+}
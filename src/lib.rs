@@ -22,6 +22,33 @@ extern crate collections;
 
 #[macro_use]
 extern crate nom;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "xattr")]
+extern crate xattr as xattr_sys;
 
+pub mod error;
 #[macro_use] pub mod types;
 #[macro_use] pub mod parser;
+pub mod writer;
+pub mod builder;
+pub mod validate;
+pub mod view;
+pub mod render;
+pub mod svg;
+pub mod png;
+#[cfg(feature = "xattr")]
+pub mod file_attr;
+pub mod resources;
+pub mod hpkg;
+pub mod imsg;
+pub mod bmessage;
+mod bytes;
+mod zlib;
+
+pub use error::HVIFError;
+pub use builder::IconBuilder;
+pub use view::HVIFView;
+pub use parser::{parse, parse_validated, parse_layout};
+#[cfg(feature = "xattr")]
+pub use file_attr::{read_icon_from_file_attr, write_icon_to_file_attr};
@@ -0,0 +1,210 @@
+//! Push decoder for HVIF images that arrive a chunk at a time
+//!
+//! Bytes are fed to the decoder as they arrive, from a socket or an archive stream, and each style, path and shape
+//! is handed back as soon as all of its bytes are in. Until then, the decoder says how many more bytes it needs to
+//! go on. That is never more than the entry needs, but an entry whose length depends on bytes not yet fed, such as
+//! a path whose coordinates may each take one byte or two, asks again for the rest once they arrive.
+
+use nom::{IResult, Needed, be_u8};
+use error::*;
+use types::*;
+
+use parser::*;
+
+#[derive(Debug)]
+/// What the decoder made of the bytes fed to it so far
+pub enum HVIFDecoded {
+  /// The next style of the image
+  Style(HVIFStyle),
+  /// The next path of the image, once every style has been decoded
+  Path(HVIFPath),
+  /// The next shape of the image, once every path has been decoded
+  Shape(HVIFShape),
+  /// More bytes must be fed before anything else can be decoded
+  NeedMore {
+    #[doc = "The fewest bytes that must be fed before the decoder can go on"] needed: usize
+  },
+  /// The whole image has been decoded
+  Done,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// The part of the image the decoder is waiting on
+enum HVIFDecoderState {
+  Magic,
+  StyleCount,
+  Styles,
+  PathCount,
+  Paths,
+  ShapeCount,
+  Shapes,
+  Done,
+}
+
+#[derive(Debug, Clone)]
+/// Decodes an HVIF image from chunks of bytes, yielding its entries as soon as each is complete
+///
+/// Entries come back in the order they are stored: every style, then every path, then every shape.
+/// A shape is checked against the style and path counts as `parse` does. Once the decoder fails,
+/// it returns the same error from then on.
+pub struct HVIFDecoder {
+  buffer: Vec<u8>,
+  // Where the undecoded bytes start in the buffer
+  start: usize,
+  // How many bytes of the image were drained from the front of the buffer
+  drained: usize,
+  state: HVIFDecoderState,
+  // Entries left to decode in the current section
+  remaining: usize,
+  style_count: usize,
+  path_count: usize,
+  needed: usize,
+  error: Option<HVIFError>,
+}
+
+impl Default for HVIFDecoder {
+  fn default() -> Self
+  {
+    HVIFDecoder::new()
+  }
+}
+
+impl HVIFDecoder {
+  /// A decoder waiting on the start of an image
+  pub fn new() -> Self
+  {
+    HVIFDecoder {
+      buffer: Vec::new(),
+      start: 0,
+      drained: 0,
+      state: HVIFDecoderState::Magic,
+      remaining: 0,
+      style_count: 0,
+      path_count: 0,
+      needed: 0,
+      error: None,
+    }
+  }
+
+  /// Adds the next chunk of the image
+  pub fn feed(&mut self, chunk: &[u8])
+  {
+    // Drop the decoded bytes before growing the buffer, so it only ever holds a partial entry and the new chunk
+    if self.start > 0 {
+      self.buffer.drain(..self.start);
+      self.drained += self.start;
+      self.start = 0;
+    }
+    self.buffer.extend_from_slice(chunk);
+  }
+
+  /// Decodes the next entry, if all of its bytes have been fed
+  pub fn decode(&mut self) -> Result<HVIFDecoded, HVIFError>
+  {
+    if let Some(error) = self.error {
+      return Err(error)
+    }
+    loop {
+      match self.state {
+        HVIFDecoderState::Magic => {
+          if self.next(|i| hvif_magic_number(i).map(|_| ()))?.is_none() {
+            return Ok(self.need_more())
+          }
+          self.state = HVIFDecoderState::StyleCount;
+        },
+        HVIFDecoderState::StyleCount | HVIFDecoderState::PathCount | HVIFDecoderState::ShapeCount => {
+          let count = match self.next(be_u8)? {
+            Some(count) => count as usize,
+            None => return Ok(self.need_more()),
+          };
+          self.remaining = count;
+          self.state = match self.state {
+            HVIFDecoderState::StyleCount => { self.style_count = count; HVIFDecoderState::Styles },
+            HVIFDecoderState::PathCount => { self.path_count = count; HVIFDecoderState::Paths },
+            _ => HVIFDecoderState::Shapes,
+          };
+        },
+        HVIFDecoderState::Styles if self.remaining == 0 => self.state = HVIFDecoderState::PathCount,
+        HVIFDecoderState::Paths if self.remaining == 0 => self.state = HVIFDecoderState::ShapeCount,
+        HVIFDecoderState::Shapes if self.remaining == 0 => self.state = HVIFDecoderState::Done,
+        HVIFDecoderState::Styles => return Ok(match self.next(hvif_style)? {
+          Some(style) => { self.remaining -= 1; HVIFDecoded::Style(style) },
+          None => self.need_more(),
+        }),
+        HVIFDecoderState::Paths => return Ok(match self.next(hvif_path)? {
+          Some(path) => { self.remaining -= 1; HVIFDecoded::Path(path) },
+          None => self.need_more(),
+        }),
+        HVIFDecoderState::Shapes => {
+          let (style_count, path_count) = (self.style_count, self.path_count);
+          return Ok(match self.next(|i| hvif_shape_checked(i, style_count, path_count))? {
+            Some(shape) => { self.remaining -= 1; HVIFDecoded::Shape(shape) },
+            None => self.need_more(),
+          })
+        },
+        HVIFDecoderState::Done => return Ok(HVIFDecoded::Done),
+      }
+    }
+  }
+
+  /// Ends the image once no more bytes will be fed, failing as `parse` does if it was cut short
+  ///
+  /// Entries that could still be decoded from the bytes already fed are skipped over.
+  pub fn finish(&mut self) -> Result<(), HVIFError>
+  {
+    loop {
+      match self.decode()? {
+        HVIFDecoded::Done => return Ok(()),
+        HVIFDecoded::NeedMore { needed } =>
+          return Err(HVIFError::Truncated { offset: self.drained + self.buffer.len(), needed: Some(needed) }),
+        _ => (),
+      }
+    }
+  }
+
+  /// How many bytes of the image have been decoded
+  pub fn offset(&self) -> usize
+  {
+    self.drained + self.start
+  }
+
+  /// The bytes fed but not yet decoded, which once the image is done are those that followed it
+  pub fn buffered(&self) -> &[u8]
+  {
+    &self.buffer[self.start..]
+  }
+
+  /// Runs a parser over the undecoded bytes, moving past what it took, or noting how many more bytes it needs
+  fn next<T, P>(&mut self, parser: P) -> Result<Option<T>, HVIFError>
+    where P: Fn(&[u8]) -> IResult<&[u8], T>
+  {
+    let (consumed, result) = {
+      let input = &self.buffer[self.start..];
+      match parser(input) {
+        IResult::Done(rest, value) => (input.len() - rest.len(), value),
+        IResult::Incomplete(needed) => {
+          self.needed = match needed {
+            // Sizes count from the start of the input, and always ask for at least one more byte
+            Needed::Size(size) => size.saturating_sub(input.len()).max(1),
+            Needed::Unknown => 1,
+          };
+          return Ok(None)
+        },
+        IResult::Error(err) => {
+          let (style_count, path_count) = (self.style_count, self.path_count);
+          let error = hvif_error_from_nom_at(input, self.drained + self.start, err, || (style_count, path_count));
+          self.error = Some(error);
+          return Err(error)
+        },
+      }
+    };
+    self.start += consumed;
+    Ok(Some(result))
+  }
+
+  fn need_more(&self) -> HVIFDecoded
+
+  {
+    HVIFDecoded::NeedMore { needed: self.needed }
+  }
+}
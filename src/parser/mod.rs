@@ -3,38 +3,210 @@ pub mod style;
 pub mod path;
 pub mod shape;
 pub mod util;
+pub mod decoder;
 
-use nom::*;
+use std::ops::Range;
+
+use nom::{IResult, ErrorKind, Needed};
+use nom::Err as NomErr;
+use error::*;
 use types::*;
+use validate::HVIFDiagnostic;
 
 use self::style::hvif_style;
 use self::path::hvif_path;
-use self::shape::hvif_shape;
+use self::shape::hvif_shape_checked;
+use self::util::hvif_length_count;
+
+pub use self::decoder::{HVIFDecoder, HVIFDecoded};
+
+// Custom error codes raised by the parsers, turned into an HVIFError by `parse`
+pub(crate) const HVIF_ERROR_BAD_MAGIC            : u32 = 1;
+pub(crate) const HVIF_ERROR_UNKNOWN_STYLE_TYPE   : u32 = 2;
+pub(crate) const HVIF_ERROR_UNKNOWN_GRADIENT_TYPE: u32 = 3;
+pub(crate) const HVIF_ERROR_UNKNOWN_TRANSFORMER  : u32 = 4;
+pub(crate) const HVIF_ERROR_BAD_SHAPE_TYPE       : u32 = 5;
+pub(crate) const HVIF_ERROR_STYLE_OUT_OF_RANGE   : u32 = 6;
+pub(crate) const HVIF_ERROR_PATH_OUT_OF_RANGE    : u32 = 7;
+pub(crate) const HVIF_ERROR_UNKNOWN_LINE_JOIN    : u32 = 8;
+pub(crate) const HVIF_ERROR_UNKNOWN_LINE_CAP     : u32 = 9;
+
+/// Parses an entire HVIF image, describing where and why the parse failed if it does
+pub fn parse(input: &[u8]) -> Result<HVIFImage, HVIFError>
+{
+  match hvif_image(input) {
+    IResult::Done(_, image) => Ok(image),
+    IResult::Incomplete(needed) => Err(HVIFError::Truncated {
+      offset: input.len(),
+      needed: match needed {
+        Needed::Size(size) => Some(size.saturating_sub(input.len())),
+        Needed::Unknown => None,
+      }
+    }),
+    IResult::Error(err) => Err(hvif_error_from_nom(input, err)),
+  }
+}
+
+/// Parses an entire HVIF image as `parse` does, then validates it
+///
+/// The parser already refuses shapes referring to styles or paths the image does not have,
+/// so the diagnostics hold what remains, such as gradients without colors and unused paths.
+pub fn parse_validated(input: &[u8]) -> Result<(HVIFImage, Vec<HVIFDiagnostic>), HVIFError>
+{
+  let image = parse(input)?;
+  let diagnostics = image.validate();
+  Ok((image, diagnostics))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Where the sections and entries of an HVIF image lie, as byte ranges from the start of the image
+pub struct HVIFLayout {
+  /// The styles section, from its count to the end of its last style
+  pub styles: Range<usize>,
+  /// The paths section, from its count to the end of its last path
+  pub paths: Range<usize>,
+  /// The shapes section, from its count to the end of its last shape
+  pub shapes: Range<usize>,
+  /// Each style, in order
+  pub style_entries: Vec<HVIFEntryLayout>,
+  /// Each path, in order
+  pub path_entries: Vec<HVIFEntryLayout>,
+  /// Each shape, in order
+  pub shape_entries: Vec<HVIFEntryLayout>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Where a single style, path or shape lies in an HVIF image
+pub struct HVIFEntryLayout {
+  /// The bytes of the entry
+  pub range: Range<usize>,
+  /// The entry's flags byte, or zero for styles other than gradients, which have none
+  pub flags: u8,
+}
+
+/// Finds where each section and entry of an HVIF image lies, failing as `parse` does
+pub fn parse_layout(input: &[u8]) -> Result<HVIFLayout, HVIFError>
+{
+  let image = parse(input)?;
+  let (styles, style_entries) = hvif_layout_section(input, 4, hvif_style, |style| match style[0] {
+    // Gradients store their gradient type after the style type, then their flags
+    2 => style[2],
+    _ => 0,
+  });
+  let (paths, path_entries) = hvif_layout_section(input, styles.end, hvif_path, |path| path[0]);
+  let (shapes, shape_entries) = hvif_layout_section(input, paths.end,
+    |i| hvif_shape_checked(i, image.styles.len(), image.paths.len()),
+    // The flags follow the type, style index, path count and path indices
+    |shape| shape[3 + shape[2] as usize]);
+  Ok(HVIFLayout {
+    styles, paths, shapes,
+    style_entries, path_entries, shape_entries,
+  })
+}
+
+/// Measures a section of an image that is known to parse, returning its range and the layout of its entries
+fn hvif_layout_section<T, P, F>(input: &[u8], start: usize, parser: P, flags: F) -> (Range<usize>, Vec<HVIFEntryLayout>)
+  where P: Fn(&[u8]) -> IResult<&[u8], T>, F: Fn(&[u8]) -> u8
+{
+  let count = input[start] as usize;
+  let mut offset = start + 1;
+  let mut entries = Vec::with_capacity(count);
+  for _ in 0..count {
+    let end = match parser(&input[offset..]) {
+      IResult::Done(rest, _) => input.len() - rest.len(),
+      _ => unreachable!("the image parsed as a whole"),
+    };
+    entries.push(HVIFEntryLayout { range: offset..end, flags: flags(&input[offset..end]) });
+    offset = end;
+  }
+  (start..offset, entries)
+}
+
+fn hvif_error_from_nom(input: &[u8], err: NomErr<&[u8]>) -> HVIFError
+{
+  hvif_error_from_nom_at(input, 0, err, || {
+    // The path count follows the styles, which must have parsed for the shapes to be reached
+    let path_count = match hvif_styles(&input[4..]) {
+      IResult::Done(rest, _) => rest[0] as usize,
+      _ => 0,
+    };
+    (input[4] as usize, path_count)
+  })
+}
+
+/// Describes a parser's error, for a parser whose input starts `base` bytes into the image
+///
+/// The style and path counts of the image are only asked for when a shape refers to an entry it does not have.
+fn hvif_error_from_nom_at<C>(input: &[u8], base: usize, err: NomErr<&[u8]>, counts: C) -> HVIFError
+  where C: FnOnce() -> (usize, usize)
+{
+  let (kind, position) = match err {
+    NomErr::Position(kind, position) | NomErr::NodePosition(kind, position, _) => (kind, position),
+    NomErr::Code(_) | NomErr::Node(_, _) => return HVIFError::Malformed { offset: base },
+  };
+  let offset = base + input.len() - position.len();
+  let byte = position.first().cloned().unwrap_or(0);
+
+  match kind {
+    ErrorKind::Custom(HVIF_ERROR_BAD_MAGIC) =>
+      HVIFError::BadMagic { offset },
+    ErrorKind::Custom(HVIF_ERROR_UNKNOWN_STYLE_TYPE) =>
+      HVIFError::UnknownStyleType { offset, style_type: byte },
+    ErrorKind::Custom(HVIF_ERROR_UNKNOWN_GRADIENT_TYPE) =>
+      HVIFError::UnknownGradientType { offset, gradient_type: byte },
+    ErrorKind::Custom(HVIF_ERROR_UNKNOWN_TRANSFORMER) =>
+      HVIFError::UnknownTransformer { offset, transformer_type: byte },
+    ErrorKind::Custom(HVIF_ERROR_BAD_SHAPE_TYPE) =>
+      HVIFError::BadShapeType { offset, shape_type: byte },
+    ErrorKind::Custom(HVIF_ERROR_UNKNOWN_LINE_JOIN) =>
+      HVIFError::UnknownLineJoin { offset, line_join: byte & 15 },
+    ErrorKind::Custom(HVIF_ERROR_UNKNOWN_LINE_CAP) =>
+      HVIFError::UnknownLineCap { offset, line_cap: byte >> 4 },
+    ErrorKind::Custom(HVIF_ERROR_STYLE_OUT_OF_RANGE) =>
+      HVIFError::IndexOutOfRange { offset, kind: HVIFIndexKind::Style, index: byte, count: counts().0 },
+    ErrorKind::Custom(HVIF_ERROR_PATH_OUT_OF_RANGE) =>
+      HVIFError::IndexOutOfRange { offset, kind: HVIFIndexKind::Path, index: byte, count: counts().1 },
+    _ => HVIFError::Malformed { offset },
+  }
+}
 
 named_attr!(#[doc = "Parses an entire HVIF image"], pub hvif_image<&[u8], HVIFImage>,
   do_parse!(
-    tag!("ncif") >>
+    hvif_magic_number >>
     styles: hvif_styles >>
     paths: hvif_paths >>
-    shapes: hvif_shapes >>
-    (HVIFImage { styles: styles, paths: paths, shapes: shapes })
+    shapes: apply!(hvif_shapes, styles.len(), paths.len()) >>
+    (HVIFImage { styles, paths, shapes, names: HVIFNames::default() })
   )
 );
 
 /// Parse the magic number at the beginning of any hvif file
-named!(hvif_magic_number, tag!("ncif"));
+fn hvif_magic_number(input: &[u8]) -> IResult<&[u8], &[u8]>
+{
+  if input.len() < 4 {
+    return IResult::Incomplete(Needed::Size(4))
+  }
+  match &input[..4] {
+    b"ncif" => IResult::Done(&input[4..], &input[..4]),
+    _ => IResult::Error(error_position!(ErrorKind::Custom(HVIF_ERROR_BAD_MAGIC), input)),
+  }
+}
 
 /// Parse a single byte to obtain a count, then run the style parser that many times
-named!(hvif_styles<&[u8], Vec<HVIFStyle>>,
-  length_count!(be_u8, hvif_style)
-);
+fn hvif_styles(input: &[u8]) -> IResult<&[u8], Vec<HVIFStyle>>
+{
+  hvif_length_count(input, hvif_style)
+}
 
 /// Parse a single byte to obtain a count, then run the path parser that many times
-named!(hvif_paths<&[u8], Vec<HVIFPath>>,
-  length_count!(be_u8, hvif_path)
-);
+fn hvif_paths(input: &[u8]) -> IResult<&[u8], Vec<HVIFPath>>
+{
+  hvif_length_count(input, hvif_path)
+}
 
-/// Parse a single byte to obtain a count, then run the shape parser that many times
-named!(hvif_shapes<&[u8], Vec<HVIFShape>>,
-  length_count!(be_u8, hvif_shape)
-);
+/// Parse a single byte to obtain a count, then run the shape parser that many times,
+/// checking each shape only refers to styles and paths that exist
+fn hvif_shapes(input: &[u8], style_count: usize, path_count: usize) -> IResult<&[u8], Vec<HVIFShape>>
+{
+  hvif_length_count(input, |i| hvif_shape_checked(i, style_count, path_count))
+}
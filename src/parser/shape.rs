@@ -2,29 +2,59 @@
 use types::*;
 use nom::*;
 
+use parser::*;
 use parser::util::*;
 
 named_attr!(#[doc = "Parses an HVIF shape"], pub hvif_shape<&[u8], HVIFShape>,
   do_parse!(
-    tag!(&[0x0a]) >> // There is only one shape type, SHAPE_TYPE_PATH_SOURCE - should always be this!
+    hvif_shape_type >>
     style_index: be_u8 >>
     path_indices: length_count!(be_u8, be_u8) >>
     shape_flags: be_u8 >>
     shape_modifiers: apply!(hvif_shape_modifier_parser_from_flags, shape_flags) >>
     (HVIFShape {
-      style_index: style_index,
-      path_indices: path_indices,
+      style_index,
+      path_indices,
       modifiers: shape_modifiers
     })
   )
 );
 
+/// Parses an HVIF shape, checking that it only refers to styles and paths that exist
+pub fn hvif_shape_checked(input: &[u8], style_count: usize, path_count: usize) -> IResult<&[u8], HVIFShape>
+{
+  let (rem_input, shape) = try_parse!(input, hvif_shape);
+
+  // The shape type, style index, path count, and path indices are always laid out in this order
+  if shape.style_index as usize >= style_count {
+    return IResult::Error(error_position!(ErrorKind::Custom(HVIF_ERROR_STYLE_OUT_OF_RANGE), &input[1..]))
+  }
+  if let Some(position) = shape.path_indices.iter().position(|&index| index as usize >= path_count) {
+    return IResult::Error(error_position!(ErrorKind::Custom(HVIF_ERROR_PATH_OUT_OF_RANGE), &input[3 + position..]))
+  }
+
+  IResult::Done(rem_input, shape)
+}
+
+fn hvif_shape_type(input: &[u8]) -> IResult<&[u8], u8>
+{
+  let (rem_input, shape_type) = try_parse!(input, be_u8);
+  match shape_type {
+    // There is only one shape type, SHAPE_TYPE_PATH_SOURCE - should always be this!
+    0x0a => IResult::Done(rem_input, shape_type),
+    _ => IResult::Error(error_position!(ErrorKind::Custom(HVIF_ERROR_BAD_SHAPE_TYPE), input))
+  }
+}
+
+/// Parses a single shape modifier
+type ModifierParser = fn(&[u8]) -> IResult<&[u8], HVIFShapeModifier>;
+
 fn hvif_shape_modifier_parser_from_flags(input: &[u8], flags: u8) -> IResult<&[u8], Vec<HVIFShapeModifier>>
 {
   let mut cur_input = input;
   let mut cur_modifiers = Vec::new();
 
-  let parsers_per_flags : Vec<(HVIFFlag, fn(&[u8]) -> IResult<&[u8], HVIFShapeModifier>)> = vec![
+  let parsers_per_flags : Vec<(HVIFFlag, ModifierParser)> = vec![
     // Order of hinting doesn't matter, it doesn't parse anything!
     (HVIF_SHAPE_FLAG_HINTING         , hvif_shape_modifier_hinting),
     // Order of these parsers matter!
@@ -33,21 +63,24 @@ fn hvif_shape_modifier_parser_from_flags(input: &[u8], flags: u8) -> IResult<&[u
     (HVIF_SHAPE_FLAG_LOD_SCALE       , hvif_shape_modifier_lod_scale),
     (HVIF_SHAPE_FLAG_HAS_TRANSFORMERS, hvif_shape_modifier_has_transformers),
   ];
-  let mut ppfiter = parsers_per_flags.into_iter();
-
   // Run all of the modifier parsers for set flags in order
-  while let Some((flag, parser)) = ppfiter.next() {
-    match flag.is_set_on(flags) {
-      true  => {
-        let (rem_input, new_mod) = try_parse!(cur_input, parser);
-        cur_input = rem_input;
-        cur_modifiers.push(new_mod);
-      },
-      false => ()
+  for (flag, parser) in parsers_per_flags {
+    if flag.is_set_on(flags) {
+      match parser(cur_input) {
+        IResult::Done(rem_input, new_mod) => {
+          cur_input = rem_input;
+          cur_modifiers.push(new_mod);
+        },
+        IResult::Error(e) => return IResult::Error(e),
+        IResult::Incomplete(Needed::Unknown) => return IResult::Incomplete(Needed::Unknown),
+        // Count the modifiers already parsed, so the needed size is from the start of this parser's input
+        IResult::Incomplete(Needed::Size(size)) =>
+          return IResult::Incomplete(Needed::Size(input.len() - cur_input.len() + size)),
+      }
     }
   }
 
-  return IResult::Done(cur_input, cur_modifiers)
+  IResult::Done(cur_input, cur_modifiers)
 }
 
 named!(hvif_shape_modifier_hinting<&[u8], HVIFShapeModifier>,
@@ -83,47 +116,34 @@ named!(hvif_shape_modifier_lod_scale<&[u8], HVIFShapeModifier>,
 );
 named!(hvif_shape_modifier_has_transformers<&[u8], HVIFShapeModifier>,
   do_parse!(
-    transformers: length_count!(be_u8, hvif_shape_modifier_transformer) >>
+    transformers: apply!(hvif_length_count, hvif_shape_modifier_transformer) >>
     (HVIFShapeModifier::HVIFTransformerList(transformers))
   )
 );
 
-named!(hvif_shape_modifier_transformer<&[u8], HVIFTransformer>,
-  do_parse!(
-    transformer_type: be_u8 >>
-    transformer: apply!(hvif_shape_modifier_transformer_parser_from_flags,transformer_type) >>
-    (transformer)
-  )
-);
-
-fn hvif_shape_modifier_transformer_parser_from_flags(input: &[u8], transformer_type: u8) -> IResult<&[u8], HVIFTransformer>
+fn hvif_shape_modifier_transformer(input: &[u8]) -> IResult<&[u8], HVIFTransformer>
 {
-  let maybe_parser: Option<fn(&[u8]) -> IResult<&[u8], HVIFTransformer>> = match transformer_type {
+  let (rem_input, transformer_type) = try_parse!(input, be_u8);
+  let parser: fn(&[u8]) -> IResult<&[u8], HVIFTransformer> = match transformer_type {
     20 => { // Affine matrix
-      Some(hvif_shape_modifier_transformer_matrix)
+      hvif_shape_modifier_transformer_matrix
     },
     21 => { // Contour
-      Some(hvif_shape_modifier_transformer_contour)
+      hvif_shape_modifier_transformer_contour
     },
     22 => { // Perspective (unused?)
-      Some(hvif_shape_modifier_transformer_perspective)
+      hvif_shape_modifier_transformer_perspective
     },
     23 => { // Stroke
-      Some(hvif_shape_modifier_transformer_stroke)
-
+      hvif_shape_modifier_transformer_stroke
     },
-    _ => None
-  };
-
-  let result = match maybe_parser {
-    Some(parser) => {
-      let (rem_input, transformer) = try_parse!(input, parser);
-      IResult::Done(rem_input, transformer)
-    }
-    None => IResult::Error(ErrorKind::Custom(1))
+    _ => return IResult::Error(error_position!(ErrorKind::Custom(HVIF_ERROR_UNKNOWN_TRANSFORMER), input))
   };
 
-  return result
+  match parser(rem_input) {
+    IResult::Incomplete(Needed::Size(size)) => IResult::Incomplete(Needed::Size(size + 1)),
+    result => result,
+  }
 }
 
 named!(hvif_shape_modifier_transformer_matrix<&[u8], HVIFTransformer>,
@@ -135,7 +155,7 @@ named!(hvif_shape_modifier_transformer_matrix<&[u8], HVIFTransformer>,
 named!(hvif_shape_modifier_transformer_contour<&[u8], HVIFTransformer>,
   do_parse!(
     width_int: be_u8 >>
-    lj: be_u8 >>
+    lj: hvif_shape_modifier_transformer_line_join >>
     ml : be_u8 >>
     (HVIFTransformer::Contour {
       width: (width_int as f32) - 128.0,
@@ -152,13 +172,37 @@ named!(hvif_shape_modifier_transformer_perspective<&[u8], HVIFTransformer>,
 named!(hvif_shape_modifier_transformer_stroke<&[u8], HVIFTransformer>,
   do_parse!(
     width_int: be_u8 >>
-    line_opts: be_u8 >>
+    line_opts: hvif_shape_modifier_transformer_line_options >>
     ml : be_u8 >>
     (HVIFTransformer::Stroke {
       width: (width_int as f32) - 128.0,
-      line_join: line_opts & 15,
-      line_cap: line_opts << 4,
+      line_join: line_opts.0,
+      line_cap: line_opts.1,
       miter_limit: ml
     })
   )
 );
+
+fn hvif_shape_modifier_transformer_line_join(input: &[u8]) -> IResult<&[u8], HVIFLineJoin>
+{
+  let (rem_input, line_join) = try_parse!(input, be_u8);
+  match line_join_from_u8(line_join) {
+    Some(line_join) => IResult::Done(rem_input, line_join),
+    None => IResult::Error(error_position!(ErrorKind::Custom(HVIF_ERROR_UNKNOWN_LINE_JOIN), input))
+  }
+}
+
+/// Parses the line join from the low four bits of a byte, and the line cap from the high four bits
+fn hvif_shape_modifier_transformer_line_options(input: &[u8]) -> IResult<&[u8], (HVIFLineJoin, HVIFLineCap)>
+{
+  let (rem_input, line_opts) = try_parse!(input, be_u8);
+  let line_join = match line_join_from_u8(line_opts & 15) {
+    Some(line_join) => line_join,
+    None => return IResult::Error(error_position!(ErrorKind::Custom(HVIF_ERROR_UNKNOWN_LINE_JOIN), input))
+  };
+  let line_cap = match line_cap_from_u8(line_opts >> 4) {
+    Some(line_cap) => line_cap,
+    None => return IResult::Error(error_position!(ErrorKind::Custom(HVIF_ERROR_UNKNOWN_LINE_CAP), input))
+  };
+  IResult::Done(rem_input, (line_join, line_cap))
+}
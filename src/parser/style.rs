@@ -2,46 +2,59 @@
 use nom::*;
 use types::*;
 
-named_attr!(#[doc = "Parses an HVIF style"], pub hvif_style<&[u8], HVIFStyle>,
-  do_parse!(
-    style_type: be_u8 >>
-    style: apply!(style_type_to_parser, style_type) >>
-    (style)
-  )
-);
+use parser::*;
+use parser::util::*;
 
-fn style_type_to_parser(input: &[u8], t: u8) -> IResult<&[u8], HVIFStyle> {
-  let p = match t {
+/// Parses an HVIF style
+pub fn hvif_style(input: &[u8]) -> IResult<&[u8], HVIFStyle>
+{
+  let (rem_input, style_type) = try_parse!(input, be_u8);
+  let parser = match style_type {
     1 => hvif_style_solid,
     2 => hvif_style_gradient,
     3 => hvif_style_solid_no_alpha,
     4 => hvif_style_gray,
     5 => hvif_style_gray_no_alpha,
-    _ => unimplemented!() //failure
+    _ => return IResult::Error(error_position!(ErrorKind::Custom(HVIF_ERROR_UNKNOWN_STYLE_TYPE), input))
   };
-  p(input)
+  match parser(rem_input) {
+    IResult::Incomplete(Needed::Size(size)) => IResult::Incomplete(Needed::Size(size + 1)),
+    result => result,
+  }
 }
 
 named!(hvif_style_solid<&[u8], HVIFStyle>,
   do_parse!(
-    a: be_u8 >>
     r: be_u8 >>
     g: be_u8 >>
     b: be_u8 >>
+    a: be_u8 >>
     (HVIFStyle::SolidColor { alpha: a, red: r, green: g, blue: b })
   )
 );
 named!(hvif_style_gradient<&[u8], HVIFStyle>,
   do_parse!(
-    gradient_type: map_opt!(be_u8, gradient_type_from_u8) >>
+    gradient_type: hvif_style_gradient_type >>
     flags : be_u8 >>
-    colors: length_count!(be_u8, apply!(hvif_style_gradient_color_parser, flags)) >>
+    color_count: be_u8 >>
+    transform: cond!(HVIF_GRADIENT_FLAG_TRANSFORM.is_set_on(flags), hvif_shape_matrix) >>
+    colors: apply!(hvif_count, color_count as usize, |i| hvif_style_gradient_color_parser(i, flags)) >>
     (HVIFStyle::Gradient(HVIFGradient {
-      gradient_type: gradient_type,
-      colors: colors
+      gradient_type,
+      transform,
+      colors
     }))
   )
 );
+fn hvif_style_gradient_type(input: &[u8]) -> IResult<&[u8], HVIFGradientType>
+{
+  let (rem_input, gradient_type) = try_parse!(input, be_u8);
+  match gradient_type_from_u8(gradient_type) {
+    Some(gradient_type) => IResult::Done(rem_input, gradient_type),
+    None => IResult::Error(error_position!(ErrorKind::Custom(HVIF_ERROR_UNKNOWN_GRADIENT_TYPE), input))
+  }
+}
+
 named!(hvif_style_solid_no_alpha<&[u8], HVIFStyle>,
   do_parse!(
     r: be_u8 >>
@@ -52,8 +65,8 @@ named!(hvif_style_solid_no_alpha<&[u8], HVIFStyle>,
 );
 named!(hvif_style_gray<&[u8], HVIFStyle>,
   do_parse!(
-    a: be_u8 >>
     v: be_u8 >>
+    a: be_u8 >>
     (HVIFStyle::SolidGray { alpha: a, value: v })
   )
 );
@@ -66,32 +79,49 @@ named!(hvif_style_gray_no_alpha<&[u8], HVIFStyle>,
 
 fn hvif_style_gradient_color_parser(input: &[u8], flags: u8) -> IResult<&[u8], HVIFGradientColor>
 {
-  let (i1, so) = try_parse!(input, be_u8);
-  let (i2, (r, g, b)) = try_parse!(i1, apply!(hvif_style_gradient_color_rgb_parser, flags));
-  let (i3, a) = try_parse!(i2, apply!(hvif_style_gradient_color_alpha_parser, flags));
-
-  return IResult::Done(i3, HVIFGradientColor { stop_offset: so, alpha: a, red: r, green: g, blue: b })
+  do_parse!(input,
+    so: be_u8 >>
+    rgb: apply!(hvif_style_gradient_color_rgb_parser, flags) >>
+    a: apply!(hvif_style_gradient_color_alpha_parser, flags) >>
+    (HVIFGradientColor { stop_offset: so, alpha: a, red: rgb.0, green: rgb.1, blue: rgb.2 })
+  )
 }
 
 fn hvif_style_gradient_color_rgb_parser(input: &[u8], flags: u8) -> IResult<&[u8], (u8, u8, u8)>
 {
   let grayscale = HVIF_GRADIENT_FLAG_GRAYS.is_set_on(flags);
-  let p = match grayscale {
-    true => hvif_style_gradient_color_rgb_grays,
-    false => hvif_style_gradient_color_rgb_nongrays,
-  };
-  p(input)
+  match grayscale {
+    true  => do_parse!(input,
+      value: apply!(hvif_style_gradient_color_channel, flags) >>
+      (value, value, value)
+    ),
+    false => tuple!(input,
+      apply!(hvif_style_gradient_color_channel, flags),
+      apply!(hvif_style_gradient_color_channel, flags),
+      apply!(hvif_style_gradient_color_channel, flags)
+    ),
+  }
 }
-named!(hvif_style_gradient_color_rgb_nongrays<&[u8], (u8,u8,u8)>, tuple!(be_u8, be_u8, be_u8));
-named!(hvif_style_gradient_color_rgb_grays<&[u8], (u8,u8,u8)>, do_parse!(value: be_u8 >> (value, value, value)));
 
 fn hvif_style_gradient_color_alpha_parser(input: &[u8], flags: u8) -> IResult<&[u8], u8>
 {
   let no_alpha = HVIF_GRADIENT_FLAG_NO_ALPHA.is_set_on(flags);
   let (rem_input, alpha) = match no_alpha {
     true  => (input, 0b1111_1111),
-    false => try_parse!(input, be_u8),
+    false => try_parse!(input, apply!(hvif_style_gradient_color_channel, flags)),
   };
 
-  return IResult::Done(rem_input, alpha)
+  IResult::Done(rem_input, alpha)
+}
+
+/// Parses a single color channel, which is two bytes wide for 16 bit colors
+///
+/// Colors are kept at 8 bits per channel, so only the most significant byte of a 16 bit channel is kept
+fn hvif_style_gradient_color_channel(input: &[u8], flags: u8) -> IResult<&[u8], u8>
+{
+  let deep_colors = HVIF_GRADIENT_FLAG_COLORS_16_BIT.is_set_on(flags);
+  match deep_colors {
+    true  => map!(input, be_u16, |channel: u16| (channel >> 8) as u8),
+    false => be_u8(input),
+  }
 }
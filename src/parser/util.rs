@@ -7,7 +7,7 @@ named_attr!(#[doc = "Parses an HVIF point"], pub hvif_point<&[u8], HVIFPoint>,
   do_parse!(
     x: hvif_path_coord >>
     y: hvif_path_coord >>
-    (HVIFPoint { x: x, y: y})
+    (HVIFPoint { x, y})
   )
 );
 
@@ -21,22 +21,51 @@ named_attr!(#[doc = "Parses an HVIF coordinate"], pub hvif_path_coord<&[u8], f32
 
 fn hvif_coord_parser(input: &[u8], first: u8) -> IResult<&[u8], f32>
 {
-  let is_big = first & 0b1000_0000 != 0;
-  let (rem_input, value) = match is_big {
+  match hvif_coord_is_long(first) {
+    true  => map!(input, be_u8, |second| hvif_coord_from_bytes(first, second)),
+    false => IResult::Done(input, hvif_coord_from_bytes(first, 0)),
+  }
+}
+
+/// Whether a coordinate starting with this byte takes up two bytes, rather than one
+pub fn hvif_coord_is_long(first: u8) -> bool
+{
+  first & 0b1000_0000 != 0
+}
+
+/// Decodes an HVIF coordinate from its bytes, ignoring the second byte for one byte coordinates
+///
+/// One byte coordinates are whole numbers from -32 to 95. Two byte coordinates hold a big endian 15 bit value,
+/// in steps of 1/102 from -128.
+pub fn hvif_coord_from_bytes(first: u8, second: u8) -> f32
+{
+  match hvif_coord_is_long(first) {
     true  => {
-      let (i1, second) = try_parse!(input, be_u8);
-      // Hooray! No mem::transmute needed
-      let u16value = ((second as u16) << 8) + (first as u16);
-      let value = ((u16value as f32) / 102.0) - 128.0;
-      (i1, value)
-    },
-    false => {
-      let value = (first as f32) - 32.0;
-      (input, value)
+      let u16value = (((first & 0b0111_1111) as u16) << 8) | (second as u16);
+      (u16value as f32) / 102.0 - 128.0
     },
-  };
+    false => (first as f32) - 32.0,
+  }
+}
 
-  return IResult::Done(rem_input, value)
+/// The smallest coordinate a path can hold
+pub const HVIF_COORD_MIN: f32 = -128.0;
+/// The largest coordinate a path can hold, the largest two byte coordinate
+pub const HVIF_COORD_MAX: f32 = (0x7fff as f32) / 102.0 - 128.0;
+
+/// Encodes an HVIF coordinate in its shortest form, the inverse of `hvif_coord_from_bytes`
+///
+/// Coordinates are clamped to the range two byte coordinates can hold, and rounded to the nearest 1/102.
+pub fn hvif_coord_to_bytes(coord: f32) -> (u8, Option<u8>)
+{
+  let u16value = ((coord.clamp(HVIF_COORD_MIN, HVIF_COORD_MAX) + 128.0) * 102.0).round() as u16;
+  let snapped = hvif_coord_from_bytes(0b1000_0000 | (u16value >> 8) as u8, u16value as u8);
+
+  if snapped.fract() == 0.0 && (-32.0..=95.0).contains(&snapped) {
+    ((snapped + 32.0) as u8, None)
+  } else {
+    (0b1000_0000 | (u16value >> 8) as u8, Some(u16value as u8))
+  }
 }
 
 named_attr!(#[doc = "Parses an HVIF affine matrix"], pub hvif_shape_matrix<&[u8], HVIFMatrix>,
@@ -48,8 +77,8 @@ named_attr!(#[doc = "Parses an HVIF affine matrix"], pub hvif_shape_matrix<&[u8]
     y2: hvif_shape_f24 >>
     z2: hvif_shape_f24 >>
     (HVIFMatrix {
-      x1: x1, y1: y1, z1: z1,
-      x2: x2, y2: y2, z2: z2,
+      x1, y1, z1,
+      x2, y2, z2,
     })
   )
 );
@@ -59,6 +88,44 @@ named_attr!(#[doc = "Parses an HVIF 24-bit float"], pub hvif_shape_f24<&[u8], HV
     fst: be_u8 >>
     snd: be_u8 >>
     thr: be_u8 >>
-    (HVIFf24 { fst: fst, snd: snd, thr: thr })
+    (HVIFf24 { fst, snd, thr })
   )
 );
+
+/// Runs a parser a given number of times, collecting the results
+///
+/// Unlike nom's `count!`, the error or needed size of the failing child parser is passed on untouched
+pub fn hvif_count<'a, O, F>(input: &'a [u8], count: usize, parser: F) -> IResult<&'a [u8], Vec<O>>
+  where F: Fn(&'a [u8]) -> IResult<&'a [u8], O>
+{
+  let mut rem_input = input;
+  let mut results = Vec::with_capacity(count);
+
+  for _ in 0..count {
+    match parser(rem_input) {
+      IResult::Done(next_input, result) => {
+        rem_input = next_input;
+        results.push(result);
+      },
+      IResult::Error(e) => return IResult::Error(e),
+      IResult::Incomplete(Needed::Unknown) => return IResult::Incomplete(Needed::Unknown),
+      IResult::Incomplete(Needed::Size(size)) => {
+        let consumed = input.len() - rem_input.len();
+        return IResult::Incomplete(Needed::Size(consumed + size))
+      },
+    }
+  }
+
+  IResult::Done(rem_input, results)
+}
+
+/// Parses a single byte to obtain a count, then runs a parser that many times
+pub fn hvif_length_count<'a, O, F>(input: &'a [u8], parser: F) -> IResult<&'a [u8], Vec<O>>
+  where F: Fn(&'a [u8]) -> IResult<&'a [u8], O>
+{
+  let (rem_input, count) = try_parse!(input, be_u8);
+  match hvif_count(rem_input, count as usize, parser) {
+    IResult::Incomplete(Needed::Size(size)) => IResult::Incomplete(Needed::Size(size + 1)),
+    result => result,
+  }
+}
@@ -0,0 +1,204 @@
+//! Writer for PNG images of rendered icons
+
+use render::HVIFBitmap;
+use zlib;
+
+/// Writes a rendered image as an 8 bit per channel RGBA PNG image
+///
+/// The premultiplied pixels of the bitmap are unpremultiplied, as PNG stores straight alpha.
+pub fn png_image(bitmap: &HVIFBitmap) -> Vec<u8>
+{
+  let mut out = Vec::new();
+  out.extend_from_slice(b"\x89PNG\r\n\x1a\n");
+
+  let mut header = Vec::with_capacity(13);
+  header.extend_from_slice(&bitmap.width.to_be_bytes());
+  header.extend_from_slice(&bitmap.height.to_be_bytes());
+  // 8 bits per channel, RGBA, with the standard compression, filtering and no interlacing
+  header.extend_from_slice(&[8, 6, 0, 0, 0]);
+  png_chunk(&mut out, b"IHDR", &header);
+
+  png_chunk(&mut out, b"IDAT", &zlib::compress(&png_scanlines(bitmap)));
+  png_chunk(&mut out, b"IEND", &[]);
+  out
+}
+
+/// The rows of an image, unpremultiplied, each filtered in whichever way makes it smallest
+fn png_scanlines(bitmap: &HVIFBitmap) -> Vec<u8>
+{
+  let stride = (bitmap.width as usize) * 4;
+  let pixels: Vec<u8> = bitmap.pixels.chunks(4).flat_map(png_unpremultiply).collect();
+
+  let mut out = Vec::with_capacity((stride + 1) * (bitmap.height as usize));
+  let mut candidate = vec![0u8; stride];
+  let mut best = vec![0u8; stride];
+  let blank = vec![0u8; stride];
+  for row in 0..(bitmap.height as usize) {
+    let current = &pixels[row * stride..(row + 1) * stride];
+    let above = match row {
+      0 => &blank[..],
+      _ => &pixels[(row - 1) * stride..row * stride],
+    };
+
+    // Pick the filter whose output has the smallest sum of magnitudes, as libpng does
+    let mut best_filter = 0;
+    let mut best_score = u64::MAX;
+    for filter in 0..5u8 {
+      png_filter(filter, current, above, &mut candidate);
+      let score: u64 = candidate.iter().map(|&byte| (byte as i8).unsigned_abs() as u64).sum();
+      if score < best_score {
+        best_score = score;
+        best_filter = filter;
+        best.copy_from_slice(&candidate);
+      }
+    }
+    out.push(best_filter);
+    out.extend_from_slice(&best);
+  }
+  out
+}
+
+fn png_unpremultiply(pixel: &[u8]) -> [u8; 4]
+{
+  let alpha = pixel[3];
+  match alpha {
+    0 => [0, 0, 0, 0],
+    255 => [pixel[0], pixel[1], pixel[2], 255],
+    _ => {
+      let channel = |value: u8| (((value as u32) * 255 + (alpha as u32) / 2) / (alpha as u32)).min(255) as u8;
+      [channel(pixel[0]), channel(pixel[1]), channel(pixel[2]), alpha]
+    },
+  }
+}
+
+/// Applies one of PNG's five filters to a row, given the row above it
+fn png_filter(filter: u8, current: &[u8], above: &[u8], out: &mut [u8])
+{
+  for i in 0..current.len() {
+    let left = if i >= 4 { current[i - 4] } else { 0 };
+    let up = above[i];
+    let up_left = if i >= 4 { above[i - 4] } else { 0 };
+    let predicted = match filter {
+      0 => 0,
+      1 => left,
+      2 => up,
+      3 => (((left as u16) + (up as u16)) / 2) as u8,
+      _ => png_paeth(left, up, up_left),
+    };
+    out[i] = current[i].wrapping_sub(predicted);
+  }
+}
+
+fn png_paeth(left: u8, up: u8, up_left: u8) -> u8
+{
+  let estimate = (left as i16) + (up as i16) - (up_left as i16);
+  let distance_left = (estimate - left as i16).abs();
+  let distance_up = (estimate - up as i16).abs();
+  let distance_up_left = (estimate - up_left as i16).abs();
+  if distance_left <= distance_up && distance_left <= distance_up_left {
+    left
+  } else if distance_up <= distance_up_left {
+    up
+  } else {
+    up_left
+  }
+}
+
+fn png_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8])
+{
+  out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+  let start = out.len();
+  out.extend_from_slice(kind);
+  out.extend_from_slice(data);
+  let crc = png_crc(&out[start..]);
+  out.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// The CRC-32 of some data, which ends every chunk
+fn png_crc(data: &[u8]) -> u32
+{
+  let mut crc = 0xffff_ffffu32;
+  for &byte in data {
+    crc ^= byte as u32;
+    for _ in 0..8 {
+      crc = match crc & 1 {
+        1 => (crc >> 1) ^ 0xedb8_8320,
+        _ => crc >> 1,
+      };
+    }
+  }
+  !crc
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Splits a PNG image into its chunks, checking the CRC of each
+  fn chunks(image: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    let mut chunks = Vec::new();
+    let mut at = 8;
+    while at < image.len() {
+      let length = u32::from_be_bytes([image[at], image[at + 1], image[at + 2], image[at + 3]]) as usize;
+      let kind = [image[at + 4], image[at + 5], image[at + 6], image[at + 7]];
+      let crc = &image[at + 8 + length..at + 12 + length];
+      assert_eq!(crc, &png_crc(&image[at + 4..at + 8 + length]).to_be_bytes()[..]);
+      chunks.push((kind, &image[at + 8..at + 8 + length]));
+      at += 12 + length;
+    }
+    chunks
+  }
+
+  /// Undoes the filter of each row, as a decoder does
+  fn unfilter(scanlines: &[u8], stride: usize) -> Vec<u8> {
+    let mut pixels: Vec<u8> = Vec::new();
+    for (row, line) in scanlines.chunks(stride + 1).enumerate() {
+      for i in 0..stride {
+        let left = if i >= 4 { pixels[row * stride + i - 4] } else { 0 };
+        let up = if row > 0 { pixels[(row - 1) * stride + i] } else { 0 };
+        let up_left = if row > 0 && i >= 4 { pixels[(row - 1) * stride + i - 4] } else { 0 };
+        let predicted = match line[0] {
+          0 => 0,
+          1 => left,
+          2 => up,
+          3 => (((left as u16) + (up as u16)) / 2) as u8,
+          _ => png_paeth(left, up, up_left),
+        };
+        pixels.push(line[1 + i].wrapping_add(predicted));
+      }
+    }
+    pixels
+  }
+
+  #[test]
+  fn computes_the_standard_crc() {
+    assert_eq!(png_crc(b"123456789"), 0xcbf4_3926);
+    assert_eq!(png_crc(b"IEND"), 0xae42_6082);
+  }
+
+  #[test]
+  fn writes_checked_chunks_whose_image_data_inflates() {
+    let bitmap = HVIFBitmap {
+      width: 3,
+      height: 2,
+      pixels: vec![
+        255, 0, 0, 255,  0, 0, 0, 0,  64, 32, 0, 128,
+        0, 255, 0, 255,  1, 2, 3, 255,  0, 0, 128, 128,
+      ],
+    };
+    let image = png_image(&bitmap);
+    assert_eq!(&image[..8], b"\x89PNG\r\n\x1a\n");
+
+    let chunks = chunks(&image);
+    let kinds: Vec<&[u8]> = chunks.iter().map(|chunk| &chunk.0[..]).collect();
+    assert_eq!(kinds, vec![&b"IHDR"[..], b"IDAT", b"IEND"]);
+    assert_eq!(chunks[0].1, &[0, 0, 0, 3, 0, 0, 0, 2, 8, 6, 0, 0, 0][..]);
+
+    let scanlines = zlib::decompress(chunks[1].1).unwrap();
+    assert_eq!(scanlines.len(), 2 * (1 + 3 * 4));
+    assert_eq!(unfilter(&scanlines, 3 * 4), vec![
+      255, 0, 0, 255,  0, 0, 0, 0,  128, 64, 0, 128,
+      0, 255, 0, 255,  1, 2, 3, 255,  0, 0, 255, 128,
+    ]);
+  }
+}
@@ -0,0 +1,344 @@
+//! Geometry used while rendering: affine transformations, path flattening, and stroking
+
+use std::f32::consts::PI;
+
+use types::*;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// A point in some 2D space
+pub struct Point {
+  /// The horizontal coordinate, growing to the right
+  pub x: f32,
+  /// The vertical coordinate, growing downwards
+  pub y: f32,
+}
+
+impl Point {
+  /// The point at the given coordinates
+  pub fn new(x: f32, y: f32) -> Point { Point { x, y } }
+
+  /// The sum of two points, taken as vectors
+  pub fn add(self, other: Point) -> Point { Point::new(self.x + other.x, self.y + other.y) }
+  /// The vector from another point to this one
+  pub fn sub(self, other: Point) -> Point { Point::new(self.x - other.x, self.y - other.y) }
+  /// This point as a vector, scaled by a factor
+  pub fn scale(self, factor: f32) -> Point { Point::new(self.x * factor, self.y * factor) }
+  /// The distance from the origin
+  pub fn length(self) -> f32 { self.x.hypot(self.y) }
+  fn cross(self, other: Point) -> f32 { self.x * other.y - self.y * other.x }
+  fn dot(self, other: Point) -> f32 { self.x * other.x + self.y * other.y }
+  /// The point a fraction `t` of the way from this point to another
+  pub fn lerp(self, other: Point, t: f32) -> Point { self.add(other.sub(self).scale(t)) }
+
+  /// The unit normal to the left of the direction from this point to another
+  fn normal_to(self, other: Point) -> Point {
+    let direction = other.sub(self);
+    let length = direction.length();
+    match length > 0.0 {
+      true  => Point::new(-direction.y / length, direction.x / length),
+      false => Point::new(0.0, 0.0),
+    }
+  }
+}
+
+impl From<HVIFPoint> for Point {
+  fn from(point: HVIFPoint) -> Self { Point::new(point.x, point.y) }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// An affine transformation, stored in the same order as `HVIFMatrix`
+///
+/// A point moves to `(x * sx + y * shx + tx, x * shy + y * sy + ty)`.
+pub struct Affine {
+  /// The horizontal scale
+  pub sx: f32,
+  /// The vertical shear, how far `y` moves per unit of `x`
+  pub shy: f32,
+  /// The horizontal shear, how far `x` moves per unit of `y`
+  pub shx: f32,
+  /// The vertical scale
+  pub sy: f32,
+  /// The horizontal translation
+  pub tx: f32,
+  /// The vertical translation
+  pub ty: f32,
+}
+
+impl Affine {
+  /// The transformation that leaves every point where it is
+  pub fn identity() -> Affine {
+    Affine { sx: 1.0, shy: 0.0, shx: 0.0, sy: 1.0, tx: 0.0, ty: 0.0 }
+  }
+
+  /// Scales by the same factor in both directions, about the origin
+  pub fn scale(factor: f32) -> Affine {
+    Affine { sx: factor, sy: factor, ..Affine::identity() }
+  }
+
+  /// Moves every point by the same offset
+  pub fn translation(x: f32, y: f32) -> Affine {
+    Affine { tx: x, ty: y, ..Affine::identity() }
+  }
+
+  /// Where this transformation takes a point
+  pub fn apply(&self, point: Point) -> Point {
+    Point::new(point.x * self.sx + point.y * self.shx + self.tx,
+               point.x * self.shy + point.y * self.sy + self.ty)
+  }
+
+  /// The transformation that applies this one, and then another
+  pub fn then(&self, next: &Affine) -> Affine {
+    Affine {
+      sx:  self.sx * next.sx + self.shy * next.shx,
+      shy: self.sx * next.shy + self.shy * next.sy,
+      shx: self.shx * next.sx + self.sy * next.shx,
+      sy:  self.shx * next.shy + self.sy * next.sy,
+      tx:  self.tx * next.sx + self.ty * next.shx + next.tx,
+      ty:  self.tx * next.shy + self.ty * next.sy + next.ty,
+    }
+  }
+
+  /// The transformation undoing this one, unless it collapses the plane onto a line or a point
+  pub fn invert(&self) -> Option<Affine> {
+    let determinant = self.sx * self.sy - self.shy * self.shx;
+    if determinant.abs() < 1.0e-12 {
+      return None
+    }
+    let (sx, shy, shx, sy) = (self.sy / determinant, -self.shy / determinant, -self.shx / determinant, self.sx / determinant);
+    Some(Affine {
+      sx, shy, shx, sy,
+      tx: -self.tx * sx - self.ty * shx,
+      ty: -self.tx * shy - self.ty * sy,
+    })
+  }
+
+  /// The average factor by which this transformation scales lengths
+  pub fn scale_factor(&self) -> f32 {
+    (self.sx * self.sy - self.shy * self.shx).abs().sqrt()
+  }
+}
+
+impl From<HVIFMatrix> for Affine {
+  fn from(matrix: HVIFMatrix) -> Self {
+    let values: [f32; 6] = matrix.into();
+    Affine { sx: values[0], shy: values[1], shx: values[2], sy: values[3], tx: values[4], ty: values[5] }
+  }
+}
+
+impl From<Affine> for HVIFMatrix {
+  fn from(affine: Affine) -> Self {
+    HVIFMatrix::from([affine.sx, affine.shy, affine.shx, affine.sy, affine.tx, affine.ty])
+  }
+}
+
+#[derive(Debug, Clone)]
+/// A path flattened into straight lines between its points
+pub struct Polyline {
+  /// The points, in order
+  pub points: Vec<Point>,
+  /// Whether a line runs from the last point back to the first
+  pub closed: bool,
+}
+
+impl Polyline {
+  /// Moves every point by a transformation
+  pub fn transform(&mut self, affine: &Affine) {
+    for point in &mut self.points {
+      *point = affine.apply(*point);
+    }
+  }
+
+  /// Twice the signed area enclosed by the polyline, positive when its points wind clockwise on screen
+  fn signed_area(&self) -> f32 {
+    let count = self.points.len();
+    (0..count).map(|i| self.points[i].cross(self.points[(i + 1) % count])).sum()
+  }
+}
+
+/// Flattens an HVIF path into a polyline, splitting curves until they stray no further than the tolerance
+pub fn flatten(path: &HVIFPath, tolerance: f32) -> Polyline
+{
+  let vertices = path.vertices();
+  let mut points = Vec::new();
+  if let Some(first) = vertices.first() {
+    points.push(first.point.into());
+  }
+
+  let segment_count = match path.closed {
+    true  => vertices.len(),
+    false => vertices.len().saturating_sub(1),
+  };
+  for i in 0..segment_count {
+    let from = &vertices[i];
+    let to = &vertices[(i + 1) % vertices.len()];
+    flatten_cubic(&mut points, from.point.into(), from.point_out.into(), to.point_in.into(), to.point.into(), tolerance, 0);
+  }
+
+  // The closing segment of a closed path returns to the first point, which is already there
+  if path.closed && points.len() > 1 {
+    points.pop();
+  }
+  Polyline { points, closed: path.closed }
+}
+
+fn flatten_cubic(out: &mut Vec<Point>, p0: Point, p1: Point, p2: Point, p3: Point, tolerance: f32, depth: u32)
+{
+  let chord = p3.sub(p0);
+  let chord_length = chord.length();
+  let deviation = match chord_length > 0.0 {
+    true  => chord.cross(p1.sub(p0)).abs().max(chord.cross(p2.sub(p0)).abs()) / chord_length,
+    false => p1.sub(p0).length().max(p2.sub(p0).length()),
+  };
+
+  if deviation <= tolerance || depth >= 16 {
+    out.push(p3);
+    return
+  }
+
+  // Split the curve in half with de Casteljau's algorithm
+  let p01 = p0.lerp(p1, 0.5);
+  let p12 = p1.lerp(p2, 0.5);
+  let p23 = p2.lerp(p3, 0.5);
+  let p012 = p01.lerp(p12, 0.5);
+  let p123 = p12.lerp(p23, 0.5);
+  let middle = p012.lerp(p123, 0.5);
+  flatten_cubic(out, p0, p01, p012, middle, tolerance, depth + 1);
+  flatten_cubic(out, middle, p123, p23, p3, tolerance, depth + 1);
+}
+
+#[derive(Debug, Copy, Clone)]
+/// How a polyline is stroked
+pub struct StrokeStyle {
+  /// The width of the line, centered on the polyline
+  pub width: f32,
+  /// How the line turns the corners between segments
+  pub line_join: HVIFLineJoin,
+  /// How the line ends at either end of an open polyline
+  pub line_cap: HVIFLineCap,
+  /// How far a miter join may reach past the corner, in half widths, before it is cut back
+  pub miter_limit: f32,
+}
+
+/// Turns the outline of polylines into closed polygons covering a line of the given style along them
+///
+/// The polygons overlap, but all wind the same way, so filling them with the non-zero rule covers their union
+pub fn stroke(lines: &[Polyline], style: &StrokeStyle, tolerance: f32) -> Vec<Polyline>
+{
+  let half_width = style.width.abs() / 2.0;
+  let mut polygons = Vec::new();
+  if half_width <= 0.0 {
+    return polygons
+  }
+
+  for line in lines {
+    let mut points = line.points.clone();
+    points.dedup();
+    if line.closed && points.len() > 1 && points.first() == points.last() {
+      points.pop();
+    }
+    let count = points.len();
+    if count == 0 {
+      continue
+    }
+    if count == 1 {
+      // A lone point only shows up through its caps
+      if style.line_cap == HVIFLineCap::Round {
+        polygons.push(circle(points[0], half_width, tolerance));
+      } else if style.line_cap == HVIFLineCap::Square {
+        let corner = Point::new(half_width, half_width);
+        let (low, high) = (points[0].sub(corner), points[0].add(corner));
+        polygons.push(closed_polygon(vec![low, Point::new(high.x, low.y), high, Point::new(low.x, high.y)]));
+      }
+      continue
+    }
+
+    let segment_count = match line.closed { true => count, false => count - 1 };
+    for i in 0..segment_count {
+      let (from, to) = (points[i], points[(i + 1) % count]);
+      let normal = from.normal_to(to).scale(half_width);
+      polygons.push(closed_polygon(vec![from.add(normal), to.add(normal), to.sub(normal), from.sub(normal)]));
+    }
+
+    // Joins between consecutive segments
+    let (first_join, last_join) = match line.closed { true => (0, count), false => (1, count - 1) };
+    for i in first_join..last_join {
+      let previous = points[(i + count - 1) % count];
+      let (vertex, next) = (points[i], points[(i + 1) % count]);
+      if let Some(join) = join_polygon(previous, vertex, next, half_width, style, tolerance) {
+        polygons.push(join);
+      }
+    }
+
+    if !line.closed {
+      polygons.extend(cap_polygon(points[1], points[0], half_width, style.line_cap, tolerance));
+      polygons.extend(cap_polygon(points[count - 2], points[count - 1], half_width, style.line_cap, tolerance));
+    }
+  }
+
+  polygons
+}
+
+fn join_polygon(previous: Point, vertex: Point, next: Point, half_width: f32, style: &StrokeStyle, tolerance: f32) -> Option<Polyline>
+{
+  let normal_in = previous.normal_to(vertex);
+  let normal_out = vertex.normal_to(next);
+  let turn = vertex.sub(previous).cross(next.sub(vertex));
+  if turn == 0.0 && normal_in.dot(normal_out) > 0.0 {
+    return None
+  }
+
+  // The outside of the corner is on the opposite side to the turn
+  let side = match turn > 0.0 { true => -1.0, false => 1.0 };
+  let outer_in = vertex.add(normal_in.scale(half_width * side));
+  let outer_out = vertex.add(normal_out.scale(half_width * side));
+
+  let cos_half_angle = ((1.0 + normal_in.dot(normal_out)) / 2.0).max(0.0).sqrt();
+  let miter_fits = cos_half_angle > 0.0 && 1.0 / cos_half_angle <= style.miter_limit;
+
+  let join = match style.line_join {
+    HVIFLineJoin::Round => return Some(circle(vertex, half_width, tolerance)),
+    HVIFLineJoin::MiterRound if !miter_fits => return Some(circle(vertex, half_width, tolerance)),
+    HVIFLineJoin::Miter | HVIFLineJoin::MiterRevert | HVIFLineJoin::MiterRound if miter_fits => {
+      let bisector = normal_in.add(normal_out);
+      let miter = vertex.add(bisector.scale(half_width * side / bisector.length() / cos_half_angle));
+      vec![vertex, outer_in, miter, outer_out]
+    },
+    _ => vec![vertex, outer_in, outer_out],
+  };
+  Some(closed_polygon(join))
+}
+
+fn cap_polygon(before: Point, end: Point, half_width: f32, line_cap: HVIFLineCap, tolerance: f32) -> Option<Polyline>
+{
+  let normal = before.normal_to(end).scale(half_width);
+  let direction = Point::new(normal.y, -normal.x);
+  match line_cap {
+    HVIFLineCap::Butt => None,
+    HVIFLineCap::Round => Some(circle(end, half_width, tolerance)),
+    HVIFLineCap::Square => Some(closed_polygon(vec![
+      end.add(normal), end.add(normal).add(direction), end.sub(normal).add(direction), end.sub(normal)
+    ])),
+  }
+}
+
+fn circle(center: Point, radius: f32, tolerance: f32) -> Polyline
+{
+  // Enough steps that each chord strays no further than the tolerance from the arc
+  let step = 2.0 * (1.0 - (tolerance / radius).min(1.0)).acos();
+  let steps = ((2.0 * PI / step.max(0.01)).ceil() as usize).clamp(8, 256);
+  let points = (0..steps).map(|i| {
+    let angle = 2.0 * PI * (i as f32) / (steps as f32);
+    Point::new(center.x + radius * angle.cos(), center.y + radius * angle.sin())
+  }).collect();
+  closed_polygon(points)
+}
+
+/// A closed polygon, wound so that its signed area is positive
+fn closed_polygon(points: Vec<Point>) -> Polyline
+{
+  let mut polygon = Polyline { points, closed: true };
+  if polygon.signed_area() < 0.0 {
+    polygon.points.reverse();
+  }
+  polygon
+}
@@ -0,0 +1,228 @@
+//! A software rasterizer for HVIF images
+//!
+//! Images are drawn on a 64 by 64 unit canvas, which is scaled to the requested size in pixels.
+//! Shapes are drawn in order, each filling its paths with the non-zero rule after applying its transformers
+//! and transformation, and blended over the shapes before it with anti-aliased coverage.
+//! Perspective transformers are skipped, as HVIF keeps none of their parameters.
+//! Shapes with a level of detail range only show when the canvas is scaled within it.
+//! Gradients are laid out in their own space, spanning -64 to 64, which their own transformation and the shape's
+//! transformation carry onto the canvas.
+pub(crate) mod geometry;
+mod paint;
+mod raster;
+
+use types::*;
+
+use self::geometry::*;
+use self::paint::*;
+use self::raster::*;
+
+/// The width and height of the canvas HVIF images are drawn on, in icon units
+pub const HVIF_CANVAS_SIZE: f32 = 64.0;
+
+/// The sizes, in pixels, that icons are commonly rendered at
+pub const HVIF_ICON_SIZES: [u32; 6] = [16, 32, 48, 64, 128, 256];
+
+/// How far flattened curves may stray from the true curve, in pixels
+const TOLERANCE: f32 = 0.1;
+
+#[derive(Debug, Clone, PartialEq)]
+/// A rendered image, as rows of premultiplied RGBA pixels with 8 bits per channel
+pub struct HVIFBitmap {
+  /// The width of the image in pixels
+  pub width: u32,
+  /// The height of the image in pixels
+  pub height: u32,
+  /// The pixels of the image, four bytes each, from the top left corner along each row
+  pub pixels: Vec<u8>,
+}
+
+/// Renders an image into a square bitmap of the given size in pixels
+pub fn render(image: &HVIFImage, size: u32) -> HVIFBitmap
+{
+  render_at_scale(image, size, (size as f32) / HVIF_CANVAS_SIZE)
+}
+
+/// Renders an image into a square bitmap of the given size in pixels, showing the shapes visible at another scale
+///
+/// This previews how an icon looks at one size, such as its 16 pixel variant, enlarged to another.
+pub fn render_at_scale(image: &HVIFImage, size: u32, scale: f32) -> HVIFBitmap
+{
+  let pixel_count = (size as usize) * (size as usize);
+  let mut canvas = vec![[0.0f32; 4]; pixel_count];
+  let global = Affine::scale((size as f32) / HVIF_CANVAS_SIZE);
+
+  for shape in image.shapes_visible_at(scale) {
+    render_shape(&mut canvas, size as usize, image, shape, &global);
+  }
+
+  let mut pixels = Vec::with_capacity(pixel_count * 4);
+  for pixel in canvas {
+    for channel in &pixel {
+      pixels.push((channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+    }
+  }
+  HVIFBitmap { width: size, height: size, pixels }
+}
+
+/// Renders an image into a square bitmap at each of the given sizes in pixels
+///
+/// Each size shows only the shapes designed for it, such as a simpler outline at small sizes.
+pub fn render_sizes(image: &HVIFImage, sizes: &[u32]) -> Vec<HVIFBitmap>
+{
+  sizes.iter().map(|&size| render(image, size)).collect()
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// A rectangle on the canvas, in icon units
+pub struct HVIFBounds {
+  /// The smallest x-coordinate
+  pub left: f32,
+  /// The smallest y-coordinate
+  pub top: f32,
+  /// The largest x-coordinate
+  pub right: f32,
+  /// The largest y-coordinate
+  pub bottom: f32,
+}
+
+/// The smallest rectangle holding everything drawn of an image at a scale, or nothing if nothing is drawn
+///
+/// Strokes and contours are included, and curves are measured along their flattened outline.
+pub fn bounds_at(image: &HVIFImage, scale: f32) -> Option<HVIFBounds>
+{
+  let mut bounds: Option<HVIFBounds> = None;
+  for shape in image.shapes_visible_at(scale) {
+    let geometry = shape_geometry(image, shape, &Affine::scale(scale.max(1.0e-6)));
+    let outline = match geometry.contour {
+      Some((outline, true)) => outline,
+      _ => Vec::new(),
+    };
+    for line in geometry.polygons.iter().chain(&outline) {
+      for point in &line.points {
+        let point = geometry.transform.apply(*point);
+        bounds = Some(match bounds {
+          Some(bounds) => HVIFBounds {
+            left: bounds.left.min(point.x), top: bounds.top.min(point.y),
+            right: bounds.right.max(point.x), bottom: bounds.bottom.max(point.y),
+          },
+          None => HVIFBounds { left: point.x, top: point.y, right: point.x, bottom: point.y },
+        });
+      }
+    }
+  }
+  bounds
+}
+
+/// The outline of a shape in path space, ready to be carried onto the canvas
+struct ShapeGeometry {
+  /// The shape's own transformation, from path space to canvas space
+  transform: Affine,
+  hinting: bool,
+  /// Polygons covering the shape, filled with the non-zero rule
+  polygons: Vec<Polyline>,
+  /// Polygons covering the band a contour adds to the shape, or removes from it, and whether it adds
+  contour: Option<(Vec<Polyline>, bool)>,
+}
+
+/// Flattens and transforms the paths of a shape, finely enough for the given transformation from canvas to pixels
+///
+/// Perspective transformers leave the paths as they are, since HVIF does not store the quadrilateral they map onto.
+fn shape_geometry(image: &HVIFImage, shape: &HVIFShape, global: &Affine) -> ShapeGeometry
+{
+  let mut transform = Affine::identity();
+  let mut hinting = false;
+  let mut transformers: &[HVIFTransformer] = &[];
+  for modifier in &shape.modifiers {
+    match *modifier {
+      HVIFShapeModifier::HVIFTransformMatrix(matrix) => transform = Affine::from(matrix),
+      HVIFShapeModifier::HVIFTranslation(offset) => transform = Affine::translation(offset.x, offset.y),
+      HVIFShapeModifier::HVIFHinting => hinting = true,
+      HVIFShapeModifier::HVIFTransformerList(ref list) => transformers = list,
+      HVIFShapeModifier::HVIFLODScale { .. } => (),
+    }
+  }
+
+  // Flatten finely enough for the final pixel size
+  let tolerance = TOLERANCE / transform.then(global).scale_factor().max(1.0e-6);
+  let mut lines: Vec<Polyline> = shape.path_indices.iter()
+    .filter_map(|&index| image.paths.get(index as usize))
+    .map(|path| flatten(path, tolerance))
+    .collect();
+
+  // Transformers work in path space, before the shape's transformation
+  let mut contour: Option<(StrokeStyle, bool)> = None;
+  for transformer in transformers {
+    match *transformer {
+      HVIFTransformer::Affine(matrix) => {
+        let affine = Affine::from(matrix);
+        for line in &mut lines {
+          line.transform(&affine);
+        }
+      },
+      HVIFTransformer::Stroke { width, line_join, line_cap, miter_limit } => {
+        let style = StrokeStyle { width, line_join, line_cap, miter_limit: miter_limit as f32 };
+        lines = stroke(&lines, &style, tolerance);
+      },
+      HVIFTransformer::Contour { width, line_join, miter_limit } => {
+        // The outline moves by the width, so the stroke along it is twice as wide
+        let style = StrokeStyle { width: width * 2.0, line_join, line_cap: HVIFLineCap::Butt, miter_limit: miter_limit as f32 };
+        contour = Some((style, width >= 0.0));
+      },
+      // Nothing to map the paths onto, so they are drawn untransformed
+      HVIFTransformer::Perspective => (),
+    }
+  }
+  let contour = contour.map(|(style, grow)| (stroke(&lines, &style, tolerance), grow));
+
+  ShapeGeometry { transform, hinting, polygons: lines, contour }
+}
+
+fn render_shape(canvas: &mut [[f32; 4]], size: usize, image: &HVIFImage, shape: &HVIFShape, global: &Affine)
+{
+  let style = match image.styles.get(shape.style_index as usize) {
+    Some(style) => style,
+    None => return,
+  };
+
+  let geometry = shape_geometry(image, shape, global);
+  let to_pixels = geometry.transform.then(global);
+  let mut coverage = rasterize(&to_pixel_space(geometry.polygons, &to_pixels, geometry.hinting), size, size);
+  if let Some((outline, grow)) = geometry.contour {
+    let outline = rasterize(&to_pixel_space(outline, &to_pixels, geometry.hinting), size, size);
+    match grow {
+      true  => coverage.union(&outline),
+      false => coverage.subtract(&outline),
+    }
+  }
+
+  let paint = Paint::from_style(style, &to_pixels);
+  for y in 0..size {
+    for x in 0..size {
+      let cover = coverage.get(x, y);
+      if cover <= 0.0 {
+        continue
+      }
+      let color = paint.color_at(x as f32 + 0.5, y as f32 + 0.5);
+      let pixel = &mut canvas[y * size + x];
+      let inverse_alpha = 1.0 - color[3] * cover;
+      for channel in 0..4 {
+        pixel[channel] = color[channel] * cover + pixel[channel] * inverse_alpha;
+      }
+    }
+  }
+}
+
+fn to_pixel_space(mut lines: Vec<Polyline>, to_pixels: &Affine, hinting: bool) -> Vec<Polyline>
+{
+  for line in &mut lines {
+    line.transform(to_pixels);
+    if hinting {
+      // Snap to pixel edges, so that straight edges stay crisp
+      for point in &mut line.points {
+        *point = Point::new(point.x.round(), point.y.round());
+      }
+    }
+  }
+  lines
+}
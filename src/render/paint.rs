@@ -0,0 +1,150 @@
+//! Paint for the pixels a shape covers, given by its style
+
+use types::*;
+
+use render::geometry::*;
+
+/// A premultiplied RGBA color, with each channel from 0 to 1
+pub type Color = [f32; 4];
+
+/// Premultiplies an 8 bit per channel color
+pub fn premultiplied(red: u8, green: u8, blue: u8, alpha: u8) -> Color
+{
+  let alpha = (alpha as f32) / 255.0;
+  [(red as f32) / 255.0 * alpha, (green as f32) / 255.0 * alpha, (blue as f32) / 255.0 * alpha, alpha]
+}
+
+/// Gradients span from -64 to 64 in gradient space along each axis
+const GRADIENT_EXTENT: f32 = 64.0;
+
+/// How many colors a gradient's lookup table holds
+const GRADIENT_STEPS: usize = 256;
+
+#[derive(Debug)]
+/// How to color the pixels of a shape
+pub enum Paint {
+  /// Every pixel has the same color
+  Solid(Color),
+  /// The color of each pixel depends on where it lies in gradient space
+  Gradient {
+    #[doc = "How positions in gradient space map to positions in the gradient"] gradient_type: HVIFGradientType,
+    #[doc = "Transformation from pixel space into gradient space"] to_gradient: Affine,
+    #[doc = "The gradient's colors, evenly spaced from its start to its end"] colors: Vec<Color>,
+  },
+}
+
+impl Paint {
+  /// The paint for a style, where the shape's space is mapped to pixel space by the given transformation
+  pub fn from_style(style: &HVIFStyle, to_pixels: &Affine) -> Paint {
+    match *style {
+      HVIFStyle::SolidColor { red, green, blue, alpha } => Paint::Solid(premultiplied(red, green, blue, alpha)),
+      HVIFStyle::SolidColorNoAlpha { red, green, blue } => Paint::Solid(premultiplied(red, green, blue, 255)),
+      HVIFStyle::SolidGray { value, alpha } => Paint::Solid(premultiplied(value, value, value, alpha)),
+      HVIFStyle::SolidGrayNoAlpha { value } => Paint::Solid(premultiplied(value, value, value, 255)),
+      HVIFStyle::Gradient(ref gradient) => {
+        let from_gradient = gradient.transform.map(Affine::from).unwrap_or_else(Affine::identity).then(to_pixels);
+        match (from_gradient.invert(), gradient.colors.len()) {
+          (_, 0) => Paint::Solid([0.0; 4]),
+          (None, _) => Paint::Solid(gradient_colors(&gradient.colors)[0]),
+          (Some(to_gradient), _) => Paint::Gradient {
+            gradient_type: gradient.gradient_type,
+            to_gradient,
+            colors: gradient_colors(&gradient.colors),
+          },
+        }
+      },
+    }
+  }
+
+  /// The color of the pixel whose center is at the given point in pixel space
+  pub fn color_at(&self, x: f32, y: f32) -> Color {
+    match *self {
+      Paint::Solid(color) => color,
+      Paint::Gradient { gradient_type, ref to_gradient, ref colors } => {
+        let point = to_gradient.apply(Point::new(x, y));
+        let offset = gradient_offset(gradient_type, point.x, point.y).clamp(0.0, 1.0);
+        colors[(offset * ((GRADIENT_STEPS - 1) as f32)).round() as usize]
+      },
+    }
+  }
+}
+
+/// How far along the gradient a point in gradient space is, from 0 at its start to 1 at its end
+///
+/// These follow the gradient functions of agg, spanning -64 to 64 for linear gradients, and 0 to 64 for the rest.
+fn gradient_offset(gradient_type: HVIFGradientType, x: f32, y: f32) -> f32
+{
+  match gradient_type {
+    HVIFGradientType::Linear   => (x + GRADIENT_EXTENT) / (2.0 * GRADIENT_EXTENT),
+    HVIFGradientType::Circular => x.hypot(y) / GRADIENT_EXTENT,
+    HVIFGradientType::Diamond  => x.abs().max(y.abs()) / GRADIENT_EXTENT,
+    HVIFGradientType::Conic    => y.atan2(x).abs() / ::std::f32::consts::PI,
+    HVIFGradientType::XY       => x.abs() * y.abs() / GRADIENT_EXTENT / GRADIENT_EXTENT,
+    HVIFGradientType::SqrtXY   => (x.abs() * y.abs()).sqrt() / GRADIENT_EXTENT,
+  }
+}
+
+/// Builds a lookup table of a gradient's colors, interpolating between its stops
+///
+/// Colors before the first stop and after the last take the color of that stop.
+fn gradient_colors(stops: &[HVIFGradientColor]) -> Vec<Color>
+{
+  let mut stops = stops.to_vec();
+  stops.sort_by_key(|stop| stop.stop_offset);
+
+  let unpremultiplied = |stop: &HVIFGradientColor| [stop.red as f32, stop.green as f32, stop.blue as f32, stop.alpha as f32];
+  (0..GRADIENT_STEPS).map(|step| {
+    let offset = (step as f32) / ((GRADIENT_STEPS - 1) as f32) * 255.0;
+    let after = stops.iter().position(|stop| stop.stop_offset as f32 >= offset);
+    let color = match after {
+      None => unpremultiplied(&stops[stops.len() - 1]),
+      Some(0) => unpremultiplied(&stops[0]),
+      Some(index) => {
+        let (from, to) = (&stops[index - 1], &stops[index]);
+        let t = (offset - from.stop_offset as f32) / ((to.stop_offset - from.stop_offset) as f32);
+        let (from, to) = (unpremultiplied(from), unpremultiplied(to));
+        [0, 1, 2, 3].iter().fold([0.0; 4], |mut color, &channel| {
+          color[channel] = from[channel] + (to[channel] - from[channel]) * t;
+          color
+        })
+      },
+    };
+    let alpha = color[3] / 255.0;
+    [color[0] / 255.0 * alpha, color[1] / 255.0 * alpha, color[2] / 255.0 * alpha, alpha]
+  }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn places_points_along_each_type_of_gradient() {
+    let samples = [
+      (HVIFGradientType::Linear, (-32.0, 50.0), 0.25),
+      (HVIFGradientType::Circular, (24.0, -32.0), 0.625),
+      (HVIFGradientType::Diamond, (-16.0, 48.0), 0.75),
+      (HVIFGradientType::Conic, (0.0, 10.0), 0.5),
+      (HVIFGradientType::XY, (32.0, -32.0), 0.25),
+      (HVIFGradientType::SqrtXY, (16.0, -64.0), 0.5),
+    ];
+    for &(gradient_type, (x, y), offset) in &samples {
+      assert!((gradient_offset(gradient_type, x, y) - offset).abs() < 1.0e-6, "{:?} at ({}, {})", gradient_type, x, y);
+    }
+  }
+
+  #[test]
+  fn colors_pixels_from_the_lookup_table() {
+    let stop = |stop_offset: u8, value: u8| HVIFGradientColor { stop_offset, alpha: 255, red: value, green: value, blue: value };
+    let gradient = HVIFStyle::Gradient(HVIFGradient {
+      gradient_type: HVIFGradientType::Linear,
+      transform: None,
+      colors: vec![stop(255, 255), stop(0, 0)],
+    });
+    // Gradient space starts at the pixel origin, so the left edge of the gradient lies 64 pixels to the left
+    let paint = Paint::from_style(&gradient, &Affine::identity());
+    assert_eq!(paint.color_at(-64.0, 0.0), [0.0, 0.0, 0.0, 1.0]);
+    assert_eq!(paint.color_at(100.0, 0.0), [1.0, 1.0, 1.0, 1.0]);
+    assert!((paint.color_at(0.0, 7.0)[0] - 0.5).abs() < 0.01);
+  }
+}
@@ -0,0 +1,141 @@
+//! An anti-aliased scanline rasterizer, accumulating the signed area each edge covers in every pixel
+
+use render::geometry::*;
+
+#[derive(Debug)]
+/// A coverage mask, holding how much of each pixel a set of polygons covers, from 0 to 1
+pub struct Coverage {
+  pub width: usize,
+  pub height: usize,
+  pub values: Vec<f32>,
+}
+
+impl Coverage {
+  pub fn get(&self, x: usize, y: usize) -> f32 {
+    self.values[y * self.width + x]
+  }
+
+  /// Takes the coverage of another mask away from this one
+  pub fn subtract(&mut self, other: &Coverage) {
+    for (value, other) in self.values.iter_mut().zip(other.values.iter()) {
+      *value = (*value - *other).max(0.0);
+    }
+  }
+
+  /// Adds the coverage of another mask to this one
+  pub fn union(&mut self, other: &Coverage) {
+    for (value, other) in self.values.iter_mut().zip(other.values.iter()) {
+      *value = value.max(*other);
+    }
+  }
+}
+
+/// Rasterizes closed polygons in pixel space with the non-zero fill rule
+pub fn rasterize(polygons: &[Polyline], width: usize, height: usize) -> Coverage
+{
+  // Each row has room for edges on the right hand border, and one past it, which are never shown
+  let stride = width + 2;
+  let mut accumulation = vec![0.0f32; stride * height];
+
+  for polygon in polygons {
+    let count = polygon.points.len();
+    for i in 0..count {
+      let (from, to) = (polygon.points[i], polygon.points[(i + 1) % count]);
+      for (from, to) in clip_horizontally(from, to, width as f32) {
+        draw_line(&mut accumulation, stride, height, from, to);
+      }
+    }
+  }
+
+  let mut values = Vec::with_capacity(width * height);
+  for row in accumulation.chunks(stride) {
+    let mut total = 0.0;
+    for cell in &row[..width] {
+      total += *cell;
+      values.push(total.abs().min(1.0));
+    }
+  }
+
+  Coverage { width, height, values }
+}
+
+/// Splits a line at the left and right borders, pulling the parts beyond them onto the border
+///
+/// An edge left of the image still covers every pixel to its right, and one right of the image covers none of them,
+/// so flattening the edge against the border keeps the coverage of every pixel inside the image the same.
+fn clip_horizontally(from: Point, to: Point, width: f32) -> Vec<(Point, Point)>
+{
+  let mut cuts = vec![0.0, 1.0];
+  for &border in &[0.0, width] {
+    let t = (border - from.x) / (to.x - from.x);
+    if t > 0.0 && t < 1.0 {
+      cuts.push(t);
+    }
+  }
+  cuts.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+  let at = |t: f32| Point::new(
+    (from.x + (to.x - from.x) * t).max(0.0).min(width),
+    from.y + (to.y - from.y) * t
+  );
+  cuts.windows(2).map(|pair| (at(pair[0]), at(pair[1]))).collect()
+}
+
+/// Accumulates the signed area of a line into the cells it passes through
+fn draw_line(accumulation: &mut [f32], stride: usize, height: usize, from: Point, to: Point)
+{
+  if from.y == to.y {
+    return
+  }
+  let (direction, from, to) = match from.y < to.y {
+    true  => (1.0, from, to),
+    false => (-1.0, to, from),
+  };
+  let dxdy = (to.x - from.x) / (to.y - from.y);
+
+  let first_row = from.y.max(0.0) as usize;
+  let last_row = (to.y.ceil().max(0.0) as usize).min(height);
+  let mut x = from.x + (from.y.max(0.0) - from.y) * dxdy;
+
+  for row in first_row..last_row {
+    let row_start = row * stride;
+    let dy = ((row + 1) as f32).min(to.y) - (row as f32).max(from.y);
+    let x_next = x + dxdy * dy;
+    let d = dy * direction;
+
+    let (x0, x1) = match x < x_next { true => (x, x_next), false => (x_next, x) };
+    let x0_floor = x0.floor();
+    let x0i = x0_floor as usize;
+    let x1_ceil = x1.ceil();
+    let x1i = x1_ceil as usize;
+
+    if x1i <= x0i + 1 {
+      // The line stays within one cell, splitting its area between that cell and the next
+      let x_mid = 0.5 * (x + x_next) - x0_floor;
+      accumulation[row_start + x0i] += d - d * x_mid;
+      accumulation[row_start + x0i + 1] += d * x_mid;
+    } else {
+      let s = (x1 - x0).recip();
+      let x0_frac = x0 - x0_floor;
+      let a0 = 0.5 * s * (1.0 - x0_frac) * (1.0 - x0_frac);
+      let x1_frac = x1 - x1_ceil + 1.0;
+      let am = 0.5 * s * x1_frac * x1_frac;
+
+      accumulation[row_start + x0i] += d * a0;
+      if x1i == x0i + 2 {
+        accumulation[row_start + x0i + 1] += d * (1.0 - a0 - am);
+      } else {
+        let a1 = s * (1.5 - x0_frac);
+        accumulation[row_start + x0i + 1] += d * (a1 - a0);
+        for xi in (x0i + 2)..(x1i - 1) {
+          accumulation[row_start + xi] += d * s;
+        }
+        let a2 = a1 + ((x1i - x0i - 3) as f32) * s;
+        accumulation[row_start + x1i - 1] += d * (1.0 - a2 - am);
+      }
+      accumulation[row_start + x1i] += d * am;
+    }
+
+    x = x_next;
+  }
+}
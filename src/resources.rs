@@ -0,0 +1,234 @@
+//! Reader for Haiku resource files, which applications keep their icon in
+//!
+//! Resources are kept in standalone `.rsrc` files, or appended to an application's ELF binary.
+//! A resource file has a header, an index giving where each resource's data lies, and an info table
+//! giving each resource's type code, id and name, grouped by type. Numbers are stored in the byte order
+//! of the machine the file was made for, which the magic number tells apart.
+//! Icons are kept as resources of the vector icon type, usually with the id 101 and the name `BEOS:ICON`.
+
+use std::fmt;
+
+use bytes::{slice, ByteOrder};
+use error::HVIFError;
+use parser::parse;
+use types::*;
+
+/// The magic number at the start of resources
+const RESOURCES_MAGIC: u32 = 0x444f_1000;
+/// The size of the header at the start of resources
+const RESOURCES_HEADER_SIZE: usize = 68;
+/// The size of the header at the start of the index section, before the index entries
+const INDEX_SECTION_HEADER_SIZE: usize = 132;
+/// The size of one index entry, giving the offset and size of a resource's data
+const INDEX_ENTRY_SIZE: usize = 12;
+/// The size of the separator between the type blocks of the info table, and of the end of the table
+const INFO_SEPARATOR_SIZE: usize = 8;
+/// Resources appended to a binary start at a multiple of this many bytes
+const ELF_RESOURCE_ALIGNMENT: usize = 32;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// Reasons resources can fail to be read
+pub enum HVIFResourceError {
+  /// The input is neither a resource file nor an ELF binary with resources appended
+  NotResources,
+  /// The resources ended before they were complete
+  Truncated {
+    #[doc = "Byte offset, from the start of the input, of the data that was cut short"] offset: usize
+  },
+  /// The resources are malformed, such as a resource referring to an index entry that does not exist
+  Malformed {
+    #[doc = "Byte offset, from the start of the input, of the malformed data"] offset: usize
+  },
+  /// A vector icon resource does not hold a valid image
+  Icon {
+    #[doc = "The id of the resource"] id: i32,
+    #[doc = "Why the image failed to parse, with offsets counted from the start of the resource's data"] error: HVIFError
+  },
+}
+
+impl fmt::Display for HVIFResourceError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      HVIFResourceError::NotResources => write!(f, "no Haiku resources found"),
+      HVIFResourceError::Truncated { offset } => write!(f, "resources cut short at byte {}", offset),
+      HVIFResourceError::Malformed { offset } => write!(f, "malformed resources at byte {}", offset),
+      HVIFResourceError::Icon { id, error } => write!(f, "icon resource {}: {}", id, error),
+    }
+  }
+}
+
+impl ::std::error::Error for HVIFResourceError {}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A single resource, borrowing its data from the input
+pub struct HVIFResource<'a> {
+  /// The four character type code of the resource, such as `HVIF_VECTOR_ICON_TYPE`
+  pub type_code: u32,
+  /// The id of the resource, unique among resources of its type
+  pub id: i32,
+  /// The name of the resource, which may be empty
+  pub name: String,
+  /// The data of the resource
+  pub data: &'a [u8],
+}
+
+/// Reads every resource of a resource file, or of an ELF binary with resources appended, in the order of the info table
+pub fn parse_resources<'a>(input: &'a [u8]) -> Result<Vec<HVIFResource<'a>>, HVIFResourceError>
+{
+  let start = match input.starts_with(b"\x7fELF") {
+    true  => resources_elf_offset(input)?,
+    false => 0,
+  };
+  let data = &input[start..];
+  let order = match resources_byte_order(data) {
+    Some(order) => order,
+    None => return Err(HVIFResourceError::NotResources),
+  };
+  let truncated = |offset: usize| HVIFResourceError::Truncated { offset: start + offset };
+  let malformed = |offset: usize| HVIFResourceError::Malformed { offset: start + offset };
+  let u32_at = |offset: usize| order.u32(data, offset).ok_or_else(|| truncated(offset));
+
+  let resource_count = u32_at(4)? as usize;
+  let index_offset = u32_at(8)? as usize;
+  if index_offset < RESOURCES_HEADER_SIZE {
+    return Err(malformed(8))
+  }
+
+  // The index section header gives where the info table lies, and the index entries follow it
+  let info_offset = u32_at(index_offset + 120)? as usize;
+  let info_size = u32_at(index_offset + 124)? as usize;
+  let entries_offset = index_offset + INDEX_SECTION_HEADER_SIZE;
+  let mut entries = Vec::with_capacity(resource_count.min(data.len() / INDEX_ENTRY_SIZE));
+  for index in 0..resource_count {
+    let entry = entries_offset + index * INDEX_ENTRY_SIZE;
+    let (offset, size) = (u32_at(entry)? as usize, u32_at(entry + 4)? as usize);
+    entries.push(slice(data, offset, size).ok_or_else(|| truncated(entry))?);
+  }
+
+  // Blocks of resources of one type, each ended by a separator, fill the table up to its last eight bytes
+  let info_end = info_offset.checked_add(info_size).ok_or_else(|| malformed(index_offset + 124))?;
+  if info_end > data.len() {
+    return Err(truncated(data.len()))
+  }
+  let is_separator = |offset: usize| u32_at(offset).ok() == Some(0xffff_ffff) && u32_at(offset + 4).ok() == Some(0xffff_ffff);
+  let mut resources = Vec::with_capacity(entries.len());
+  let mut offset = info_offset;
+  while offset + INFO_SEPARATOR_SIZE < info_end {
+    let type_code = u32_at(offset)?;
+    offset += 4;
+    while offset + INFO_SEPARATOR_SIZE < info_end && !is_separator(offset) {
+      let id = u32_at(offset)? as i32;
+      let index = u32_at(offset + 4)? as usize;
+      let name_size = order.u16(data, offset + 8).ok_or_else(|| truncated(offset + 8))? as usize;
+      let name = slice(data, offset + 10, name_size).ok_or_else(|| truncated(offset + 10))?;
+      // Indices count from one
+      let data = match index.checked_sub(1).and_then(|index| entries.get(index)) {
+        Some(&data) => data,
+        None => return Err(malformed(offset + 4)),
+      };
+      resources.push(HVIFResource { type_code, id, name: resources_name(name), data });
+      offset += 10 + name_size;
+    }
+    offset += INFO_SEPARATOR_SIZE;
+  }
+  Ok(resources)
+}
+
+/// Reads every vector icon resource of a resource file, or of an ELF binary with resources appended
+pub fn resource_icons<'a>(input: &'a [u8]) -> Result<Vec<(HVIFResource<'a>, HVIFImage)>, HVIFResourceError>
+{
+  parse_resources(input)?.into_iter()
+    .filter(|resource| resource.type_code == HVIF_VECTOR_ICON_TYPE)
+    .map(|resource| match parse(resource.data) {
+      Ok(image) => Ok((resource, image)),
+      Err(error) => Err(HVIFResourceError::Icon { id: resource.id, error }),
+    })
+    .collect()
+}
+
+/// The byte order of resources, told apart by their magic number, if they start with one
+fn resources_byte_order(data: &[u8]) -> Option<ByteOrder>
+{
+  [ByteOrder::Little, ByteOrder::Big].iter().cloned().find(|&order| order.u32(data, 0) == Some(RESOURCES_MAGIC))
+}
+
+/// Where the resources appended to an ELF binary start
+///
+/// They follow the last byte the binary's headers account for, at the next multiple of the alignment
+/// of its segments, which is at least 32 bytes, so every such multiple is tried in turn.
+fn resources_elf_offset(input: &[u8]) -> Result<usize, HVIFResourceError>
+{
+  let malformed = |offset: usize| HVIFResourceError::Malformed { offset };
+  let order = match input.get(5) {
+    Some(&1) => ByteOrder::Little,
+    Some(&2) => ByteOrder::Big,
+    _ => return Err(malformed(5)),
+  };
+  let wide = match input.get(4) {
+    Some(&1) => false,
+    Some(&2) => true,
+    _ => return Err(malformed(4)),
+  };
+  let truncated = |offset: usize| HVIFResourceError::Truncated { offset };
+  let u16_at = |offset: usize| order.u16(input, offset).map(|value| value as usize).ok_or_else(|| truncated(offset));
+  // Addresses and offsets are four bytes wide in 32 bit binaries, and eight in 64 bit ones
+  let word_at = |offset: usize| match wide {
+    true  => order.u64(input, offset).map(|value| value as usize),
+    false => order.u32(input, offset).map(|value| value as usize),
+  }.ok_or_else(|| truncated(offset));
+
+  let (program_field, section_field) = if wide { (32, 40) } else { (28, 32) };
+  let (program_offset, section_offset) = (word_at(program_field)?, word_at(section_field)?);
+  // The sizes and counts of the program and section headers follow the size of the ELF header
+  let header_fields = if wide { 52 } else { 40 };
+  let program_size = u16_at(header_fields + 2)?;
+  let program_count = u16_at(header_fields + 4)?;
+  let section_size = u16_at(header_fields + 6)?;
+  let section_count = u16_at(header_fields + 8)?;
+  // Where a field of a program or section header lies, which headers placed past the end of memory do not have
+  let field_at = |table_field: usize, table: usize, size: usize, index: usize, field: usize| {
+    index.checked_mul(size).and_then(|header| header.checked_add(table)).and_then(|header| header.checked_add(field))
+      .ok_or_else(|| malformed(table_field))
+  };
+  let program_at = |index: usize, field: usize| field_at(program_field, program_offset, program_size, index, field);
+  let section_at = |index: usize, field: usize| field_at(section_field, section_offset, section_size, index, field);
+
+  let mut end = header_fields + 12;
+  end = end.max(program_offset.saturating_add(program_size.saturating_mul(program_count)));
+  end = end.max(section_offset.saturating_add(section_size.saturating_mul(section_count)));
+  for index in 0..program_count {
+    let (offset, size) = match wide {
+      true  => (word_at(program_at(index, 8)?)?, word_at(program_at(index, 32)?)?),
+      false => (word_at(program_at(index, 4)?)?, word_at(program_at(index, 16)?)?),
+    };
+    end = end.max(offset.saturating_add(size));
+  }
+  for index in 0..section_count {
+    // Sections without bits, such as .bss, take no room in the file
+    if order.u32(input, section_at(index, 4)?) == Some(8) {
+      continue
+    }
+    let (offset, size) = match wide {
+      true  => (word_at(section_at(index, 24)?)?, word_at(section_at(index, 32)?)?),
+      false => (word_at(section_at(index, 16)?)?, word_at(section_at(index, 20)?)?),
+    };
+    end = end.max(offset.saturating_add(size));
+  }
+
+  // Binaries whose headers reach past the input have nothing appended
+  let mut offset = end.div_ceil(ELF_RESOURCE_ALIGNMENT).saturating_mul(ELF_RESOURCE_ALIGNMENT);
+  while offset < input.len() {
+    if resources_byte_order(&input[offset..]).is_some() {
+      return Ok(offset)
+    }
+    offset += ELF_RESOURCE_ALIGNMENT;
+  }
+  Err(HVIFResourceError::NotResources)
+}
+
+/// A resource name, up to the zero byte that ends it
+fn resources_name(name: &[u8]) -> String
+{
+  let end = name.iter().position(|&byte| byte == 0).unwrap_or(name.len());
+  String::from_utf8_lossy(&name[..end]).into_owned()
+}
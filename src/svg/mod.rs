@@ -0,0 +1,8 @@
+//! Conversion between HVIF images and SVG documents
+pub mod writer;
+pub mod reader;
+mod path_data;
+mod xml;
+
+pub use self::writer::{svg_image, svg_image_at_scale};
+pub use self::reader::{parse, SVGError, SVGImport, SVGImportNote};
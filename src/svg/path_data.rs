@@ -0,0 +1,421 @@
+//! Reader for SVG path data, turning every kind of segment into lines and cubic Bézier curves
+
+use std::f32::consts::PI;
+
+use render::geometry::*;
+
+#[derive(Debug, Copy, Clone)]
+/// A point of a subpath, with the control points of the curves arriving at and leaving it
+pub struct Vertex {
+  /// The last control point of the curve arriving at the point, or the point itself after a line
+  pub point_in: Point,
+  /// The point the subpath passes through
+  pub point: Point,
+  /// The first control point of the curve leaving the point, or the point itself before a line
+  pub point_out: Point,
+}
+
+#[derive(Debug, Clone)]
+/// A connected run of segments, started by a move
+pub struct Subpath {
+  /// The points of the subpath, starting with the point moved to
+  pub vertices: Vec<Vertex>,
+  /// Whether the subpath was closed, joining its last point back to its first
+  pub closed: bool,
+}
+
+#[derive(Debug, Default)]
+/// Collects subpaths from a sequence of drawing operations
+pub struct PathBuilder {
+  /// The subpaths drawn so far, including the one being drawn
+  pub subpaths: Vec<Subpath>,
+  current: Option<Point>,
+  start: Option<Point>,
+  open: bool,
+}
+
+impl PathBuilder {
+  /// A builder that has drawn nothing yet, with its current point at the origin
+  pub fn new() -> PathBuilder {
+    PathBuilder::default()
+  }
+
+  fn current(&self) -> Point {
+    self.current.unwrap_or_else(|| Point::new(0.0, 0.0))
+  }
+
+  /// Moves to a point, where the next segment starts a new subpath
+  pub fn move_to(&mut self, point: Point) {
+    self.current = Some(point);
+    self.start = Some(point);
+    self.open = false;
+  }
+
+  /// The subpath being drawn, starting a new one at the current point if there is none
+  fn subpath(&mut self) -> &mut Subpath {
+    if !self.open {
+      let start = self.current();
+      self.start = Some(start);
+      self.subpaths.push(Subpath {
+        vertices: vec![Vertex { point_in: start, point: start, point_out: start }],
+        closed: false,
+      });
+      self.open = true;
+    }
+    self.subpaths.last_mut().unwrap()
+  }
+
+  /// Draws a straight line from the current point
+  pub fn line_to(&mut self, point: Point) {
+    self.subpath().vertices.push(Vertex { point_in: point, point, point_out: point });
+    self.current = Some(point);
+  }
+
+  /// Draws a cubic Bézier curve from the current point, with its two control points in order
+  pub fn cubic_to(&mut self, control_out: Point, control_in: Point, point: Point) {
+    {
+      let subpath = self.subpath();
+      subpath.vertices.last_mut().unwrap().point_out = control_out;
+      subpath.vertices.push(Vertex { point_in: control_in, point, point_out: point });
+    }
+    self.current = Some(point);
+  }
+
+  /// Draws a quadratic Bézier curve from the current point, as the cubic curve of the same shape
+  pub fn quadratic_to(&mut self, control: Point, point: Point) {
+    let current = self.current();
+    self.cubic_to(current.lerp(control, 2.0 / 3.0), point.lerp(control, 2.0 / 3.0), point);
+  }
+
+  /// Draws an elliptical arc as in SVG, from the current point to another, as a series of cubic curves
+  pub fn arc_to(&mut self, radius_x: f32, radius_y: f32, rotation: f32, large_arc: bool, sweep: bool, point: Point) {
+    let current = self.current();
+    if current == point {
+      return
+    }
+    let (mut rx, mut ry) = (radius_x.abs(), radius_y.abs());
+    if rx == 0.0 || ry == 0.0 {
+      return self.line_to(point)
+    }
+
+    // Find the center of the ellipse, following the SVG implementation notes
+    let (sin, cos) = rotation.to_radians().sin_cos();
+    let half = current.sub(point).scale(0.5);
+    let x1 = cos * half.x + sin * half.y;
+    let y1 = -sin * half.x + cos * half.y;
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+      rx *= lambda.sqrt();
+      ry *= lambda.sqrt();
+    }
+    let numerator = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+    let denominator = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let sign = if large_arc == sweep { -1.0 } else { 1.0 };
+    let coefficient = sign * (numerator / denominator).max(0.0).sqrt();
+    let center_x1 = coefficient * rx * y1 / ry;
+    let center_y1 = -coefficient * ry * x1 / rx;
+    let middle = current.lerp(point, 0.5);
+    let center = Point::new(cos * center_x1 - sin * center_y1 + middle.x, sin * center_x1 + cos * center_y1 + middle.y);
+
+    let start_angle = ((y1 - center_y1) / ry).atan2((x1 - center_x1) / rx);
+    let end_angle = ((-y1 - center_y1) / ry).atan2((-x1 - center_x1) / rx);
+    let mut sweep_angle = end_angle - start_angle;
+    if sweep && sweep_angle < 0.0 {
+      sweep_angle += 2.0 * PI;
+    } else if !sweep && sweep_angle > 0.0 {
+      sweep_angle -= 2.0 * PI;
+    }
+
+    // Each curve covers at most a quarter of the ellipse
+    let on_ellipse = |angle: f32| {
+      let (sin_angle, cos_angle) = angle.sin_cos();
+      (Point::new(cos * rx * cos_angle - sin * ry * sin_angle + center.x, sin * rx * cos_angle + cos * ry * sin_angle + center.y),
+       Point::new(-cos * rx * sin_angle - sin * ry * cos_angle, -sin * rx * sin_angle + cos * ry * cos_angle))
+    };
+    let segments = (sweep_angle.abs() / (PI / 2.0) - 1.0e-4).ceil().max(1.0) as usize;
+    let step = sweep_angle / (segments as f32);
+    let handle = 4.0 / 3.0 * (step / 4.0).tan();
+    for segment in 0..segments {
+      let (from, from_tangent) = on_ellipse(start_angle + step * (segment as f32));
+      let (to, to_tangent) = on_ellipse(start_angle + step * ((segment + 1) as f32));
+      let to = if segment + 1 == segments { point } else { to };
+      self.cubic_to(from.add(from_tangent.scale(handle)), to.sub(to_tangent.scale(handle)), to);
+    }
+  }
+
+  /// Closes the subpath being drawn, merging its last point into its first if they are the same
+  pub fn close(&mut self) {
+    if self.open {
+      let subpath = self.subpaths.last_mut().unwrap();
+      subpath.closed = true;
+      if subpath.vertices.len() > 1 {
+        let last = *subpath.vertices.last().unwrap();
+        if last.point == subpath.vertices[0].point {
+          subpath.vertices[0].point_in = last.point_in;
+          subpath.vertices.pop();
+        }
+      }
+    }
+    self.open = false;
+    self.current = self.start;
+  }
+}
+
+/// Reads SVG path data, returning the subpaths it draws and whether all of it could be read
+///
+/// As SVG asks, a path with an error in its data is drawn up to the error.
+pub fn path_data(data: &str) -> (Vec<Subpath>, bool)
+{
+  let mut builder = PathBuilder::new();
+  let mut lexer = Lexer { data: data.as_bytes(), position: 0 };
+  let mut command = None;
+  // The control point reflected by smooth curves, which only follow curves of their own kind
+  let mut last_cubic: Option<Point> = None;
+  let mut last_quadratic: Option<Point> = None;
+
+  loop {
+    lexer.skip_separators();
+    let next = match lexer.peek() {
+      Some(next) => next,
+      None => return (builder.subpaths, true),
+    };
+    if next.is_ascii_alphabetic() {
+      lexer.position += 1;
+      command = Some(next);
+    } else if command.is_none() {
+      return (builder.subpaths, false)
+    }
+    let letter = command.unwrap();
+    let relative = letter.is_ascii_lowercase();
+    let origin = match relative {
+      true  => builder.current(),
+      false => Point::new(0.0, 0.0),
+    };
+    let current = builder.current();
+
+    let (cubic, quadratic) = match lexer.segment(letter.to_ascii_uppercase(), origin) {
+      Some(Segment::Move(point)) => {
+        builder.move_to(point);
+        // Coordinates after a move continue as lines
+        command = Some(if relative { b'l' } else { b'L' });
+        (None, None)
+      },
+      Some(Segment::Line(point)) => { builder.line_to(point); (None, None) },
+      Some(Segment::Horizontal(x)) => { builder.line_to(Point::new(x, current.y)); (None, None) },
+      Some(Segment::Vertical(y)) => { builder.line_to(Point::new(current.x, y)); (None, None) },
+      Some(Segment::Cubic(control_out, control_in, point)) => {
+        let control_out = control_out.unwrap_or_else(|| reflect(last_cubic, current));
+        builder.cubic_to(control_out, control_in, point);
+        (Some(control_in), None)
+      },
+      Some(Segment::Quadratic(control, point)) => {
+        let control = control.unwrap_or_else(|| reflect(last_quadratic, current));
+        builder.quadratic_to(control, point);
+        (None, Some(control))
+      },
+      Some(Segment::Arc(rx, ry, rotation, large_arc, sweep, point)) => {
+        builder.arc_to(rx, ry, rotation, large_arc, sweep, point);
+        (None, None)
+      },
+      Some(Segment::Close) => {
+        builder.close();
+        // Only a new command may follow a close
+        command = None;
+        (None, None)
+      },
+      None => return (builder.subpaths, false),
+    };
+    last_cubic = cubic;
+    last_quadratic = quadratic;
+  }
+}
+
+/// The control point of a smooth curve, reflecting the previous curve's last control point through the current point
+fn reflect(control: Option<Point>, current: Point) -> Point
+{
+  match control {
+    Some(control) => current.add(current.sub(control)),
+    None => current,
+  }
+}
+
+/// One segment of path data, with its points made absolute
+///
+/// Smooth curves leave out their first control point.
+enum Segment {
+  Move(Point),
+  Line(Point),
+  Horizontal(f32),
+  Vertical(f32),
+  Cubic(Option<Point>, Point, Point),
+  Quadratic(Option<Point>, Point),
+  Arc(f32, f32, f32, bool, bool, Point),
+  Close,
+}
+
+struct Lexer<'a> {
+  data: &'a [u8],
+  position: usize,
+}
+
+impl<'a> Lexer<'a> {
+  fn peek(&self) -> Option<u8> {
+    self.data.get(self.position).cloned()
+  }
+
+  fn skip_separators(&mut self) {
+    while let Some(next) = self.peek() {
+      match next {
+        b' ' | b'\t' | b'\n' | b'\r' | b'\x0c' | b',' => self.position += 1,
+        _ => return,
+      }
+    }
+  }
+
+  fn segment(&mut self, command: u8, origin: Point) -> Option<Segment> {
+    Some(match command {
+      b'M' => Segment::Move(self.point(origin)?),
+      b'L' => Segment::Line(self.point(origin)?),
+      b'H' => Segment::Horizontal(self.number()? + origin.x),
+      b'V' => Segment::Vertical(self.number()? + origin.y),
+      b'C' => Segment::Cubic(Some(self.point(origin)?), self.point(origin)?, self.point(origin)?),
+      b'S' => Segment::Cubic(None, self.point(origin)?, self.point(origin)?),
+      b'Q' => Segment::Quadratic(Some(self.point(origin)?), self.point(origin)?),
+      b'T' => Segment::Quadratic(None, self.point(origin)?),
+      b'A' => Segment::Arc(self.number()?, self.number()?, self.number()?, self.flag()?, self.flag()?, self.point(origin)?),
+      b'Z' => Segment::Close,
+      _ => return None,
+    })
+  }
+
+  fn point(&mut self, origin: Point) -> Option<Point> {
+    let x = self.number()?;
+    let y = self.number()?;
+    Some(Point::new(origin.x + x, origin.y + y))
+  }
+
+  /// Reads an arc flag, which needs no separator from what follows it
+  fn flag(&mut self) -> Option<bool> {
+    self.skip_separators();
+    let flag = match self.peek()? {
+      b'0' => false,
+      b'1' => true,
+      _ => return None,
+    };
+    self.position += 1;
+    Some(flag)
+  }
+
+  fn number(&mut self) -> Option<f32> {
+    self.skip_separators();
+    let start = self.position;
+    if let Some(b'+') | Some(b'-') = self.peek() {
+      self.position += 1;
+    }
+    let mut digits = self.digits();
+    if self.peek() == Some(b'.') {
+      self.position += 1;
+      digits += self.digits();
+    }
+    if digits == 0 {
+      self.position = start;
+      return None
+    }
+    if let Some(b'e') | Some(b'E') = self.peek() {
+      let mantissa_end = self.position;
+      self.position += 1;
+      if let Some(b'+') | Some(b'-') = self.peek() {
+        self.position += 1;
+      }
+      if self.digits() == 0 {
+        self.position = mantissa_end;
+      }
+    }
+    ::std::str::from_utf8(&self.data[start..self.position]).ok()?.parse().ok()
+  }
+
+  fn digits(&mut self) -> usize {
+    let start = self.position;
+    while let Some(b'0'..=b'9') = self.peek() {
+      self.position += 1;
+    }
+    self.position - start
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn points(subpath: &Subpath) -> Vec<(f32, f32)> {
+    subpath.vertices.iter().map(|vertex| (vertex.point.x, vertex.point.y)).collect()
+  }
+
+  fn close_to(a: Point, b: Point) -> bool {
+    a.sub(b).length() < 1.0e-4
+  }
+
+  #[test]
+  fn reads_absolute_and_relative_commands() {
+    let (subpaths, complete) = path_data("M10 10 L20 10 h5 v-5 H0 V0 l1,1 z m 2 2 l 1 0");
+    assert!(complete);
+    assert_eq!(subpaths.len(), 2);
+    assert_eq!(points(&subpaths[0]), vec![(10.0, 10.0), (20.0, 10.0), (25.0, 10.0), (25.0, 5.0), (0.0, 5.0), (0.0, 0.0), (1.0, 1.0)]);
+    assert!(subpaths[0].closed);
+    // A relative move after a close starts from the start of the closed subpath
+    assert_eq!(points(&subpaths[1]), vec![(12.0, 12.0), (13.0, 12.0)]);
+    assert!(!subpaths[1].closed);
+  }
+
+  #[test]
+  fn repeats_commands_for_further_coordinates() {
+    // Coordinates after a move are lines, and after any other command repeat it
+    let (subpaths, complete) = path_data("m1 1 2 0 0 2 H5 6 c1 0 1 1 1 1 1 0 1 1 1 1");
+    assert!(complete);
+    assert_eq!(points(&subpaths[0]), vec![(1.0, 1.0), (3.0, 1.0), (3.0, 3.0), (5.0, 3.0), (6.0, 3.0), (7.0, 4.0), (8.0, 5.0)]);
+    // Numbers may run together where their signs and points set them apart
+    let (subpaths, _) = path_data("M-1-2.5.5.5L1e1-1");
+    assert_eq!(points(&subpaths[0]), vec![(-1.0, -2.5), (0.5, 0.5), (10.0, -1.0)]);
+  }
+
+  #[test]
+  fn makes_every_curve_cubic() {
+    let (subpaths, _) = path_data("M0 0 C1 2 3 2 4 0 S7 -2 8 0 Q10 2 12 0 T16 0");
+    let vertices = &subpaths[0].vertices;
+    assert_eq!(vertices.len(), 5);
+    assert_eq!((vertices[0].point_out, vertices[1].point_in), (Point::new(1.0, 2.0), Point::new(3.0, 2.0)));
+    // Smooth curves reflect the last control point of the curve before
+    assert_eq!(vertices[1].point_out, Point::new(5.0, -2.0));
+    // Quadratic control points sit two thirds of the way to the quadratic one
+    assert!(close_to(vertices[2].point_out, Point::new(8.0 + 4.0 / 3.0, 4.0 / 3.0)));
+    assert!(close_to(vertices[4].point_in, Point::new(16.0 - 4.0 / 3.0, -4.0 / 3.0)));
+  }
+
+  #[test]
+  fn draws_arcs_as_quarter_curves() {
+    // Half a circle of radius 5, with flags that need no separators
+    let (subpaths, complete) = path_data("M0 0 A5 5 0 0110 0");
+    assert!(complete);
+    let vertices = &subpaths[0].vertices;
+    assert_eq!(vertices.len(), 3);
+    // Sweeping through positive angles, which run upward from the start with y pointing down
+    assert!(close_to(vertices[1].point, Point::new(5.0, -5.0)));
+    assert!(close_to(vertices[0].point_out, Point::new(0.0, -5.0 * 0.552_284_8)));
+    assert_eq!(vertices[2].point, Point::new(10.0, 0.0));
+    // A radius too small to reach is scaled up, and no radius at all draws a line
+    let (subpaths, _) = path_data("M0 0 a1 1 0 1 1 10 0 A0 3 0 0 0 20 0");
+    let vertices = &subpaths[0].vertices;
+    assert!(close_to(vertices[2].point, Point::new(10.0, 0.0)));
+    assert_eq!(vertices.last().map(|vertex| vertex.point_in), Some(Point::new(20.0, 0.0)));
+  }
+
+  #[test]
+  fn stops_at_errors() {
+    let (subpaths, complete) = path_data("M0 0 L10 0 L10 x L20 20");
+    assert!(!complete);
+    assert_eq!(points(&subpaths[0]), vec![(0.0, 0.0), (10.0, 0.0)]);
+    assert!(!path_data("10 10").1);
+    assert!(!path_data("M0 0 z 5 5").1);
+    assert_eq!(path_data("").0.len(), 0);
+  }
+}
@@ -0,0 +1,846 @@
+//! Reader for SVG documents, converting the subset of SVG that HVIF can express
+//!
+//! Paths and the basic shapes are read with their fills, strokes, solid colors and linear and radial gradients,
+//! under the transformations of the groups holding them. Quadratic curves and arcs become cubic curves.
+//! Everything is brought onto HVIF's 64 by 64 canvas, fitting the document's view box to it.
+//! Whatever cannot be carried over, such as text, clipping or dashes, is left out and noted in the result.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use types::*;
+
+use render::HVIF_CANVAS_SIZE;
+use render::geometry::*;
+use parser::util::{HVIF_COORD_MIN, HVIF_COORD_MAX};
+use writer::HVIF_MAX_LIST_LENGTH;
+
+use svg::path_data::*;
+use svg::xml::{self, Element};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// Reasons an SVG document can fail to be read
+pub enum SVGError {
+  /// The document is not well-formed XML
+  Malformed {
+    #[doc = "The byte offset at which reading failed"] offset: usize
+  },
+  /// The root element of the document is not an `svg` element
+  NotSVG,
+}
+
+impl fmt::Display for SVGError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      SVGError::Malformed { offset } => write!(f, "malformed XML at byte {}", offset),
+      SVGError::NotSVG => write!(f, "the document is not an SVG document"),
+    }
+  }
+}
+
+impl ::std::error::Error for SVGError {}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Something in an SVG document that could not be carried over exactly
+pub enum SVGImportNote {
+  /// A feature that was left out entirely
+  Dropped(String),
+  /// A feature that was replaced by something close to it
+  Approximated(String),
+}
+
+impl fmt::Display for SVGImportNote {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      SVGImportNote::Dropped(ref feature) => write!(f, "dropped {}", feature),
+      SVGImportNote::Approximated(ref feature) => write!(f, "approximated {}", feature),
+    }
+  }
+}
+
+#[derive(Debug)]
+/// An image read from an SVG document, along with what could not be carried over
+pub struct SVGImport {
+  /// The image
+  pub image: HVIFImage,
+  /// Every feature of the document that was dropped or approximated, each noted once
+  pub notes: Vec<SVGImportNote>,
+}
+
+/// Reads an SVG document into an HVIF image
+pub fn parse(input: &str) -> Result<SVGImport, SVGError>
+{
+  let root = xml::parse(input)?;
+  if root.name != "svg" {
+    return Err(SVGError::NotSVG)
+  }
+
+  let mut importer = Importer {
+    definitions: HashMap::new(),
+    viewport: (HVIF_CANVAS_SIZE, HVIF_CANVAS_SIZE),
+    image: HVIFImage { styles: Vec::new(), paths: Vec::new(), shapes: Vec::new(), names: HVIFNames::default() },
+    notes: Vec::new(),
+  };
+  importer.collect_definitions(&root);
+
+  let transform = importer.root_transform(&root);
+  let context = Context { transform, ..Context::default() };
+  importer.children(&root, &context);
+
+  Ok(SVGImport { image: importer.image, notes: importer.notes })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// How the inside or outline of a shape is painted
+enum Paint {
+  None,
+  Color([u8; 3]),
+  CurrentColor,
+  /// A gradient, or the paint to use if there is no such gradient
+  Reference(String, Box<Paint>),
+}
+
+#[derive(Debug, Clone)]
+/// The properties inherited from the elements around a shape
+struct Context {
+  /// From the user space of the element to the canvas
+  transform: Affine,
+  fill: Paint,
+  fill_opacity: f32,
+  fill_even_odd: bool,
+  stroke: Paint,
+  stroke_opacity: f32,
+  stroke_width: f32,
+  line_join: HVIFLineJoin,
+  line_cap: HVIFLineCap,
+  miter_limit: f32,
+  /// The opacity of the groups holding the element
+  opacity: f32,
+  color: [u8; 3],
+}
+
+impl Default for Context {
+  fn default() -> Context {
+    Context {
+      transform: Affine::identity(),
+      fill: Paint::Color([0, 0, 0]),
+      fill_opacity: 1.0,
+      fill_even_odd: false,
+      stroke: Paint::None,
+      stroke_opacity: 1.0,
+      stroke_width: 1.0,
+      line_join: HVIFLineJoin::Miter,
+      line_cap: HVIFLineCap::Butt,
+      miter_limit: 4.0,
+      opacity: 1.0,
+      color: [0, 0, 0],
+    }
+  }
+}
+
+struct Importer<'a> {
+  /// Elements that can be referred to by their identifier
+  definitions: HashMap<&'a str, &'a Element>,
+  /// The size of the document's view box, which percentages in user space refer to
+  viewport: (f32, f32),
+  image: HVIFImage,
+  notes: Vec<SVGImportNote>,
+}
+
+impl<'a> Importer<'a> {
+  fn dropped(&mut self, feature: &str) {
+    self.note(SVGImportNote::Dropped(feature.to_string()));
+  }
+
+  fn approximated(&mut self, feature: &str) {
+    self.note(SVGImportNote::Approximated(feature.to_string()));
+  }
+
+  fn note(&mut self, note: SVGImportNote) {
+    if !self.notes.contains(&note) {
+      self.notes.push(note);
+    }
+  }
+
+  fn collect_definitions(&mut self, element: &'a Element) {
+    if let Some(id) = element.attribute("id") {
+      self.definitions.insert(id, element);
+    }
+    for child in &element.children {
+      self.collect_definitions(child);
+    }
+  }
+
+  /// The transformation fitting the document's view box onto the canvas
+  fn root_transform(&mut self, root: &Element) -> Affine {
+    let view_box: Vec<f32> = root.attribute("viewBox")
+      .map(|view_box| numbers(view_box).unwrap_or_default())
+      .unwrap_or_default();
+    let (x, y, width, height) = match view_box.len() {
+      4 => (view_box[0], view_box[1], view_box[2], view_box[3]),
+      _ => {
+        let width = root.attribute("width").and_then(length).unwrap_or(HVIF_CANVAS_SIZE);
+        let height = root.attribute("height").and_then(length).unwrap_or(HVIF_CANVAS_SIZE);
+        (0.0, 0.0, width, height)
+      },
+    };
+    if width <= 0.0 || height <= 0.0 {
+      return Affine::identity()
+    }
+    self.viewport = (width, height);
+
+    if let Some(aspect) = root.attribute("preserveAspectRatio") {
+      if aspect.trim() != "xMidYMid meet" && aspect.trim() != "xMidYMid" {
+        self.approximated("view box alignments other than centered");
+      }
+    }
+    // The view box is fit inside the canvas, keeping its aspect ratio, and centered
+    let scale = HVIF_CANVAS_SIZE / width.max(height);
+    Affine::translation(-x, -y)
+      .then(&Affine::scale(scale))
+      .then(&Affine::translation((HVIF_CANVAS_SIZE - width * scale) / 2.0, (HVIF_CANVAS_SIZE - height * scale) / 2.0))
+  }
+
+  fn children(&mut self, element: &'a Element, context: &Context) {
+    for child in &element.children {
+      self.element(child, context);
+    }
+  }
+
+  fn element(&mut self, element: &'a Element, parent: &Context) {
+    let name = element.name.as_str();
+    match name {
+      // Definitions are only drawn where they are referred to, and descriptions are not drawn at all
+      "defs" | "linearGradient" | "radialGradient" | "stop" | "title" | "desc" | "metadata" |
+      "clipPath" | "mask" | "pattern" | "filter" | "marker" | "symbol" => return,
+      "style" => return self.dropped("style sheets"),
+      "text" => return self.dropped("text"),
+      "image" => return self.dropped("embedded images"),
+      "use" => return self.dropped("references to other elements"),
+      "g" | "svg" | "a" | "switch" | "path" | "rect" | "circle" | "ellipse" | "line" | "polyline" | "polygon" => (),
+      // Elements of other namespaces, such as editor settings, carry nothing to draw
+      _ if name.contains(':') => return,
+      _ => return self.dropped(&format!("<{}> elements", name)),
+    }
+
+    if property(element, "display").is_some_and(|display| display == "none") {
+      return
+    }
+    for &(attribute, feature) in &[("clip-path", "clipping paths"), ("mask", "masks"), ("filter", "filters"),
+                                   ("marker-start", "markers"), ("marker-mid", "markers"), ("marker-end", "markers")] {
+      if property(element, attribute).is_some_and(|value| value != "none") {
+        self.dropped(feature);
+      }
+    }
+
+    let context = self.context(element, parent);
+    match name {
+      "svg" => {
+        // Only the root element fits a view box to the canvas
+        if element.attribute("viewBox").is_some() {
+          self.approximated("view boxes of nested documents");
+        }
+        let mut context = context;
+        let offset = Affine::translation(attribute_length(element, "x"), attribute_length(element, "y"));
+        context.transform = offset.then(&context.transform);
+        self.children(element, &context)
+      },
+      "g" | "a" | "switch" => self.children(element, &context),
+      _ => self.shape(element, &context),
+    }
+  }
+
+  /// The properties of an element, inheriting those it does not set from its parent
+  fn context(&mut self, element: &Element, parent: &Context) -> Context {
+    let mut context = parent.clone();
+    if let Some(transform) = element.attribute("transform") {
+      match transform_list(transform) {
+        Some(transform) => context.transform = transform.then(&parent.transform),
+        None => self.dropped("malformed transformations"),
+      }
+    }
+
+    if let Some(color) = property(element, "color") {
+      if let Some(color) = self.color(&color) {
+        context.color = color;
+      }
+    }
+    if let Some(fill) = property(element, "fill") {
+      context.fill = self.paint(&fill, &parent.fill);
+    }
+    if let Some(stroke) = property(element, "stroke") {
+      context.stroke = self.paint(&stroke, &parent.stroke);
+    }
+    if let Some(opacity) = property(element, "fill-opacity").and_then(|value| number(&value)) {
+      context.fill_opacity = opacity.clamp(0.0, 1.0);
+    }
+    if let Some(opacity) = property(element, "stroke-opacity").and_then(|value| number(&value)) {
+      context.stroke_opacity = opacity.clamp(0.0, 1.0);
+    }
+    if let Some(opacity) = property(element, "opacity").and_then(|value| number(&value)) {
+      context.opacity = parent.opacity * opacity.clamp(0.0, 1.0);
+    }
+    if let Some(rule) = property(element, "fill-rule") {
+      context.fill_even_odd = rule == "evenodd";
+    }
+    if let Some(width) = property(element, "stroke-width").and_then(|value| length(&value)) {
+      context.stroke_width = width;
+    }
+    if let Some(limit) = property(element, "stroke-miterlimit").and_then(|value| number(&value)) {
+      context.miter_limit = limit;
+    }
+    match property(element, "stroke-linejoin").as_deref() {
+      Some("miter") | Some("miter-clip") => context.line_join = HVIFLineJoin::Miter,
+      Some("round") => context.line_join = HVIFLineJoin::Round,
+      Some("bevel") => context.line_join = HVIFLineJoin::Bevel,
+      Some("arcs") => { context.line_join = HVIFLineJoin::Round; self.approximated("arc line joins"); },
+      _ => (),
+    }
+    match property(element, "stroke-linecap").as_deref() {
+      Some("butt") => context.line_cap = HVIFLineCap::Butt,
+      Some("round") => context.line_cap = HVIFLineCap::Round,
+      Some("square") => context.line_cap = HVIFLineCap::Square,
+      _ => (),
+    }
+    if property(element, "stroke-dasharray").is_some_and(|value| value != "none") {
+      self.dropped("dashed strokes");
+    }
+    context
+  }
+
+  fn paint(&mut self, value: &str, inherited: &Paint) -> Paint {
+    let value = value.trim();
+    match value {
+      "none" | "transparent" => Paint::None,
+      "inherit" => inherited.clone(),
+      "currentColor" => Paint::CurrentColor,
+      _ if value.starts_with("url(") => {
+        let end = value.find(')').unwrap_or(value.len());
+        let reference = value[4..end].trim().trim_matches(|c| c == '\'' || c == '"').trim_start_matches('#');
+        let fallback = match value[end..].trim_start_matches(')').trim() {
+          "" => Paint::None,
+          fallback => self.paint(fallback, inherited),
+        };
+        Paint::Reference(reference.to_string(), Box::new(fallback))
+      },
+      _ => match self.color(value) {
+        Some(color) => Paint::Color(color),
+        None => inherited.clone(),
+      },
+    }
+  }
+
+  fn color(&mut self, value: &str) -> Option<[u8; 3]> {
+    let color = color(value);
+    if color.is_none() && value != "inherit" && value != "currentColor" {
+      self.dropped(&format!("the unknown color {}", value));
+    }
+    color
+  }
+
+  fn shape(&mut self, element: &Element, context: &Context) {
+    let (subpaths, complete) = match element.name.as_str() {
+      "path" => path_data(element.attribute("d").unwrap_or("")),
+      name => (basic_shape(element, name), true),
+    };
+    if !complete {
+      self.dropped("malformed path data past the error");
+    }
+    if subpaths.is_empty() {
+      return
+    }
+
+    let bounds = bounding_box(&subpaths);
+    let fill = self.style_for(&context.fill, context, context.fill_opacity * context.opacity, bounds);
+    let stroke = match context.stroke_width > 0.0 {
+      true  => self.style_for(&context.stroke, context, context.stroke_opacity * context.opacity, bounds),
+      false => None,
+    };
+    if fill.is_none() && stroke.is_none() {
+      return
+    }
+    if fill.is_some() && context.fill_even_odd && subpaths.len() > 1 {
+      self.approximated("the even-odd fill rule with the non-zero fill rule");
+    }
+
+    let path_indices = match self.paths(subpaths, &context.transform) {
+      Some(path_indices) => path_indices,
+      None => return,
+    };
+
+    if let Some(style) = fill {
+      self.add_shape(style, path_indices.clone(), Vec::new());
+    }
+    if let Some(style) = stroke {
+      let transform = &context.transform;
+      if (transform.sx - transform.sy).abs() > 1.0e-3 || (transform.shx + transform.shy).abs() > 1.0e-3 {
+        self.approximated("strokes under non-uniform scaling");
+      }
+      // Images keep stroke widths in whole units
+      let width = context.stroke_width * transform.scale_factor();
+      let rounded = width.round().clamp(-128.0, 127.0);
+      if rounded != width {
+        self.approximated("stroke widths with whole units");
+      }
+      let stroke = HVIFTransformer::Stroke {
+        width: rounded,
+        line_join: context.line_join,
+        line_cap: context.line_cap,
+        miter_limit: context.miter_limit.round().clamp(0.0, 255.0) as u8,
+      };
+      self.add_shape(style, path_indices, vec![HVIFShapeModifier::HVIFTransformerList(vec![stroke])]);
+    }
+  }
+
+  /// Adds the paths of a shape, carried onto the canvas, returning their indices
+  fn paths(&mut self, subpaths: Vec<Subpath>, transform: &Affine) -> Option<Vec<u8>> {
+    if subpaths.iter().any(|subpath| subpath.vertices.len() > HVIF_MAX_LIST_LENGTH) {
+      self.approximated("subpaths of more than 255 points with several open paths");
+    }
+    let subpaths: Vec<Subpath> = subpaths.into_iter().flat_map(split_subpath).collect();
+    if self.image.paths.len() + subpaths.len() > HVIF_MAX_LIST_LENGTH {
+      self.dropped("paths beyond the 255 an image can hold");
+      return None
+    }
+
+    let range = HVIF_COORD_MIN..=HVIF_COORD_MAX;
+    let mut out_of_range = false;
+    let mut indices = Vec::with_capacity(subpaths.len());
+    for subpath in subpaths {
+      let mut to_canvas = |point: Point| {
+        let point = transform.apply(point);
+        out_of_range |= !range.contains(&point.x) || !range.contains(&point.y);
+        HVIFPoint { x: point.x, y: point.y }
+      };
+      let points = subpath.vertices.iter().map(|vertex| {
+        match vertex.point_in == vertex.point && vertex.point_out == vertex.point {
+          true  => HVIFPointCommand::Line { point: to_canvas(vertex.point) },
+          false => HVIFPointCommand::Curve {
+            point_in: to_canvas(vertex.point_in),
+            point: to_canvas(vertex.point),
+            point_out: to_canvas(vertex.point_out),
+          },
+        }
+      }).collect();
+      indices.push(self.image.paths.len() as u8);
+      self.image.paths.push(HVIFPath { closed: subpath.closed, points });
+    }
+    if out_of_range {
+      self.approximated("coordinates outside the range HVIF can store");
+    }
+    Some(indices)
+  }
+
+  fn add_shape(&mut self, style: HVIFStyle, path_indices: Vec<u8>, modifiers: Vec<HVIFShapeModifier>) {
+    if self.image.shapes.len() >= HVIF_MAX_LIST_LENGTH {
+      return self.dropped("shapes beyond the 255 an image can hold")
+    }
+    let style_index = match self.image.styles.iter().position(|existing| same_style(existing, &style)) {
+      Some(index) => index,
+      None if self.image.styles.len() < HVIF_MAX_LIST_LENGTH => {
+        self.image.styles.push(style);
+        self.image.styles.len() - 1
+      },
+      None => return self.dropped("styles beyond the 255 an image can hold"),
+    };
+    self.image.shapes.push(HVIFShape { style_index: style_index as u8, path_indices, modifiers });
+  }
+
+  /// The style painting a shape with the given opacity, if it is painted at all
+  fn style_for(&mut self, paint: &Paint, context: &Context, opacity: f32, bounds: (Point, Point)) -> Option<HVIFStyle> {
+    match *paint {
+      Paint::None => None,
+      Paint::Color(color) => Some(solid_style(color, opacity)),
+      Paint::CurrentColor => Some(solid_style(context.color, opacity)),
+      Paint::Reference(ref id, ref fallback) => {
+        let definition = self.definitions.get(id.as_str()).cloned();
+        match definition {
+          Some(element) if element.name == "linearGradient" || element.name == "radialGradient" =>
+            self.gradient(element, context, opacity, bounds),
+          Some(element) => {
+            self.dropped(&format!("<{}> paint", element.name));
+            None
+          },
+          None => self.style_for(fallback, context, opacity, bounds),
+        }
+      },
+    }
+  }
+
+  fn gradient(&mut self, element: &'a Element, context: &Context, opacity: f32, bounds: (Point, Point)) -> Option<HVIFStyle> {
+    // Gradients may take their attributes and stops from the gradients they refer to
+    let mut chain = vec![element];
+    while chain.len() < 16 {
+      let next = chain.last().unwrap().attribute("href").or_else(|| chain.last().unwrap().attribute("xlink:href"))
+        .and_then(|reference| self.definitions.get(reference.trim_start_matches('#')).cloned());
+      match next {
+        Some(next) if !chain.iter().any(|&seen| ::std::ptr::eq(seen, next)) => chain.push(next),
+        _ => break,
+      }
+    }
+    let attribute = |name: &str| chain.iter().filter_map(|element| element.attribute(name)).next();
+
+    let stops = match chain.iter().find(|element| element.children.iter().any(|child| child.name == "stop")) {
+      Some(element) => self.stops(element, opacity),
+      None => Vec::new(),
+    };
+    match stops.len() {
+      0 => return None,
+      1 => return Some(solid_style([stops[0].red, stops[0].green, stops[0].blue], (stops[0].alpha as f32) / 255.0)),
+      _ => (),
+    }
+
+    if attribute("spreadMethod").is_some_and(|method| method != "pad") {
+      self.approximated("reflected and repeated gradients");
+    }
+    let bounding_box_units = attribute("gradientUnits") != Some("userSpaceOnUse");
+    let (horizontal, vertical) = match bounding_box_units {
+      true  => (1.0, 1.0),
+      false => self.viewport,
+    };
+    let coordinate = |name: &str, default: f32, extent: f32| {
+      attribute(name).and_then(|value| fraction_or_length(value, extent)).unwrap_or(default)
+    };
+
+    // From HVIF's gradient space to the gradient's own coordinates
+    let (gradient_type, to_coordinates) = match element.name.as_str() {
+      "linearGradient" => {
+        let start = Point::new(coordinate("x1", 0.0, horizontal), coordinate("y1", 0.0, vertical));
+        let end = Point::new(coordinate("x2", horizontal, horizontal), coordinate("y2", 0.0, vertical));
+        if start == end {
+          let last = stops.last().unwrap();
+          return Some(solid_style([last.red, last.green, last.blue], (last.alpha as f32) / 255.0))
+        }
+        // The gradient runs along x from -64 to 64, and its y axis is perpendicular to that
+        let along = end.sub(start).scale(1.0 / 128.0);
+        let middle = start.lerp(end, 0.5);
+        (HVIFGradientType::Linear, Affine { sx: along.x, shy: along.y, shx: -along.y, sy: along.x, tx: middle.x, ty: middle.y })
+      },
+      _ => {
+        let diagonal = (horizontal.hypot(vertical)) / ::std::f32::consts::SQRT_2;
+        let center = Point::new(coordinate("cx", 0.5 * horizontal, horizontal), coordinate("cy", 0.5 * vertical, vertical));
+        let radius = coordinate("r", 0.5 * diagonal, diagonal);
+        let focus = Point::new(coordinate("fx", center.x, horizontal), coordinate("fy", center.y, vertical));
+        if focus != center {
+          self.approximated("radial gradients with a focal point away from their center");
+        }
+        (HVIFGradientType::Circular, Affine::scale(radius / 64.0).then(&Affine::translation(center.x, center.y)))
+      },
+    };
+
+    let mut to_canvas = to_coordinates;
+    if let Some(transform) = attribute("gradientTransform") {
+      match transform_list(transform) {
+        Some(transform) => to_canvas = to_canvas.then(&transform),
+        None => self.dropped("malformed transformations"),
+      }
+    }
+    if bounding_box_units {
+      let (min, max) = bounds;
+      let to_bounds = Affine { sx: max.x - min.x, shy: 0.0, shx: 0.0, sy: max.y - min.y, tx: min.x, ty: min.y };
+      to_canvas = to_canvas.then(&to_bounds);
+    }
+    to_canvas = to_canvas.then(&context.transform);
+
+    Some(HVIFStyle::Gradient(HVIFGradient {
+      gradient_type,
+      transform: Some(HVIFMatrix::from(to_canvas)),
+      colors: stops,
+    }))
+  }
+
+  fn stops(&mut self, element: &Element, opacity: f32) -> Vec<HVIFGradientColor> {
+    let mut colors = Vec::new();
+    let mut last_offset = 0.0f32;
+    for stop in element.children.iter().filter(|child| child.name == "stop") {
+      if colors.len() == HVIF_MAX_LIST_LENGTH {
+        self.dropped("gradient stops beyond the 255 a gradient can hold");
+        break
+      }
+      let offset = stop.attribute("offset").and_then(|value| fraction_or_length(value, 1.0)).unwrap_or(0.0);
+      // Offsets never go backwards
+      last_offset = offset.clamp(last_offset, 1.0);
+      let color = match property(stop, "stop-color") {
+        Some(ref value) if value == "currentColor" => [0, 0, 0],
+        Some(value) => self.color(&value).unwrap_or([0, 0, 0]),
+        None => [0, 0, 0],
+      };
+      let stop_opacity = property(stop, "stop-opacity").and_then(|value| number(&value)).unwrap_or(1.0).clamp(0.0, 1.0);
+      colors.push(HVIFGradientColor {
+        stop_offset: (last_offset * 255.0).round() as u8,
+        alpha: (stop_opacity * opacity * 255.0).round() as u8,
+        red: color[0],
+        green: color[1],
+        blue: color[2],
+      });
+    }
+    colors
+  }
+}
+
+/// The outline of one of SVG's basic shapes
+fn basic_shape(element: &Element, name: &str) -> Vec<Subpath>
+{
+  let mut builder = PathBuilder::new();
+  let attribute = |name: &str| attribute_length(element, name);
+  match name {
+    "rect" => {
+      let (x, y, width, height) = (attribute("x"), attribute("y"), attribute("width"), attribute("height"));
+      if width <= 0.0 || height <= 0.0 {
+        return Vec::new()
+      }
+      // A missing radius takes the value of the other one
+      let rx = element.attribute("rx").and_then(length);
+      let ry = element.attribute("ry").and_then(length);
+      let rx = rx.or(ry).unwrap_or(0.0).clamp(0.0, width / 2.0);
+      let ry = ry.or(Some(rx)).unwrap_or(0.0).clamp(0.0, height / 2.0);
+      builder.move_to(Point::new(x + rx, y));
+      builder.line_to(Point::new(x + width - rx, y));
+      builder.arc_to(rx, ry, 0.0, false, true, Point::new(x + width, y + ry));
+      builder.line_to(Point::new(x + width, y + height - ry));
+      builder.arc_to(rx, ry, 0.0, false, true, Point::new(x + width - rx, y + height));
+      builder.line_to(Point::new(x + rx, y + height));
+      builder.arc_to(rx, ry, 0.0, false, true, Point::new(x, y + height - ry));
+      builder.line_to(Point::new(x, y + ry));
+      builder.arc_to(rx, ry, 0.0, false, true, Point::new(x + rx, y));
+      builder.close();
+    },
+    "circle" | "ellipse" => {
+      let (cx, cy) = (attribute("cx"), attribute("cy"));
+      let (rx, ry) = match name {
+        "circle" => (attribute("r"), attribute("r")),
+        _ => (attribute("rx"), attribute("ry")),
+      };
+      if rx <= 0.0 || ry <= 0.0 {
+        return Vec::new()
+      }
+      builder.move_to(Point::new(cx + rx, cy));
+      builder.arc_to(rx, ry, 0.0, false, true, Point::new(cx, cy + ry));
+      builder.arc_to(rx, ry, 0.0, false, true, Point::new(cx - rx, cy));
+      builder.arc_to(rx, ry, 0.0, false, true, Point::new(cx, cy - ry));
+      builder.arc_to(rx, ry, 0.0, false, true, Point::new(cx + rx, cy));
+      builder.close();
+    },
+    "line" => {
+      builder.move_to(Point::new(attribute("x1"), attribute("y1")));
+      builder.line_to(Point::new(attribute("x2"), attribute("y2")));
+    },
+    _ => {
+      // Polylines and polygons, which ignore a trailing odd coordinate
+      let coordinates = element.attribute("points").and_then(numbers).unwrap_or_default();
+      for (index, pair) in coordinates.chunks(2).filter(|pair| pair.len() == 2).enumerate() {
+        match index {
+          0 => builder.move_to(Point::new(pair[0], pair[1])),
+          _ => builder.line_to(Point::new(pair[0], pair[1])),
+        }
+      }
+      if name == "polygon" {
+        builder.close();
+      }
+    },
+  }
+  builder.subpaths
+}
+
+/// Splits a subpath with more points than a path can hold into open runs of points, each starting where the last ended
+fn split_subpath(subpath: Subpath) -> Vec<Subpath>
+{
+  if subpath.vertices.len() <= HVIF_MAX_LIST_LENGTH {
+    return vec![subpath]
+  }
+  let mut vertices = subpath.vertices;
+  if subpath.closed {
+    // The segment closing the subpath goes back to its first point
+    let first = vertices[0];
+    vertices.push(first);
+  }
+  let mut runs = Vec::new();
+  let mut start = 0;
+  while start + 1 < vertices.len() {
+    let end = (start + HVIF_MAX_LIST_LENGTH).min(vertices.len());
+    runs.push(Subpath { vertices: vertices[start..end].to_vec(), closed: false });
+    start = end - 1;
+  }
+  runs
+}
+
+/// The smallest and largest corners of the box around a shape's points and control points
+fn bounding_box(subpaths: &[Subpath]) -> (Point, Point)
+{
+  let mut min = Point::new(f32::INFINITY, f32::INFINITY);
+  let mut max = Point::new(f32::NEG_INFINITY, f32::NEG_INFINITY);
+  for vertex in subpaths.iter().flat_map(|subpath| subpath.vertices.iter()) {
+    for point in &[vertex.point_in, vertex.point, vertex.point_out] {
+      min = Point::new(min.x.min(point.x), min.y.min(point.y));
+      max = Point::new(max.x.max(point.x), max.y.max(point.y));
+    }
+  }
+  (min, max)
+}
+
+/// The most compact style for a solid color
+fn solid_style(color: [u8; 3], opacity: f32) -> HVIFStyle
+{
+  let alpha = (opacity * 255.0).round() as u8;
+  let (red, green, blue) = (color[0], color[1], color[2]);
+  match (red == green && green == blue, alpha) {
+    (true, 255)  => HVIFStyle::SolidGrayNoAlpha { value: red },
+    (true, _)    => HVIFStyle::SolidGray { value: red, alpha },
+    (false, 255) => HVIFStyle::SolidColorNoAlpha { red, green, blue },
+    (false, _)   => HVIFStyle::SolidColor { red, green, blue, alpha },
+  }
+}
+
+/// Whether two solid styles are the same, so that shapes can share them
+///
+/// Gradients are never shared, as each is laid out for its own shape.
+fn same_style(a: &HVIFStyle, b: &HVIFStyle) -> bool
+{
+  match (a, b) {
+    (&HVIFStyle::SolidColor { red: r1, green: g1, blue: b1, alpha: a1 }, &HVIFStyle::SolidColor { red: r2, green: g2, blue: b2, alpha: a2 }) =>
+      (r1, g1, b1, a1) == (r2, g2, b2, a2),
+    (&HVIFStyle::SolidColorNoAlpha { red: r1, green: g1, blue: b1 }, &HVIFStyle::SolidColorNoAlpha { red: r2, green: g2, blue: b2 }) =>
+      (r1, g1, b1) == (r2, g2, b2),
+    (&HVIFStyle::SolidGray { value: v1, alpha: a1 }, &HVIFStyle::SolidGray { value: v2, alpha: a2 }) => (v1, a1) == (v2, a2),
+    (&HVIFStyle::SolidGrayNoAlpha { value: v1 }, &HVIFStyle::SolidGrayNoAlpha { value: v2 }) => v1 == v2,
+    _ => false,
+  }
+}
+
+/// The value of a property of an element, from its style attribute or else its presentation attribute
+fn property(element: &Element, name: &str) -> Option<String>
+{
+  let from_style = element.attribute("style").and_then(|style| {
+    style.split(';').filter_map(|declaration| {
+      let mut parts = declaration.splitn(2, ':');
+      match (parts.next(), parts.next()) {
+        (Some(key), Some(value)) if key.trim() == name => Some(value.replace("!important", "").trim().to_string()),
+        _ => None,
+      }
+    }).next_back()
+  });
+  from_style.or_else(|| element.attribute(name).map(|value| value.trim().to_string()))
+}
+
+/// The length held by an attribute of an element, or zero
+fn attribute_length(element: &Element, name: &str) -> f32
+{
+  element.attribute(name).and_then(length).unwrap_or(0.0)
+}
+
+fn number(value: &str) -> Option<f32>
+{
+  value.trim().parse().ok().filter(|value: &f32| value.is_finite())
+}
+
+/// Reads a length in user units, converting absolute units to pixels
+fn length(value: &str) -> Option<f32>
+{
+  let value = value.trim();
+  let split = value.find(|c: char| c.is_ascii_alphabetic() || c == '%').unwrap_or(value.len());
+  let factor = match &value[split..] {
+    "" | "px" => 1.0,
+    "pt" => 4.0 / 3.0,
+    "pc" => 16.0,
+    "mm" => 96.0 / 25.4,
+    "cm" => 96.0 / 2.54,
+    "in" => 96.0,
+    "em" => 16.0,
+    _ => return None,
+  };
+  number(&value[..split]).map(|number| number * factor)
+}
+
+/// Reads a length, or a percentage of some extent
+fn fraction_or_length(value: &str, extent: f32) -> Option<f32>
+{
+  let value = value.trim();
+  match value.ends_with('%') {
+    true  => number(&value[..value.len() - 1]).map(|percentage| percentage / 100.0 * extent),
+    false => length(value),
+  }
+}
+
+/// Reads a list of numbers separated by whitespace or commas
+fn numbers(value: &str) -> Option<Vec<f32>>
+{
+  value.split(|c: char| c.is_whitespace() || c == ',')
+    .filter(|part| !part.is_empty())
+    .map(number)
+    .collect()
+}
+
+/// Reads a transformation list, as in the `transform` attribute
+fn transform_list(value: &str) -> Option<Affine>
+{
+  let mut transform = Affine::identity();
+  let mut rest = value.trim();
+  while !rest.is_empty() {
+    let open = rest.find('(')?;
+    let close = rest.find(')')?;
+    let name = rest[..open].trim().trim_start_matches(',').trim();
+    let arguments = numbers(&rest[open + 1..close])?;
+    let next = match (name, arguments.as_slice()) {
+      ("matrix", &[sx, shy, shx, sy, tx, ty]) => Affine { sx, shy, shx, sy, tx, ty },
+      ("translate", &[x]) => Affine::translation(x, 0.0),
+      ("translate", &[x, y]) => Affine::translation(x, y),
+      ("scale", &[factor]) => Affine::scale(factor),
+      ("scale", &[x, y]) => Affine { sx: x, sy: y, ..Affine::identity() },
+      ("rotate", &[angle]) => rotation(angle),
+      ("rotate", &[angle, x, y]) =>
+        Affine::translation(-x, -y).then(&rotation(angle)).then(&Affine::translation(x, y)),
+      ("skewX", &[angle]) => Affine { shx: angle.to_radians().tan(), ..Affine::identity() },
+      ("skewY", &[angle]) => Affine { shy: angle.to_radians().tan(), ..Affine::identity() },
+      _ => return None,
+    };
+    // Each transformation applies inside the ones before it
+    transform = next.then(&transform);
+    rest = rest[close + 1..].trim_start();
+  }
+  Some(transform)
+}
+
+fn rotation(degrees: f32) -> Affine
+{
+  let (sin, cos) = degrees.to_radians().sin_cos();
+  Affine { sx: cos, shy: sin, shx: -sin, sy: cos, tx: 0.0, ty: 0.0 }
+}
+
+/// Reads a color in hexadecimal, functional or named notation
+fn color(value: &str) -> Option<[u8; 3]>
+{
+  let value = value.trim();
+  if let Some(digits) = value.strip_prefix('#') {
+    let channel = |index: usize, width: usize| u8::from_str_radix(digits.get(index * width..(index + 1) * width)?, 16).ok();
+    return match digits.len() {
+      3 => Some([channel(0, 1)? * 17, channel(1, 1)? * 17, channel(2, 1)? * 17]),
+      6 => Some([channel(0, 2)?, channel(1, 2)?, channel(2, 2)?]),
+      _ => None,
+    }
+  }
+  if value.starts_with("rgb(") && value.ends_with(')') {
+    let channels: Vec<&str> = value[4..value.len() - 1].split(',').map(str::trim).collect();
+    if channels.len() != 3 {
+      return None
+    }
+    let mut color = [0u8; 3];
+    for (channel, text) in color.iter_mut().zip(channels) {
+      *channel = match text.ends_with('%') {
+        true  => number(&text[..text.len() - 1])? / 100.0 * 255.0,
+        false => number(text)?,
+      }.round().clamp(0.0, 255.0) as u8;
+    }
+    return Some(color)
+  }
+  NAMED_COLORS.iter().find(|&&(name, _)| name.eq_ignore_ascii_case(value)).map(|&(_, color)| color)
+}
+
+/// The basic named colors of CSS, along with a few common extended ones
+const NAMED_COLORS: [(&str, [u8; 3]); 24] = [
+  ("black", [0, 0, 0]), ("silver", [192, 192, 192]), ("gray", [128, 128, 128]), ("grey", [128, 128, 128]),
+  ("white", [255, 255, 255]), ("maroon", [128, 0, 0]), ("red", [255, 0, 0]), ("purple", [128, 0, 128]),
+  ("fuchsia", [255, 0, 255]), ("magenta", [255, 0, 255]), ("green", [0, 128, 0]), ("lime", [0, 255, 0]),
+  ("olive", [128, 128, 0]), ("yellow", [255, 255, 0]), ("navy", [0, 0, 128]), ("blue", [0, 0, 255]),
+  ("teal", [0, 128, 128]), ("aqua", [0, 255, 255]), ("cyan", [0, 255, 255]), ("orange", [255, 165, 0]),
+  ("brown", [165, 42, 42]), ("pink", [255, 192, 203]), ("gold", [255, 215, 0]), ("darkgray", [169, 169, 169]),
+];
@@ -0,0 +1,279 @@
+//! Writer for SVG documents, following the layout of Icon-O-Matic's own SVG export
+//!
+//! SVG has no equivalent for some of what HVIF can express, so these are approximated:
+//! diamond, XY and square root XY gradients become radial gradients, conic gradients become linear gradients
+//! running from the gradient's right to its left, and a contour becomes a stroke of twice its width under the fill.
+//! Contours that shrink the shape and perspective transformers are left out.
+
+use std::fmt::Write;
+
+use types::*;
+
+use render::HVIF_CANVAS_SIZE;
+
+/// Writes an entire HVIF image as an SVG document
+///
+/// Every shape is written, whatever its level of detail range.
+pub fn svg_image(image: &HVIFImage) -> String
+{
+  svg_document(image, image.shapes.iter())
+}
+
+/// Writes the shapes of an HVIF image visible at a scale, relative to the 64 by 64 canvas, as an SVG document
+pub fn svg_image_at_scale(image: &HVIFImage, scale: f32) -> String
+{
+  svg_document(image, image.shapes_visible_at(scale))
+}
+
+fn svg_document<'a, I>(image: &'a HVIFImage, shapes: I) -> String
+  where I: Iterator<Item = &'a HVIFShape>
+{
+  let mut out = String::new();
+  out.push_str("<?xml version=\"1.0\" standalone=\"no\"?>\n");
+  let _ = writeln!(out, "<svg width=\"{0}\" height=\"{0}\" viewBox=\"0 0 {0} {0}\" xmlns=\"http://www.w3.org/2000/svg\" version=\"1.1\">",
+                   HVIF_CANVAS_SIZE);
+
+  let gradients: Vec<(usize, &HVIFGradient)> = image.styles.iter().enumerate()
+    .filter_map(|(index, style)| match *style {
+      HVIFStyle::Gradient(ref gradient) => Some((index, gradient)),
+      _ => None,
+    })
+    .collect();
+  if !gradients.is_empty() {
+    out.push_str("  <defs>\n");
+    for (index, gradient) in gradients {
+      svg_gradient(&mut out, index, gradient);
+    }
+    out.push_str("  </defs>\n");
+  }
+
+  for shape in shapes {
+    svg_shape(&mut out, image, shape);
+  }
+
+  out.push_str("</svg>\n");
+  out
+}
+
+fn svg_gradient(out: &mut String, index: usize, gradient: &HVIFGradient)
+{
+  let transform = match gradient.transform {
+    Some(matrix) => format!(" gradientTransform=\"{}\"", svg_matrix(matrix)),
+    None => String::new(),
+  };
+  let (element, geometry) = match gradient.gradient_type {
+    HVIFGradientType::Linear => ("linearGradient", "x1=\"-64\" y1=\"0\" x2=\"64\" y2=\"0\""),
+    HVIFGradientType::Conic  => ("linearGradient", "x1=\"64\" y1=\"0\" x2=\"-64\" y2=\"0\""),
+    HVIFGradientType::Circular | HVIFGradientType::Diamond |
+    HVIFGradientType::XY | HVIFGradientType::SqrtXY => ("radialGradient", "cx=\"0\" cy=\"0\" r=\"64\""),
+  };
+
+  let _ = writeln!(out, "    <{} id=\"{}\" gradientUnits=\"userSpaceOnUse\" {}{}>",
+                   element, svg_gradient_id(index), geometry, transform);
+  for color in &gradient.colors {
+    let _ = write!(out, "      <stop offset=\"{}\" stop-color=\"{}\"",
+                   svg_number((color.stop_offset as f32) / 255.0), svg_color(color.red, color.green, color.blue));
+    if color.alpha != 255 {
+      let _ = write!(out, " stop-opacity=\"{}\"", svg_opacity(color.alpha));
+    }
+    out.push_str("/>\n");
+  }
+  let _ = writeln!(out, "    </{}>", element);
+}
+
+fn svg_shape(out: &mut String, image: &HVIFImage, shape: &HVIFShape)
+{
+  let style = match image.styles.get(shape.style_index as usize) {
+    Some(style) => style,
+    None => return,
+  };
+
+  let mut transform = None;
+  let mut transformers: &[HVIFTransformer] = &[];
+  for modifier in &shape.modifiers {
+    match *modifier {
+      HVIFShapeModifier::HVIFTransformMatrix(matrix) => transform = Some(svg_matrix(matrix)),
+      HVIFShapeModifier::HVIFTranslation(offset) =>
+        transform = Some(format!("translate({} {})", svg_number(offset.x), svg_number(offset.y))),
+      HVIFShapeModifier::HVIFTransformerList(ref list) => transformers = list,
+      HVIFShapeModifier::HVIFHinting | HVIFShapeModifier::HVIFLODScale { .. } => (),
+    }
+  }
+
+  // Affine transformers move the path itself, so that gradients stay in the shape's space
+  let mut path_transform = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+  let mut stroke = None;
+  let mut contour = None;
+  for transformer in transformers {
+    match *transformer {
+      HVIFTransformer::Affine(matrix) => path_transform = svg_concat(path_transform, matrix.into()),
+      HVIFTransformer::Stroke { width, line_join, line_cap, miter_limit } =>
+        stroke = Some((width, line_join, line_cap, miter_limit)),
+      HVIFTransformer::Contour { width, line_join, miter_limit } if width > 0.0 =>
+        contour = Some((width * 2.0, line_join, HVIFLineCap::Butt, miter_limit)),
+      HVIFTransformer::Contour { .. } | HVIFTransformer::Perspective => (),
+    }
+  }
+
+  let mut data = String::new();
+  for &index in &shape.path_indices {
+    if let Some(path) = image.paths.get(index as usize) {
+      svg_path_data(&mut data, path, &path_transform);
+    }
+  }
+
+  let paint = svg_paint(shape.style_index as usize, style);
+  let mut attributes = String::new();
+  match stroke {
+    Some(stroke) => {
+      let _ = write!(attributes, "fill:none;stroke:{}", paint);
+      svg_stroke_attributes(&mut attributes, stroke, style);
+    },
+    None => {
+      let _ = write!(attributes, "fill:{}", paint);
+      if let Some(opacity) = svg_style_opacity(style) {
+        let _ = write!(attributes, ";fill-opacity:{}", opacity);
+      }
+      if let Some(contour) = contour {
+        let _ = write!(attributes, ";stroke:{}", paint);
+        svg_stroke_attributes(&mut attributes, contour, style);
+        attributes.push_str(";paint-order:stroke");
+      }
+    },
+  }
+
+  out.push_str("  <path");
+  if let Some(transform) = transform {
+    let _ = write!(out, " transform=\"{}\"", transform);
+  }
+  let _ = writeln!(out, " style=\"{}\" d=\"{}\"/>", attributes, data.trim_end());
+}
+
+fn svg_stroke_attributes(out: &mut String, stroke: (f32, HVIFLineJoin, HVIFLineCap, u8), style: &HVIFStyle)
+{
+  let (width, line_join, line_cap, miter_limit) = stroke;
+  let _ = write!(out, ";stroke-width:{};stroke-linejoin:{};stroke-linecap:{};stroke-miterlimit:{}",
+                 svg_number(width.abs()), svg_line_join(line_join), svg_line_cap(line_cap), miter_limit);
+  if let Some(opacity) = svg_style_opacity(style) {
+    let _ = write!(out, ";stroke-opacity:{}", opacity);
+  }
+}
+
+/// Appends the path data of a path, moving its points by the given transformation
+fn svg_path_data(out: &mut String, path: &HVIFPath, transform: &[f32; 6])
+{
+  let vertices = path.vertices();
+  let first = match vertices.first() {
+    Some(first) => first,
+    None => return,
+  };
+  let point = |point: HVIFPoint| {
+    let x = transform[0] * point.x + transform[2] * point.y + transform[4];
+    let y = transform[1] * point.x + transform[3] * point.y + transform[5];
+    format!("{} {}", svg_number(x), svg_number(y))
+  };
+
+  let _ = write!(out, "M {} ", point(first.point));
+  let segment_count = match path.closed {
+    true  => vertices.len(),
+    false => vertices.len() - 1,
+  };
+  for i in 0..segment_count {
+    let from = &vertices[i];
+    let to = &vertices[(i + 1) % vertices.len()];
+    let straight = svg_same_point(from.point_out, from.point) && svg_same_point(to.point_in, to.point);
+    match (straight, path.closed && i + 1 == vertices.len()) {
+      // A straight closing segment is drawn by closing the path
+      (true, true) => (),
+      (true, false) => { let _ = write!(out, "L {} ", point(to.point)); },
+      (false, _) => { let _ = write!(out, "C {} {} {} ", point(from.point_out), point(to.point_in), point(to.point)); },
+    }
+  }
+  if path.closed {
+    out.push_str("Z ");
+  }
+}
+
+fn svg_same_point(a: HVIFPoint, b: HVIFPoint) -> bool
+{
+  a.x == b.x && a.y == b.y
+}
+
+/// Multiplies two transformations in storage order, applying the first before the second
+fn svg_concat(first: [f32; 6], second: [f32; 6]) -> [f32; 6]
+{
+  [
+    second[0] * first[0] + second[2] * first[1],
+    second[1] * first[0] + second[3] * first[1],
+    second[0] * first[2] + second[2] * first[3],
+    second[1] * first[2] + second[3] * first[3],
+    second[0] * first[4] + second[2] * first[5] + second[4],
+    second[1] * first[4] + second[3] * first[5] + second[5],
+  ]
+}
+
+fn svg_matrix(matrix: HVIFMatrix) -> String
+{
+  let values: [f32; 6] = matrix.into();
+  let values: Vec<String> = values.iter().map(|&value| svg_number(value)).collect();
+  format!("matrix({})", values.join(" "))
+}
+
+fn svg_paint(index: usize, style: &HVIFStyle) -> String
+{
+  match *style {
+    HVIFStyle::SolidColor { red, green, blue, .. } | HVIFStyle::SolidColorNoAlpha { red, green, blue } =>
+      svg_color(red, green, blue),
+    HVIFStyle::SolidGray { value, .. } | HVIFStyle::SolidGrayNoAlpha { value } => svg_color(value, value, value),
+    HVIFStyle::Gradient(_) => format!("url(#{})", svg_gradient_id(index)),
+  }
+}
+
+/// The opacity of a solid style, if it is not opaque
+fn svg_style_opacity(style: &HVIFStyle) -> Option<String>
+{
+  match *style {
+    HVIFStyle::SolidColor { alpha, .. } | HVIFStyle::SolidGray { alpha, .. } if alpha != 255 => Some(svg_opacity(alpha)),
+    _ => None,
+  }
+}
+
+fn svg_gradient_id(index: usize) -> String
+{
+  format!("gradient{}", index)
+}
+
+fn svg_color(red: u8, green: u8, blue: u8) -> String
+{
+  format!("#{:02x}{:02x}{:02x}", red, green, blue)
+}
+
+fn svg_opacity(alpha: u8) -> String
+{
+  svg_number((alpha as f32) / 255.0)
+}
+
+fn svg_line_join(line_join: HVIFLineJoin) -> &'static str
+{
+  match line_join {
+    HVIFLineJoin::Miter | HVIFLineJoin::MiterRevert | HVIFLineJoin::MiterRound => "miter",
+    HVIFLineJoin::Round => "round",
+    HVIFLineJoin::Bevel => "bevel",
+  }
+}
+
+fn svg_line_cap(line_cap: HVIFLineCap) -> &'static str
+{
+  match line_cap {
+    HVIFLineCap::Butt => "butt",
+    HVIFLineCap::Square => "square",
+    HVIFLineCap::Round => "round",
+  }
+}
+
+/// Formats a number as briefly as possible while still reading back as the same value
+fn svg_number(value: f32) -> String
+{
+  // Adding zero turns negative zero into positive zero
+  format!("{}", value + 0.0)
+}
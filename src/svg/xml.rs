@@ -0,0 +1,250 @@
+//! A small XML reader, building a tree of the elements of a document
+//!
+//! Only what SVG documents need is supported: elements, attributes, and the predefined and numeric entities.
+//! Text, comments, processing instructions and document type declarations are skipped.
+
+use svg::reader::SVGError;
+
+/// How deeply elements may nest, far deeper than any drawing needs
+const MAX_DEPTH: usize = 256;
+
+#[derive(Debug)]
+/// An element of an XML document, with its attributes and child elements
+pub struct Element {
+  /// The name of the element, without any namespace prefix of `svg`
+  pub name: String,
+  /// The attributes of the element, in document order
+  pub attributes: Vec<(String, String)>,
+  /// The child elements of the element, in document order
+  pub children: Vec<Element>,
+}
+
+impl Element {
+  /// The value of an attribute of this element
+  pub fn attribute(&self, name: &str) -> Option<&str> {
+    self.attributes.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+  }
+}
+
+/// Reads the root element of an XML document
+pub fn parse(input: &str) -> Result<Element, SVGError>
+{
+  let mut reader = Reader { input, position: 0 };
+  reader.skip_misc()?;
+  let root = reader.element(0)?;
+  reader.skip_misc()?;
+  match reader.position == input.len() {
+    true  => Ok(root),
+    false => Err(reader.malformed()),
+  }
+}
+
+struct Reader<'a> {
+  input: &'a str,
+  position: usize,
+}
+
+impl<'a> Reader<'a> {
+  fn rest(&self) -> &'a str {
+    &self.input[self.position..]
+  }
+
+  fn malformed(&self) -> SVGError {
+    SVGError::Malformed { offset: self.position }
+  }
+
+  fn skip_whitespace(&mut self) {
+    let rest = self.rest();
+    self.position += rest.len() - rest.trim_start().len();
+  }
+
+  /// Skips past the next occurrence of a delimiter
+  fn skip_past(&mut self, delimiter: &str) -> Result<(), SVGError> {
+    match self.rest().find(delimiter) {
+      Some(index) => { self.position += index + delimiter.len(); Ok(()) },
+      None => { self.position = self.input.len(); Err(self.malformed()) },
+    }
+  }
+
+  /// Skips comments, processing instructions and document type declarations between elements
+  fn skip_misc(&mut self) -> Result<(), SVGError> {
+    loop {
+      self.skip_whitespace();
+      let rest = self.rest();
+      if rest.starts_with("<?") {
+        self.skip_past("?>")?;
+      } else if rest.starts_with("<!--") {
+        self.skip_past("-->")?;
+      } else if rest.starts_with("<!DOCTYPE") {
+        // The internal subset may hold declarations of its own, ending before the declaration does
+        let end = rest.find('>');
+        match (rest.find('['), end) {
+          (Some(open), Some(end)) if open < end => { self.skip_past("]")?; self.skip_past(">")?; },
+          _ => self.skip_past(">")?,
+        }
+      } else {
+        return Ok(())
+      }
+    }
+  }
+
+  fn name(&mut self) -> Result<&'a str, SVGError> {
+    let rest = self.rest();
+    let length = rest.find(|c: char| c.is_whitespace() || c == '/' || c == '>' || c == '=').unwrap_or(rest.len());
+    if length == 0 {
+      return Err(self.malformed())
+    }
+    self.position += length;
+    Ok(&rest[..length])
+  }
+
+  fn expect(&mut self, text: &str) -> Result<(), SVGError> {
+    match self.rest().starts_with(text) {
+      true  => { self.position += text.len(); Ok(()) },
+      false => Err(self.malformed()),
+    }
+  }
+
+  /// Reads an element held by as many elements as its depth, with everything it holds
+  fn element(&mut self, depth: usize) -> Result<Element, SVGError> {
+    if depth > MAX_DEPTH {
+      return Err(self.malformed())
+    }
+    self.expect("<")?;
+    let name = self.name()?;
+    let mut element = Element { name: local_name(name).to_string(), attributes: Vec::new(), children: Vec::new() };
+
+    loop {
+      self.skip_whitespace();
+      if self.rest().starts_with("/>") {
+        self.position += 2;
+        return Ok(element)
+      }
+      if self.rest().starts_with('>') {
+        self.position += 1;
+        break
+      }
+      let key = self.name()?;
+      self.skip_whitespace();
+      self.expect("=")?;
+      self.skip_whitespace();
+      let quote = match self.rest().chars().next() {
+        Some(quote @ '"') | Some(quote @ '\'') => quote,
+        _ => return Err(self.malformed()),
+      };
+      self.position += 1;
+      let length = match self.rest().find(quote) {
+        Some(length) => length,
+        None => return Err(self.malformed()),
+      };
+      let value = decode_entities(&self.rest()[..length]);
+      self.position += length + 1;
+      element.attributes.push((key.to_string(), value));
+    }
+
+    loop {
+      let rest = self.rest();
+      if rest.starts_with("</") {
+        self.position += 2;
+        if self.name()? != name {
+          return Err(self.malformed())
+        }
+        self.skip_whitespace();
+        self.expect(">")?;
+        return Ok(element)
+      } else if rest.starts_with("<!--") {
+        self.skip_past("-->")?;
+      } else if rest.starts_with("<![CDATA[") {
+        self.skip_past("]]>")?;
+      } else if rest.starts_with("<?") {
+        self.skip_past("?>")?;
+      } else if rest.starts_with('<') {
+        let child = self.element(depth + 1)?;
+        element.children.push(child);
+      } else if rest.is_empty() {
+        return Err(self.malformed())
+      } else {
+        // Text is not drawn, so it is skipped
+        self.position += rest.find('<').unwrap_or(rest.len());
+      }
+    }
+  }
+}
+
+/// The name of an element, without the prefix of the SVG namespace
+fn local_name(name: &str) -> &str
+{
+  match name.starts_with("svg:") {
+    true  => &name[4..],
+    false => name,
+  }
+}
+
+/// Replaces the predefined and numeric entities of a piece of text with the characters they stand for
+fn decode_entities(text: &str) -> String
+{
+  let mut out = String::with_capacity(text.len());
+  let mut rest = text;
+  while let Some(start) = rest.find('&') {
+    out.push_str(&rest[..start]);
+    rest = &rest[start..];
+    let end = match rest.find(';') {
+      Some(end) => end,
+      None => break,
+    };
+    let entity = &rest[1..end];
+    let character = match entity {
+      "amp" => Some('&'),
+      "lt" => Some('<'),
+      "gt" => Some('>'),
+      "quot" => Some('"'),
+      "apos" => Some('\''),
+      _ if entity.starts_with("#x") => u32::from_str_radix(&entity[2..], 16).ok().and_then(::std::char::from_u32),
+      _ if entity.starts_with('#') => entity[1..].parse().ok().and_then(::std::char::from_u32),
+      _ => None,
+    };
+    match character {
+      Some(character) => { out.push(character); rest = &rest[end + 1..]; },
+      // Unknown entities are kept as they are
+      None => { out.push('&'); rest = &rest[1..]; },
+    }
+  }
+  out.push_str(rest);
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn decodes_entities() {
+    assert_eq!(decode_entities("a &lt; b &amp;&amp; c &gt; d"), "a < b && c > d");
+    assert_eq!(decode_entities("&quot;&apos;&#65;&#x42;&#x1F600;"), "\"'AB\u{1F600}");
+    // Unknown and unfinished entities are kept as they are
+    assert_eq!(decode_entities("&nbsp; &#xZZ; & &amp"), "&nbsp; &#xZZ; & &amp");
+  }
+
+  #[test]
+  fn reads_elements_and_attributes() {
+    let root = parse("<?xml version=\"1.0\"?>\n<!DOCTYPE svg [ <!ENTITY e \"x\"> ]>\n\
+      <svg:svg width='64' id=\"a&amp;b\"><!-- note --><g>text<![CDATA[<path/>]]><path d=\"M0 0\"/></g></svg:svg>\n").unwrap();
+    assert_eq!(root.name, "svg");
+    assert_eq!((root.attribute("width"), root.attribute("id"), root.attribute("height")), (Some("64"), Some("a&b"), None));
+    assert_eq!(root.children.len(), 1);
+    assert_eq!(root.children[0].children.iter().map(|child| child.name.as_str()).collect::<Vec<_>>(), vec!["path"]);
+  }
+
+  #[test]
+  fn rejects_malformed_documents() {
+    assert_eq!(parse("<svg><g></svg>").err(), Some(SVGError::Malformed { offset: 13 }));
+    assert!(parse("<svg width=64/>").is_err());
+    assert!(parse("<svg/><svg/>").is_err());
+    assert!(parse("<svg>").is_err());
+
+    let nested = |depth: usize| format!("{}{}", "<g>".repeat(depth), "</g>".repeat(depth));
+    assert!(parse(&nested(MAX_DEPTH + 1)).is_ok());
+    assert!(parse(&nested(MAX_DEPTH + 2)).is_err());
+    assert!(parse(&nested(100_000)).is_err());
+  }
+}
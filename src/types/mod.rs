@@ -1,18 +1,52 @@
 //! Types for HVIF images
+//!
+//! # Serialization
+//!
+//! With the `serde` feature, images and everything in them can be serialized and deserialized.
+//! The schema below is stable, and is the one `hvif dump --json` writes, through serde when the tool
+//! is built with the feature. `data/sample.json` holds `data/sample.hvif` in it, with every kind of entry.
+//!
+//! * An image is `{ "styles": [...], "paths": [...], "shapes": [...] }`, with a `names` object
+//!   alongside only when an editor named any of its entries.
+//! * Styles, point commands, shape modifiers and transformers are tagged by their `type` field,
+//!   whose values are the snake case names of their variants.
+//!   `HLine` and `VLine` are tagged `hline` and `vline`, and modifiers drop their `HVIF` prefix,
+//!   so that `HVIFLODScale` is tagged `lod_scale`.
+//! * A gradient style holds its `gradient_type`, its `transform` or null, and its `colors`,
+//!   each with a `stop_offset`, `red`, `green`, `blue` and `alpha`.
+//! * Points are `{ "x": ..., "y": ... }`, with coordinates as numbers.
+//! * Matrices are arrays of six numbers in storage order, the horizontal scale, vertical shear,
+//!   horizontal shear, vertical scale, horizontal translation and vertical translation,
+//!   rather than the 24 bit floats they are stored as.
+//! * A modifier or transformer holding a value names it: `transform_matrix` and `affine` hold a `matrix`,
+//!   `translation` an `offset`, and `transformer_list` its `transformers`.
+//! * Line joins and caps are the snake case names of their variants, such as `miter_revert`.
+//!
+//! A shape drawing the first path with the first style, moved by a translation, is written as
+//!
+//! ```json
+//! { "style_index": 0, "path_indices": [0], "modifiers": [{ "type": "translation", "offset": { "x": 4, "y": 8 } }] }
+//! ```
 pub mod path;
 pub mod shape;
 pub mod style;
+#[cfg(feature = "serde")]
+mod schema;
 
 pub use self::style::*;
 pub use self::path::*;
 pub use self::shape::*;
 
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
 #[cfg(feature = "core")]
 use collections::vec::Vec;
 #[cfg(not(feature = "core"))]
 use std::vec::Vec;
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 /// A fully specified HVIF image
 pub struct HVIFImage {
   /// All styles belonging to this image
@@ -20,9 +54,50 @@ pub struct HVIFImage {
   /// All paths belonging to this image
   pub paths: Vec<HVIFPath>,
   /// All shapes belonging to this image
-  pub shapes: Vec<HVIFShape>
+  pub shapes: Vec<HVIFShape>,
+  /// Names of the styles, paths and shapes, which only editor documents keep
+  #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "HVIFNames::is_empty"))]
+  pub names: HVIFNames,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// Names given to the entries of an image by an editor such as Icon-O-Matic, which HVIF itself does not store
+///
+/// Each list is indexed like the matching list of the image, and may be shorter than it, or empty.
+pub struct HVIFNames {
+  /// The names of the styles
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub styles: Vec<String>,
+  /// The names of the paths
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub paths: Vec<String>,
+  /// The names of the shapes
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub shapes: Vec<String>,
+}
+
+impl HVIFNames {
+  /// Whether no entry has a name
+  pub fn is_empty(&self) -> bool
+  {
+    self.styles.iter().chain(&self.paths).chain(&self.shapes).all(|name| name.is_empty())
+  }
 }
 
+impl HVIFImage {
+  /// The shapes that show at a scale, relative to the 64 by 64 canvas, in drawing order
+  ///
+  /// An icon drawn at 16 pixels has a scale of 0.25, and one drawn at 128 pixels has a scale of 2.
+  pub fn shapes_visible_at(&self, scale: f32) -> impl Iterator<Item = &HVIFShape>
+  {
+    self.shapes.iter().filter(move |shape| shape.visible_at(scale))
+  }
+}
+
+/// Haiku's type code for vector icons, `'VICN'`, which marks attributes and resources holding HVIF images
+pub const HVIF_VECTOR_ICON_TYPE: u32 = 0x5649_434e;
+
 #[derive(Debug, Copy, Clone)]
 /// Flags that modify the parsing of the following data
 pub struct HVIFFlag(u8);
@@ -31,34 +106,39 @@ impl HVIFFlag {
   /// Checks whether the flag is set on a byte
   pub fn is_set_on(&self, flags: u8) -> bool
   {
-    let masked: u8 = flags & &self.clone().into();
+    let masked: u8 = flags & self.0;
     masked != 0
   }
+  /// Sets the flag on a byte, returning the result
+  pub fn set_on(&self, flags: u8) -> u8
+  {
+    flags | self.0
+  }
 }
 
-/// ?
-pub const HVIF_GRADIENT_FLAG_TRANSFORM     : HVIFFlag = HVIFFlag(0b0000_0001);
+/// The gradient has its own affine transformation matrix
+pub const HVIF_GRADIENT_FLAG_TRANSFORM     : HVIFFlag = HVIFFlag(0b0000_0010);
 /// The colors in this gradient have no alpha channel
-pub const HVIF_GRADIENT_FLAG_NO_ALPHA      : HVIFFlag = HVIFFlag(0b0000_0010);
+pub const HVIF_GRADIENT_FLAG_NO_ALPHA      : HVIFFlag = HVIFFlag(0b0000_0100);
 /// The colors in this gradient have a deeper color depth per channel of 16 bits, instead of 8
-pub const HVIF_GRADIENT_FLAG_COLORS_16_BIT : HVIFFlag = HVIFFlag(0b0000_0100);
+pub const HVIF_GRADIENT_FLAG_COLORS_16_BIT : HVIFFlag = HVIFFlag(0b0000_1000);
 /// The colors in this gradient are all grayscale
-pub const HVIF_GRADIENT_FLAG_GRAYS         : HVIFFlag = HVIFFlag(0b0000_1000);
+pub const HVIF_GRADIENT_FLAG_GRAYS         : HVIFFlag = HVIFFlag(0b0001_0000);
 
 /// The path's last point is connected to its first point
-pub const HVIF_PATH_FLAG_CLOSED        : HVIFFlag = HVIFFlag(0b0000_0001);
+pub const HVIF_PATH_FLAG_CLOSED        : HVIFFlag = HVIFFlag(0b0000_0010);
 /// The path has a command section, and can use HLine and VLine commands
-pub const HVIF_PATH_FLAG_USES_COMMANDS : HVIFFlag = HVIFFlag(0b0000_0010);
+pub const HVIF_PATH_FLAG_USES_COMMANDS : HVIFFlag = HVIFFlag(0b0000_0100);
 /// The path is made up entirely of straight lines
-pub const HVIF_PATH_FLAG_NO_CURVES     : HVIFFlag = HVIFFlag(0b0000_0100);
+pub const HVIF_PATH_FLAG_NO_CURVES     : HVIFFlag = HVIFFlag(0b0000_1000);
 
 /// This shape uses an affine transformation matrix to translate, rotate, and scale itself
-pub const HVIF_SHAPE_FLAG_TRANSFORM        : HVIFFlag = HVIFFlag(0b0000_0001);
-/// ?
-pub const HVIF_SHAPE_FLAG_HINTING          : HVIFFlag = HVIFFlag(0b0000_0010);
-/// ?
-pub const HVIF_SHAPE_FLAG_LOD_SCALE        : HVIFFlag = HVIFFlag(0b0000_0100);
-/// ?
-pub const HVIF_SHAPE_FLAG_HAS_TRANSFORMERS : HVIFFlag = HVIFFlag(0b0000_1000);
-/// ?
-pub const HVIF_SHAPE_FLAG_TRANSLATION      : HVIFFlag = HVIFFlag(0b0001_0000);
+pub const HVIF_SHAPE_FLAG_TRANSFORM        : HVIFFlag = HVIFFlag(0b0000_0010);
+/// This shape's points are snapped to the pixel grid when rendered
+pub const HVIF_SHAPE_FLAG_HINTING          : HVIFFlag = HVIFFlag(0b0000_0100);
+/// This shape is only visible within a range of scales
+pub const HVIF_SHAPE_FLAG_LOD_SCALE        : HVIFFlag = HVIFFlag(0b0000_1000);
+/// This shape has a list of transformers
+pub const HVIF_SHAPE_FLAG_HAS_TRANSFORMERS : HVIFFlag = HVIFFlag(0b0001_0000);
+/// This shape is offset by a translation, in lieu of a full transformation matrix
+pub const HVIF_SHAPE_FLAG_TRANSLATION      : HVIFFlag = HVIFFlag(0b0010_0000);
@@ -1,26 +1,80 @@
 //! Types for HVIF paths
 
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 /// An HVIF path, drawn between points
 pub struct HVIFPath {
+  /// Whether the path's last point is connected back to its first point
+  pub closed: bool,
   /// A list of commands that represent the sequence of points for this path
   pub points: Vec<HVIFPointCommand>
 }
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
 /// One or more points, and a command that specifies how the point/s are to be interpreted
 pub enum HVIFPointCommand {
   /// A horizontal line from the previous x-coordinate to this one
+  #[cfg_attr(feature = "serde", serde(rename = "hline"))]
   HLine { #[doc="target x-coordinate"] x: f32 },
   /// A vertical line from the previous y-coordinate to this one
+  #[cfg_attr(feature = "serde", serde(rename = "vline"))]
   VLine { #[doc="target y-coordinate"] y: f32 },
   /// A straight line from the previous point to this one
   Line  { #[doc="target point"] point: HVIFPoint },
   /// A cubic Bezier curve from the previous point to this one
-  Curve { #[doc="inital control point"] point_in: HVIFPoint, #[doc="target point"] point: HVIFPoint, #[doc="final control point"] point_out: HVIFPoint},
+  Curve { #[doc="control point of the curve arriving at the target point"] point_in: HVIFPoint, #[doc="target point"] point: HVIFPoint, #[doc="control point of the curve leaving the target point"] point_out: HVIFPoint},
+}
+
+impl HVIFPath {
+  /// Resolves each command to an absolute vertex
+  ///
+  /// Horizontal and vertical lines inherit their other coordinate from the previous point, starting from the origin.
+  /// Anything but a curve has both of its control points on the vertex itself.
+  pub fn vertices(&self) -> Vec<HVIFVertex>
+  {
+    let mut current = HVIFPoint { x: 0.0, y: 0.0 };
+    self.points.iter().map(|command| {
+      match *command {
+        HVIFPointCommand::HLine { x } => {
+          current = HVIFPoint { x, y: current.y };
+          HVIFVertex { point_in: current, point: current, point_out: current }
+        },
+        HVIFPointCommand::VLine { y } => {
+          current = HVIFPoint { x: current.x, y };
+          HVIFVertex { point_in: current, point: current, point_out: current }
+        },
+        HVIFPointCommand::Line { point } => {
+          current = point;
+          HVIFVertex { point_in: current, point: current, point_out: current }
+        },
+        HVIFPointCommand::Curve { point_in, point, point_out } => {
+          current = point;
+          HVIFVertex { point_in, point: current, point_out }
+        },
+      }
+    }).collect()
+  }
+}
+
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// A point on a path, along with the control points of the curves arriving at and leaving it
+pub struct HVIFVertex {
+  /// The control point of the curve arriving at this vertex
+  pub point_in: HVIFPoint,
+  /// The vertex itself
+  pub point: HVIFPoint,
+  /// The control point of the curve leaving this vertex
+  pub point_out: HVIFPoint,
 }
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 /// A simple 2D point in the XY plane, where each coordinate is a floating point value
 pub struct HVIFPoint {
   /// The x-coordinate
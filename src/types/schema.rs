@@ -0,0 +1,78 @@
+//! The serialized forms of types that serde cannot derive as they are
+//!
+//! Variants holding a single value are tagged with a `type` field like the others,
+//! so their value is given a name, and they are converted to and from these forms.
+
+use serde::{Serialize, Deserialize};
+
+use types::*;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ModifierSchema {
+  TransformMatrix { matrix: HVIFMatrix },
+  Hinting,
+  #[serde(rename = "lod_scale")]
+  LODScale { min: f32, max: f32 },
+  Translation { offset: HVIFPoint },
+  TransformerList { transformers: Vec<HVIFTransformer> },
+}
+
+impl From<HVIFShapeModifier> for ModifierSchema {
+  fn from(modifier: HVIFShapeModifier) -> Self {
+    match modifier {
+      HVIFShapeModifier::HVIFTransformMatrix(matrix) => ModifierSchema::TransformMatrix { matrix },
+      HVIFShapeModifier::HVIFHinting => ModifierSchema::Hinting,
+      HVIFShapeModifier::HVIFLODScale { min, max } => ModifierSchema::LODScale { min, max },
+      HVIFShapeModifier::HVIFTranslation(offset) => ModifierSchema::Translation { offset },
+      HVIFShapeModifier::HVIFTransformerList(transformers) => ModifierSchema::TransformerList { transformers },
+    }
+  }
+}
+
+impl From<ModifierSchema> for HVIFShapeModifier {
+  fn from(modifier: ModifierSchema) -> Self {
+    match modifier {
+      ModifierSchema::TransformMatrix { matrix } => HVIFShapeModifier::HVIFTransformMatrix(matrix),
+      ModifierSchema::Hinting => HVIFShapeModifier::HVIFHinting,
+      ModifierSchema::LODScale { min, max } => HVIFShapeModifier::HVIFLODScale { min, max },
+      ModifierSchema::Translation { offset } => HVIFShapeModifier::HVIFTranslation(offset),
+      ModifierSchema::TransformerList { transformers } => HVIFShapeModifier::HVIFTransformerList(transformers),
+    }
+  }
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TransformerSchema {
+  Affine { matrix: HVIFMatrix },
+  Contour { width: f32, line_join: HVIFLineJoin, miter_limit: u8 },
+  Perspective,
+  Stroke { width: f32, line_join: HVIFLineJoin, line_cap: HVIFLineCap, miter_limit: u8 },
+}
+
+impl From<HVIFTransformer> for TransformerSchema {
+  fn from(transformer: HVIFTransformer) -> Self {
+    match transformer {
+      HVIFTransformer::Affine(matrix) => TransformerSchema::Affine { matrix },
+      HVIFTransformer::Contour { width, line_join, miter_limit } =>
+        TransformerSchema::Contour { width, line_join, miter_limit },
+      HVIFTransformer::Perspective => TransformerSchema::Perspective,
+      HVIFTransformer::Stroke { width, line_join, line_cap, miter_limit } =>
+        TransformerSchema::Stroke { width, line_join, line_cap, miter_limit },
+    }
+  }
+}
+
+impl From<TransformerSchema> for HVIFTransformer {
+  fn from(transformer: TransformerSchema) -> Self {
+    match transformer {
+      TransformerSchema::Affine { matrix } => HVIFTransformer::Affine(matrix),
+      TransformerSchema::Contour { width, line_join, miter_limit } =>
+        HVIFTransformer::Contour { width, line_join, miter_limit },
+      TransformerSchema::Perspective => HVIFTransformer::Perspective,
+      TransformerSchema::Stroke { width, line_join, line_cap, miter_limit } =>
+        HVIFTransformer::Stroke { width, line_join, line_cap, miter_limit },
+    }
+  }
+}
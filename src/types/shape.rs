@@ -2,7 +2,13 @@
 
 use types::path::*;
 
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+#[cfg(feature = "serde")]
+use types::schema;
+
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 /// An HVIF shape, consisting of a single style, one or more paths, and optional additional transformation data
 pub struct HVIFShape {
   /// The index of the style used in the shape
@@ -14,7 +20,22 @@ pub struct HVIFShape {
 
 }
 
-#[derive(Debug)]
+impl HVIFShape {
+  /// Whether the shape shows at a scale, relative to the 64 by 64 canvas
+  ///
+  /// Shapes with a level of detail range only show between its minimum and maximum, inclusive, and others always show.
+  pub fn visible_at(&self, scale: f32) -> bool
+  {
+    self.modifiers.iter().all(|modifier| match *modifier {
+      HVIFShapeModifier::HVIFLODScale { min, max } => min <= scale && scale <= max,
+      _ => true,
+    })
+  }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "schema::ModifierSchema", into = "schema::ModifierSchema"))]
 /// A modifier to an HVIF shape
 pub enum HVIFShapeModifier {
   /// A single affine transformation matrix
@@ -22,7 +43,7 @@ pub enum HVIFShapeModifier {
   /// The shape uses hinting
   HVIFHinting,
   /// Level of Detail scales
-  /// Represents the allowable scales at which this shape will appear correctly
+  /// Represents the allowable scales at which this shape will appear correctly, relative to the 64 by 64 canvas
   HVIFLODScale {
     #[doc = "Minimum LOD for this shape"] min: f32,
     #[doc = "Maximum LOD for this shape"] max: f32,
@@ -34,59 +55,179 @@ pub enum HVIFShapeModifier {
 
 }
 
-#[derive(Debug, Copy, Clone)]
-/// A temporary implementation of an f24 value, as three bytes
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// A 24-bit floating point value, as three bytes
+///
+/// From most to least significant, there is 1 sign bit, 6 exponent bits biased by 32, and 17 mantissa bits.
+/// The encoding with all bits clear is zero.
 pub struct HVIFf24 {
   #[doc = "First (most significant) bits"] pub fst: u8,
   #[doc = "Middle bits"] pub snd: u8,
   #[doc = "Last (least significant) bits"] pub thr: u8
 }
+impl HVIFf24 {
+  fn to_bits(self) -> u32 {
+    ((self.fst as u32) << 16) | ((self.snd as u32) << 8) | (self.thr as u32)
+  }
+  fn from_bits(bits: u32) -> Self {
+    HVIFf24 { fst: (bits >> 16) as u8, snd: (bits >> 8) as u8, thr: bits as u8 }
+  }
+}
 impl From<HVIFf24> for f32 {
-  // Not quite exact in its current form - be warned!
+  /// Exact, as every f24 value can be represented as an f32
   fn from(hvif_f24: HVIFf24) -> Self {
-    let f24_as_u32: u32 = ((hvif_f24.fst as u32) << 16) | ((hvif_f24.snd as u32) << 8) | (hvif_f24.thr as u32);
+    let f24_as_u32 = hvif_f24.to_bits();
+    if f24_as_u32 == 0 {
+      return 0.0
+    }
 
     // Get the raw bits for each independent component
     let sign_bit      = (f24_as_u32 & 0b100000000000000000000000) >> 23;
     let exponent_bits = (f24_as_u32 & 0b011111100000000000000000) >> 17;
     let mantissa_bits =  f24_as_u32 & 0b000000011111111111111111;
 
-    // The sign is just -1 to the power of this bit
-    let sign : i32 = match sign_bit {
-      0 =>  1,
-      _ => -1,
-    };
+    // Rebias the exponent from 32 to 127, and widen the mantissa from 17 to 23 bits
+    let exponent = exponent_bits + 127 - 32;
+    f32::from_bits((sign_bit << 31) | (exponent << 23) | (mantissa_bits << 6))
+  }
+}
+impl From<f32> for HVIFf24 {
+  /// Rounds to the nearest f24 value, with ties to even
+  ///
+  /// Values too small to represent, and NaN, become zero. Values too large to represent, including infinities,
+  /// are clamped to the largest f24 value of the same sign. Haiku's own writer truncates the mantissa rather than
+  /// rounding it, and writes zero for large values, but both agree on every value that an f24 can represent exactly.
+  fn from(value: f32) -> Self {
+    let f32_as_u32 = value.to_bits();
+    if value.is_nan() {
+      return HVIFf24::from_bits(0)
+    }
 
-    // The exponent is actually only 6 bits, so unsigned values range from 0 to 63
-    // Make it a signed exponent, and offset it to range from -31 to 32
-    let exponent_pre = (exponent_bits as i32) - 31;
+    let sign_bit     = f32_as_u32 >> 31;
+    let mut exponent = ((f32_as_u32 & 0x7f80_0000) >> 23) as i32 - 127;
+    let mantissa     =   f32_as_u32 & 0x007f_ffff;
 
-    // The mantissa is 1.(bits), so we can divite the exponentiation into whole and fractional parts
-    let mantissa_base = f32::powf(2.0, exponent_pre as f32);
-    let mantissa_frac = (mantissa_bits as f32) * f32::powf(2.0, (exponent_pre -17) as f32);
+    // Drop the 6 least significant mantissa bits, rounding to nearest even
+    let dropped = mantissa & 0b11_1111;
+    let mut mantissa_bits = mantissa >> 6;
+    if dropped > 0b10_0000 || (dropped == 0b10_0000 && mantissa_bits & 1 == 1) {
+      mantissa_bits += 1;
+      // Rounding up can carry into the exponent
+      if mantissa_bits == 1 << 17 {
+        mantissa_bits = 0;
+        exponent += 1;
+      }
+    }
 
-    (sign as f32) * (mantissa_base + mantissa_frac)
+    if exponent < -32 {
+      HVIFf24::from_bits(0)
+    } else if exponent > 31 {
+      HVIFf24::from_bits((sign_bit << 23) | (63 << 17) | 0x1_ffff)
+    } else {
+      HVIFf24::from_bits((sign_bit << 23) | (((exponent + 32) as u32) << 17) | mantissa_bits)
+    }
   }
 }
 
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "[f32; 6]", into = "[f32; 6]"))]
 /// A 2D affine transformation matrix
 /// As per HVIF spec, consists of six f24 values
+///
+/// A point (x, y) is transformed to (x * x1 + y * z1 + y2, x * y1 + y * x2 + z2),
+/// following the storage order of agg's `trans_affine`
 pub struct HVIFMatrix {
-  #[doc = "x1"] pub x1: HVIFf24, #[doc = "y1"] pub y1: HVIFf24, #[doc = "z1"] pub z1: HVIFf24,
-  #[doc = "x2"] pub x2: HVIFf24, #[doc = "y2"] pub y2: HVIFf24, #[doc = "z2"] pub z2: HVIFf24,
+  #[doc = "x1, the horizontal scale"] pub x1: HVIFf24, #[doc = "y1, the vertical shear"] pub y1: HVIFf24, #[doc = "z1, the horizontal shear"] pub z1: HVIFf24,
+  #[doc = "x2, the vertical scale"] pub x2: HVIFf24, #[doc = "y2, the horizontal translation"] pub y2: HVIFf24, #[doc = "z2, the vertical translation"] pub z2: HVIFf24,
+}
+impl From<HVIFMatrix> for [f32; 6] {
+  /// The matrix values in storage order
+  fn from(matrix: HVIFMatrix) -> Self {
+    [matrix.x1.into(), matrix.y1.into(), matrix.z1.into(), matrix.x2.into(), matrix.y2.into(), matrix.z2.into()]
+  }
+}
+impl From<[f32; 6]> for HVIFMatrix {
+  /// Builds a matrix from its values in storage order
+  fn from(values: [f32; 6]) -> Self {
+    HVIFMatrix {
+      x1: values[0].into(), y1: values[1].into(), z1: values[2].into(),
+      x2: values[3].into(), y2: values[4].into(), z2: values[5].into(),
+    }
+  }
 }
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "schema::TransformerSchema", into = "schema::TransformerSchema"))]
 /// Some transformation on a basic HVIF shape
 pub enum HVIFTransformer {
   /// An affine transformation using a single matrix
   Affine(HVIFMatrix),
-  /// ?
-  Contour { #[doc="?"] width: f32, #[doc="?"] line_join: u8, #[doc="?"] miter_limit: u8 },
-  /// ?
+  /// Grows or shrinks the outline of the shape
+  Contour {
+    #[doc="How far the outline moves outwards, or inwards if negative"] width: f32,
+    #[doc="How corners of the outline are joined"] line_join: HVIFLineJoin,
+    #[doc="How far a mitered corner may extend, as a multiple of the width"] miter_limit: u8
+  },
+  /// A perspective transformation, which the format stores no data for
   Perspective,
-  /// ?
-  Stroke { #[doc="?"] width: f32, #[doc="?"] line_join: u8, #[doc="?"] line_cap: u8, #[doc="?"] miter_limit: u8 }
+  /// Draws the outline of the shape as a line, rather than filling it
+  Stroke {
+    #[doc="The width of the line"] width: f32,
+    #[doc="How corners of the line are joined"] line_join: HVIFLineJoin,
+    #[doc="How the ends of the line are capped"] line_cap: HVIFLineCap,
+    #[doc="How far a mitered corner may extend, as a multiple of the width"] miter_limit: u8
+  }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+/// How two line segments are joined at a corner
+pub enum HVIFLineJoin {
+  /// The outer edges are extended until they meet, up to the miter limit, beyond which the corner is beveled
+  Miter       = 0,
+  /// As with a miter, but beyond the miter limit the corner is cut off at the limit
+  MiterRevert = 1,
+  /// The corner is rounded off
+  Round       = 2,
+  /// The corner is cut off straight across
+  Bevel       = 3,
+  /// As with a miter, but beyond the miter limit the corner is rounded off
+  MiterRound  = 4
+}
+/// Convert a u8 into a line join
+pub fn line_join_from_u8(num: u8) -> Option<HVIFLineJoin> {
+  match num {
+    0 => Some(HVIFLineJoin::Miter),
+    1 => Some(HVIFLineJoin::MiterRevert),
+    2 => Some(HVIFLineJoin::Round),
+    3 => Some(HVIFLineJoin::Bevel),
+    4 => Some(HVIFLineJoin::MiterRound),
+    _ => None
+  }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+/// How the ends of an open line are drawn
+pub enum HVIFLineCap {
+  /// The line stops exactly at its end point
+  Butt   = 0,
+  /// The line extends past its end point by half its width
+  Square = 1,
+  /// The line ends in a half circle around its end point
+  Round  = 2
+}
+/// Convert a u8 into a line cap
+pub fn line_cap_from_u8(num: u8) -> Option<HVIFLineCap> {
+  match num {
+    0 => Some(HVIFLineCap::Butt),
+    1 => Some(HVIFLineCap::Square),
+    2 => Some(HVIFLineCap::Round),
+    _ => None
+  }
 }
@@ -1,6 +1,13 @@
 //! Types for HVIF styles
 
+use types::shape::*;
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
 /// A single HVIF style
 pub enum HVIFStyle {
   /// A solid aRGB color
@@ -30,15 +37,20 @@ pub enum HVIFStyle {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 /// A gradient between mutiple aRGB colors
 pub struct HVIFGradient {
   /// The type of the gradient
   pub gradient_type: HVIFGradientType,
+  /// An optional affine transformation of the gradient, from gradient space into the shape's space
+  pub transform: Option<HVIFMatrix>,
   /// A collection of the colors making up the gradient
   pub colors: Vec<HVIFGradientColor>
 }
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 /// The type of a gradient - determines how the gradient renders spatially
 pub enum HVIFGradientType {
   /// A linear gradient; follows a line from one point to another
@@ -50,8 +62,10 @@ pub enum HVIFGradientType {
   /// A conic gradient; changes angularly across a circular region
   Conic    = 3,
   /// An XY gradient; changes linearly in both the X and Y directions
+  #[cfg_attr(feature = "serde", serde(rename = "xy"))]
   XY       = 4,
   /// A square root XY gradient: changes quadratically in both the X and Y directions
+  #[cfg_attr(feature = "serde", serde(rename = "sqrt_xy"))]
   SqrtXY   = 5
 }
 /// Convert a u8 into a gradient type
@@ -72,6 +86,7 @@ pub fn gradient_type_from_u8(num: u8) -> Option<HVIFGradientType> {
 
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 /// Color of a gradient, along with a stop offset
 pub struct HVIFGradientColor {
   /// The stop offset for this color - the "position" it holds relative to other colors in the gradient
@@ -0,0 +1,265 @@
+//! Validation of HVIF images, finding what the format cannot store or that would draw wrongly
+
+use std::fmt;
+
+use error::HVIFIndexKind;
+use types::*;
+use writer::HVIF_MAX_LIST_LENGTH;
+use parser::util::{HVIF_COORD_MIN, HVIF_COORD_MAX};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// How serious a diagnostic is
+pub enum HVIFSeverity {
+  /// The image can be stored, but something in it is likely a mistake
+  Warning,
+  /// The image cannot be stored, or would not read back as it is
+  Error,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// Where in an image a diagnostic applies
+pub enum HVIFLocation {
+  /// The image as a whole
+  Image,
+  /// A style
+  Style {
+    #[doc = "The index of the style"] index: usize
+  },
+  /// A path
+  Path {
+    #[doc = "The index of the path"] index: usize
+  },
+  /// A shape
+  Shape {
+    #[doc = "The index of the shape"] index: usize
+  },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// What is wrong with an image
+pub enum HVIFProblem {
+  /// A list holds more entries than its one byte length can count
+  TooMany {
+    #[doc = "What kind of entries the list holds"] what: &'static str,
+    #[doc = "How many entries the list holds"] count: usize
+  },
+  /// A shape refers to a style or path that the image does not have
+  IndexOutOfRange {
+    #[doc = "What the index refers to, either a style or a path"] kind: HVIFIndexKind,
+    #[doc = "The out of range index"] index: u8,
+    #[doc = "How many entries of that kind the image has"] count: usize
+  },
+  /// A shape carries modifiers that the format cannot store together,
+  /// such as two matrices, or both a matrix and a translation
+  ConflictingModifiers,
+  /// A gradient has no colors, so it paints nothing
+  NoGradientStops,
+  /// A gradient color has a smaller stop offset than the color before it
+  GradientStopsOutOfOrder {
+    #[doc = "The index of the color"] stop: usize
+  },
+  /// A path has no points
+  EmptyPath,
+  /// A path has a point outside the range of coordinates the format can store, which would be clamped
+  CoordinateOutOfRange {
+    #[doc = "The index of the point command"] point: usize
+  },
+  /// A shape draws no paths
+  ShapeWithoutPaths,
+  /// A shape's level of detail range has a minimum above its maximum, so it never shows
+  EmptyLODRange,
+  /// No shape uses a style
+  UnusedStyle,
+  /// No shape uses a path
+  UnusedPath,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// A problem found by validating an image
+pub struct HVIFDiagnostic {
+  /// How serious the problem is
+  pub severity: HVIFSeverity,
+  /// Where the problem is
+  pub location: HVIFLocation,
+  /// What the problem is
+  pub problem: HVIFProblem,
+}
+
+impl fmt::Display for HVIFDiagnostic {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.severity {
+      HVIFSeverity::Warning => write!(f, "warning: ")?,
+      HVIFSeverity::Error => write!(f, "error: ")?,
+    }
+    match self.location {
+      HVIFLocation::Image => write!(f, "image: ")?,
+      HVIFLocation::Style { index } => write!(f, "style {}: ", index)?,
+      HVIFLocation::Path { index } => write!(f, "path {}: ", index)?,
+      HVIFLocation::Shape { index } => write!(f, "shape {}: ", index)?,
+    }
+    match self.problem {
+      HVIFProblem::TooMany { what, count } =>
+        write!(f, "too many {}: {} (at most {})", what, count, HVIF_MAX_LIST_LENGTH),
+      HVIFProblem::IndexOutOfRange { kind, index, count } => {
+        let what = match kind { HVIFIndexKind::Style => "style", HVIFIndexKind::Path => "path" };
+        write!(f, "{} index {} is out of range, the image has {} {}s", what, index, count, what)
+      },
+      HVIFProblem::ConflictingModifiers => write!(f, "modifiers cannot be written together"),
+      HVIFProblem::NoGradientStops => write!(f, "gradient has no colors"),
+      HVIFProblem::GradientStopsOutOfOrder { stop } => write!(f, "gradient color {} comes before the color preceding it", stop),
+      HVIFProblem::EmptyPath => write!(f, "path has no points"),
+      HVIFProblem::CoordinateOutOfRange { point } => write!(f, "point {} is out of the range of coordinates", point),
+      HVIFProblem::ShapeWithoutPaths => write!(f, "shape has no paths"),
+      HVIFProblem::EmptyLODRange => write!(f, "level of detail range is empty, so the shape never shows"),
+      HVIFProblem::UnusedStyle => write!(f, "style is not used by any shape"),
+      HVIFProblem::UnusedPath => write!(f, "path is not used by any shape"),
+    }
+  }
+}
+
+impl HVIFImage {
+  /// Checks the image for anything the format cannot store, or that is likely a mistake
+  ///
+  /// Images with errors cannot be written, while warnings leave the image writable.
+  pub fn validate(&self) -> Vec<HVIFDiagnostic>
+  {
+    let mut diagnostics = Vec::new();
+    validate_count(&mut diagnostics, HVIFLocation::Image, "styles", self.styles.len());
+    validate_count(&mut diagnostics, HVIFLocation::Image, "paths", self.paths.len());
+    validate_count(&mut diagnostics, HVIFLocation::Image, "shapes", self.shapes.len());
+
+    for (index, style) in self.styles.iter().enumerate() {
+      validate_style(&mut diagnostics, index, style);
+    }
+    for (index, path) in self.paths.iter().enumerate() {
+      validate_path(&mut diagnostics, index, path);
+    }
+    for (index, shape) in self.shapes.iter().enumerate() {
+      validate_shape(&mut diagnostics, index, shape, self.styles.len(), self.paths.len());
+    }
+
+    for index in 0..self.styles.len() {
+      if !self.shapes.iter().any(|shape| shape.style_index as usize == index) {
+        diagnostics.push(validate_warning(HVIFLocation::Style { index }, HVIFProblem::UnusedStyle));
+      }
+    }
+    for index in 0..self.paths.len() {
+      if !self.shapes.iter().any(|shape| shape.path_indices.iter().any(|&path| path as usize == index)) {
+        diagnostics.push(validate_warning(HVIFLocation::Path { index }, HVIFProblem::UnusedPath));
+      }
+    }
+    diagnostics
+  }
+}
+
+fn validate_style(diagnostics: &mut Vec<HVIFDiagnostic>, index: usize, style: &HVIFStyle)
+{
+  let gradient = match *style {
+    HVIFStyle::Gradient(ref gradient) => gradient,
+    _ => return,
+  };
+  let location = HVIFLocation::Style { index };
+  validate_count(diagnostics, location, "gradient colors", gradient.colors.len());
+  if gradient.colors.is_empty() {
+    diagnostics.push(validate_warning(location, HVIFProblem::NoGradientStops));
+  }
+  for (stop, pair) in gradient.colors.windows(2).enumerate() {
+    if pair[1].stop_offset < pair[0].stop_offset {
+      diagnostics.push(validate_warning(location, HVIFProblem::GradientStopsOutOfOrder { stop: stop + 1 }));
+    }
+  }
+}
+
+fn validate_path(diagnostics: &mut Vec<HVIFDiagnostic>, index: usize, path: &HVIFPath)
+{
+  let location = HVIFLocation::Path { index };
+  validate_count(diagnostics, location, "points", path.points.len());
+  if path.points.is_empty() {
+    diagnostics.push(validate_warning(location, HVIFProblem::EmptyPath));
+  }
+  let range = HVIF_COORD_MIN..=HVIF_COORD_MAX;
+  let in_range = |point: HVIFPoint| range.contains(&point.x) && range.contains(&point.y);
+  for (point, command) in path.points.iter().enumerate() {
+    let fits = match *command {
+      HVIFPointCommand::HLine { x } => range.contains(&x),
+      HVIFPointCommand::VLine { y } => range.contains(&y),
+      HVIFPointCommand::Line { point } => in_range(point),
+      HVIFPointCommand::Curve { point_in, point, point_out } => in_range(point_in) && in_range(point) && in_range(point_out),
+    };
+    if !fits {
+      diagnostics.push(validate_warning(location, HVIFProblem::CoordinateOutOfRange { point }));
+    }
+  }
+}
+
+fn validate_shape(diagnostics: &mut Vec<HVIFDiagnostic>, index: usize, shape: &HVIFShape, style_count: usize, path_count: usize)
+{
+  let location = HVIFLocation::Shape { index };
+  if shape.style_index as usize >= style_count {
+    diagnostics.push(validate_error(location, HVIFProblem::IndexOutOfRange {
+      kind: HVIFIndexKind::Style, index: shape.style_index, count: style_count,
+    }));
+  }
+  for &path in &shape.path_indices {
+    if path as usize >= path_count {
+      diagnostics.push(validate_error(location, HVIFProblem::IndexOutOfRange {
+        kind: HVIFIndexKind::Path, index: path, count: path_count,
+      }));
+    }
+  }
+  if shape.path_indices.is_empty() {
+    diagnostics.push(validate_warning(location, HVIFProblem::ShapeWithoutPaths));
+  }
+  validate_count(diagnostics, location, "paths in a shape", shape.path_indices.len());
+
+  for modifier in &shape.modifiers {
+    match *modifier {
+      HVIFShapeModifier::HVIFLODScale { min, max } if min > max =>
+        diagnostics.push(validate_warning(location, HVIFProblem::EmptyLODRange)),
+      HVIFShapeModifier::HVIFTransformerList(ref transformers) =>
+        validate_count(diagnostics, location, "transformers", transformers.len()),
+      _ => (),
+    }
+  }
+  if validate_conflicting(&shape.modifiers) {
+    diagnostics.push(validate_error(location, HVIFProblem::ConflictingModifiers));
+  }
+}
+
+/// Whether modifiers include two of a kind, or both a matrix and a translation, which the format cannot store
+pub(crate) fn validate_conflicting(modifiers: &[HVIFShapeModifier]) -> bool
+{
+  // A matrix and a translation both place the shape, and share one slot with each other
+  let mut seen = [false; 4];
+  for modifier in modifiers {
+    let kind = match *modifier {
+      HVIFShapeModifier::HVIFTransformMatrix(_) | HVIFShapeModifier::HVIFTranslation(_) => 0,
+      HVIFShapeModifier::HVIFHinting => 1,
+      HVIFShapeModifier::HVIFLODScale { .. } => 2,
+      HVIFShapeModifier::HVIFTransformerList(_) => 3,
+    };
+    if seen[kind] {
+      return true
+    }
+    seen[kind] = true;
+  }
+  false
+}
+
+/// Reports a list too long for its one byte length
+fn validate_count(diagnostics: &mut Vec<HVIFDiagnostic>, location: HVIFLocation, what: &'static str, count: usize)
+{
+  if count > HVIF_MAX_LIST_LENGTH {
+    diagnostics.push(validate_error(location, HVIFProblem::TooMany { what, count }));
+  }
+}
+
+fn validate_error(location: HVIFLocation, problem: HVIFProblem) -> HVIFDiagnostic
+{
+  HVIFDiagnostic { severity: HVIFSeverity::Error, location, problem }
+}
+
+fn validate_warning(location: HVIFLocation, problem: HVIFProblem) -> HVIFDiagnostic
+{
+  HVIFDiagnostic { severity: HVIFSeverity::Warning, location, problem }
+}
@@ -0,0 +1,772 @@
+//! Zero-copy view over an HVIF image, decoding its entries only as they are asked for
+//!
+//! Making a view walks the image once, checking that it parses just as `parse` would, but keeps nothing but where
+//! each section starts. Its styles, paths and shapes are then borrowed slices of the input, which decode their
+//! colors, points and modifiers on demand, so that scanning many images allocates nothing per entry.
+
+use error::HVIFError;
+use parser::parse;
+use parser::util::{hvif_coord_from_bytes, hvif_coord_is_long};
+use types::*;
+
+/// The size of an affine matrix, six f24 values
+const HVIF_VIEW_MATRIX_SIZE: usize = 18;
+/// The only shape type, a shape whose outline comes from paths
+const HVIF_VIEW_SHAPE_TYPE: u8 = 0x0a;
+
+/// Transformer types
+const HVIF_VIEW_TRANSFORMER_AFFINE: u8 = 20;
+const HVIF_VIEW_TRANSFORMER_CONTOUR: u8 = 21;
+const HVIF_VIEW_TRANSFORMER_PERSPECTIVE: u8 = 22;
+const HVIF_VIEW_TRANSFORMER_STROKE: u8 = 23;
+
+#[derive(Debug, Copy, Clone)]
+/// An HVIF image, borrowed from the bytes it was read from
+pub struct HVIFView<'a> {
+  bytes: &'a [u8],
+  styles: usize,
+  style_count: usize,
+  paths: usize,
+  path_count: usize,
+  shapes: usize,
+  shape_count: usize,
+}
+
+impl<'a> HVIFView<'a> {
+  /// Views an HVIF image, failing as `parse` does if it would not parse
+  ///
+  /// As with `parse`, any bytes following the image are ignored.
+  pub fn new(input: &'a [u8]) -> Result<HVIFView<'a>, HVIFError>
+  {
+    let fail = |offset: usize| Err(parse(input).err().unwrap_or(HVIFError::Malformed { offset }));
+    if input.get(..4) != Some(&b"ncif"[..]) {
+      return fail(0)
+    }
+
+    let (styles, style_count, paths) = match hvif_view_section(input, 4, hvif_view_style_len) {
+      Ok(section) => section,
+      Err(offset) => return fail(offset),
+    };
+    let (paths, path_count, shapes) = match hvif_view_section(input, paths, hvif_view_path_len) {
+      Ok(section) => section,
+      Err(offset) => return fail(offset),
+    };
+    let (shapes, shape_count, end) =
+      match hvif_view_section(input, shapes, |shape| hvif_view_shape_len(shape, style_count, path_count)) {
+        Ok(section) => section,
+        Err(offset) => return fail(offset),
+      };
+
+    Ok(HVIFView {
+      bytes: &input[..end],
+      styles, style_count,
+      paths, path_count,
+      shapes, shape_count,
+    })
+  }
+
+  /// The bytes of the image, without any that followed it
+  pub fn bytes(&self) -> &'a [u8]
+  {
+    self.bytes
+  }
+
+  /// How many styles the image has
+  pub fn style_count(&self) -> usize
+  {
+    self.style_count
+  }
+
+  /// How many paths the image has
+  pub fn path_count(&self) -> usize
+  {
+    self.path_count
+  }
+
+  /// How many shapes the image has
+  pub fn shape_count(&self) -> usize
+  {
+    self.shape_count
+  }
+
+  /// The styles of the image, in order
+  pub fn styles(&self) -> HVIFViewStyles<'a>
+  {
+    HVIFViewStyles { rest: &self.bytes[self.styles..self.paths - 1], remaining: self.style_count }
+  }
+
+  /// The paths of the image, in order
+  pub fn paths(&self) -> HVIFViewPaths<'a>
+  {
+    HVIFViewPaths { rest: &self.bytes[self.paths..self.shapes - 1], remaining: self.path_count }
+  }
+
+  /// The shapes of the image, in order
+  pub fn shapes(&self) -> HVIFViewShapes<'a>
+  {
+    HVIFViewShapes {
+      rest: &self.bytes[self.shapes..],
+      remaining: self.shape_count,
+      style_count: self.style_count,
+      path_count: self.path_count,
+    }
+  }
+
+  /// Decodes the whole image, as `parse` would have
+  pub fn to_image(&self) -> HVIFImage
+  {
+    HVIFImage {
+      styles: self.styles().map(|style| style.to_style()).collect(),
+      paths: self.paths().map(|path| path.to_path()).collect(),
+      shapes: self.shapes().map(|shape| shape.to_shape()).collect(),
+      names: HVIFNames::default(),
+    }
+  }
+}
+
+/// Measures a section, from its count, returning where its entries start, how many there are, and where it ends,
+/// or the offset of the entry that does not parse
+fn hvif_view_section<F>(input: &[u8], start: usize, entry_len: F) -> Result<(usize, usize, usize), usize>
+  where F: Fn(&[u8]) -> Option<usize>
+{
+  let count = *input.get(start).ok_or(start)? as usize;
+  let mut offset = start + 1;
+  for _ in 0..count {
+    offset += entry_len(&input[offset..]).ok_or(offset)?;
+  }
+  Ok((start + 1, count, offset))
+}
+
+/// The length of a coordinate, one byte or two, if all of it is there
+fn hvif_view_coord_len(bytes: &[u8], at: usize) -> Option<usize>
+{
+  let len = if hvif_coord_is_long(*bytes.get(at)?) { 2 } else { 1 };
+  bytes.get(at + len - 1).map(|_| len)
+}
+
+/// The length of a run of coordinates, if all of them are there
+fn hvif_view_coords_len(bytes: &[u8], at: usize, count: usize) -> Option<usize>
+{
+  let mut len = 0;
+  for _ in 0..count {
+    len += hvif_view_coord_len(bytes, at + len)?;
+  }
+  Some(len)
+}
+
+/// Decodes the coordinate at an offset, moving the offset past it
+fn hvif_view_coord(bytes: &[u8], at: &mut usize) -> f32
+{
+  let first = bytes[*at];
+  let second = if hvif_coord_is_long(first) { bytes[*at + 1] } else { 0 };
+  *at += if hvif_coord_is_long(first) { 2 } else { 1 };
+  hvif_coord_from_bytes(first, second)
+}
+
+/// Decodes the point at an offset, moving the offset past it
+fn hvif_view_point(bytes: &[u8], at: &mut usize) -> HVIFPoint
+{
+  let x = hvif_view_coord(bytes, at);
+  let y = hvif_view_coord(bytes, at);
+  HVIFPoint { x, y }
+}
+
+/// Decodes the matrix at the start of some bytes
+fn hvif_view_matrix(bytes: &[u8]) -> HVIFMatrix
+{
+  let f24 = |index: usize| HVIFf24 { fst: bytes[index * 3], snd: bytes[index * 3 + 1], thr: bytes[index * 3 + 2] };
+  HVIFMatrix { x1: f24(0), y1: f24(1), z1: f24(2), x2: f24(3), y2: f24(4), z2: f24(5) }
+}
+
+/// The length of a style, if it is all there and of a known type
+fn hvif_view_style_len(bytes: &[u8]) -> Option<usize>
+{
+  let len = match *bytes.first()? {
+    1 => 5,
+    2 => {
+      gradient_type_from_u8(*bytes.get(1)?)?;
+      let flags = *bytes.get(2)?;
+      let color_count = *bytes.get(3)? as usize;
+      4 + hvif_view_gradient_matrix_len(flags) + color_count * hvif_view_gradient_color_len(flags)
+    },
+    3 => 4,
+    4 => 3,
+    5 => 2,
+    _ => return None,
+  };
+  bytes.get(len - 1).map(|_| len)
+}
+
+fn hvif_view_gradient_matrix_len(flags: u8) -> usize
+{
+  if HVIF_GRADIENT_FLAG_TRANSFORM.is_set_on(flags) { HVIF_VIEW_MATRIX_SIZE } else { 0 }
+}
+
+/// The length of a gradient color, its stop offset and its channels
+fn hvif_view_gradient_color_len(flags: u8) -> usize
+{
+  let colors = if HVIF_GRADIENT_FLAG_GRAYS.is_set_on(flags) { 1 } else { 3 };
+  let alpha = if HVIF_GRADIENT_FLAG_NO_ALPHA.is_set_on(flags) { 0 } else { 1 };
+  let depth = if HVIF_GRADIENT_FLAG_COLORS_16_BIT.is_set_on(flags) { 2 } else { 1 };
+  1 + (colors + alpha) * depth
+}
+
+/// The length of a path, if it is all there
+fn hvif_view_path_len(bytes: &[u8]) -> Option<usize>
+{
+  let flags = *bytes.first()?;
+  let point_count = *bytes.get(1)? as usize;
+  let mut len = 2;
+  if HVIF_PATH_FLAG_USES_COMMANDS.is_set_on(flags) {
+    let commands = bytes.get(2..2 + point_count.div_ceil(4))?;
+    len += commands.len();
+    for index in 0..point_count {
+      let coords = match hvif_view_command(commands, index) {
+        0 | 1 => 1,
+        2 => 2,
+        _ => 6,
+      };
+      len += hvif_view_coords_len(bytes, len, coords)?;
+    }
+  } else {
+    let coords = if HVIF_PATH_FLAG_NO_CURVES.is_set_on(flags) { 2 } else { 6 };
+    len += hvif_view_coords_len(bytes, len, point_count * coords)?;
+  }
+  Some(len)
+}
+
+/// The two bit command of a point, packed four to a byte from the least significant bits
+fn hvif_view_command(commands: &[u8], index: usize) -> u8
+{
+  (commands[index / 4] >> (2 * (index % 4))) & 0b11
+}
+
+/// The length of a shape, if it is all there, of a known type and refers only to styles and paths that exist
+fn hvif_view_shape_len(bytes: &[u8], style_count: usize, path_count: usize) -> Option<usize>
+{
+  if *bytes.first()? != HVIF_VIEW_SHAPE_TYPE || *bytes.get(1)? as usize >= style_count {
+    return None
+  }
+  let path_indices = bytes.get(3..3 + *bytes.get(2)? as usize)?;
+  if path_indices.iter().any(|&index| index as usize >= path_count) {
+    return None
+  }
+  let flags = *bytes.get(3 + path_indices.len())?;
+  let mut len = 4 + path_indices.len();
+  if HVIF_SHAPE_FLAG_TRANSFORM.is_set_on(flags) {
+    len += HVIF_VIEW_MATRIX_SIZE;
+  }
+  if HVIF_SHAPE_FLAG_TRANSLATION.is_set_on(flags) {
+    len += hvif_view_coords_len(bytes, len, 2)?;
+  }
+  if HVIF_SHAPE_FLAG_LOD_SCALE.is_set_on(flags) {
+    len += 2;
+  }
+  if HVIF_SHAPE_FLAG_HAS_TRANSFORMERS.is_set_on(flags) {
+    let transformer_count = *bytes.get(len)?;
+    len += 1;
+    for _ in 0..transformer_count {
+      len += 1 + match *bytes.get(len)? {
+        HVIF_VIEW_TRANSFORMER_AFFINE => HVIF_VIEW_MATRIX_SIZE,
+        HVIF_VIEW_TRANSFORMER_CONTOUR => {
+          line_join_from_u8(*bytes.get(len + 2)?)?;
+          3
+        },
+        HVIF_VIEW_TRANSFORMER_PERSPECTIVE => 0,
+        HVIF_VIEW_TRANSFORMER_STROKE => {
+          let line_options = *bytes.get(len + 2)?;
+          line_join_from_u8(line_options & 15)?;
+          line_cap_from_u8(line_options >> 4)?;
+          3
+        },
+        _ => return None,
+      };
+    }
+  }
+  bytes.get(len - 1).map(|_| len)
+}
+
+#[derive(Debug, Clone)]
+/// The styles of a viewed image
+pub struct HVIFViewStyles<'a> {
+  rest: &'a [u8],
+  remaining: usize,
+}
+
+impl<'a> Iterator for HVIFViewStyles<'a> {
+  type Item = HVIFStyleView<'a>;
+
+  fn next(&mut self) -> Option<HVIFStyleView<'a>>
+  {
+    if self.remaining == 0 {
+      return None
+    }
+    let len = hvif_view_style_len(self.rest).expect("the view checked every style");
+    let (bytes, rest) = self.rest.split_at(len);
+    self.rest = rest;
+    self.remaining -= 1;
+    Some(HVIFStyleView { bytes })
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>)
+  {
+    (self.remaining, Some(self.remaining))
+  }
+}
+
+impl<'a> ExactSizeIterator for HVIFViewStyles<'a> {}
+
+#[derive(Debug, Copy, Clone)]
+/// A style of a viewed image
+pub struct HVIFStyleView<'a> {
+  bytes: &'a [u8],
+}
+
+impl<'a> HVIFStyleView<'a> {
+  /// The bytes of the style, starting with its type
+  pub fn bytes(&self) -> &'a [u8]
+  {
+    self.bytes
+  }
+
+  /// Whether the style is a gradient, rather than a solid color
+  pub fn is_gradient(&self) -> bool
+  {
+    self.bytes[0] == 2
+  }
+
+  /// The type of the gradient, for a gradient
+  pub fn gradient_type(&self) -> Option<HVIFGradientType>
+  {
+    match self.is_gradient() {
+      true  => gradient_type_from_u8(self.bytes[1]),
+      false => None,
+    }
+  }
+
+  /// The transformation of the gradient, for a gradient that has one
+  pub fn gradient_transform(&self) -> Option<HVIFMatrix>
+  {
+    match self.is_gradient() && HVIF_GRADIENT_FLAG_TRANSFORM.is_set_on(self.bytes[2]) {
+      true  => Some(hvif_view_matrix(&self.bytes[4..])),
+      false => None,
+    }
+  }
+
+  /// The colors of the gradient, or none for a solid color
+  pub fn gradient_colors(&self) -> HVIFViewColors<'a>
+  {
+    match self.is_gradient() {
+      true  => HVIFViewColors {
+        rest: &self.bytes[4 + hvif_view_gradient_matrix_len(self.bytes[2])..],
+        remaining: self.bytes[3] as usize,
+        flags: self.bytes[2],
+      },
+      false => HVIFViewColors { rest: &[], remaining: 0, flags: 0 },
+    }
+  }
+
+  /// Decodes the style
+  pub fn to_style(&self) -> HVIFStyle
+  {
+    let bytes = self.bytes;
+    match bytes[0] {
+      1 => HVIFStyle::SolidColor { red: bytes[1], green: bytes[2], blue: bytes[3], alpha: bytes[4] },
+      3 => HVIFStyle::SolidColorNoAlpha { red: bytes[1], green: bytes[2], blue: bytes[3] },
+      4 => HVIFStyle::SolidGray { value: bytes[1], alpha: bytes[2] },
+      5 => HVIFStyle::SolidGrayNoAlpha { value: bytes[1] },
+      _ => HVIFStyle::Gradient(HVIFGradient {
+        gradient_type: self.gradient_type().expect("the view checked the gradient type"),
+        transform: self.gradient_transform(),
+        colors: self.gradient_colors().collect(),
+      }),
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+/// The colors of a viewed gradient
+pub struct HVIFViewColors<'a> {
+  rest: &'a [u8],
+  remaining: usize,
+  flags: u8,
+}
+
+impl<'a> Iterator for HVIFViewColors<'a> {
+  type Item = HVIFGradientColor;
+
+  fn next(&mut self) -> Option<HVIFGradientColor>
+  {
+    if self.remaining == 0 {
+      return None
+    }
+    let (bytes, rest) = self.rest.split_at(hvif_view_gradient_color_len(self.flags));
+    self.rest = rest;
+    self.remaining -= 1;
+
+    // Only the most significant byte of 16 bit channels is kept, as the parser does
+    let step = if HVIF_GRADIENT_FLAG_COLORS_16_BIT.is_set_on(self.flags) { 2 } else { 1 };
+    let channel = |index: usize| bytes[1 + index * step];
+    let (red, green, blue, next) = match HVIF_GRADIENT_FLAG_GRAYS.is_set_on(self.flags) {
+      true  => (channel(0), channel(0), channel(0), 1),
+      false => (channel(0), channel(1), channel(2), 3),
+    };
+    let alpha = match HVIF_GRADIENT_FLAG_NO_ALPHA.is_set_on(self.flags) {
+      true  => 255,
+      false => channel(next),
+    };
+    Some(HVIFGradientColor { stop_offset: bytes[0], alpha, red, green, blue })
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>)
+  {
+    (self.remaining, Some(self.remaining))
+  }
+}
+
+impl<'a> ExactSizeIterator for HVIFViewColors<'a> {}
+
+#[derive(Debug, Clone)]
+/// The paths of a viewed image
+pub struct HVIFViewPaths<'a> {
+  rest: &'a [u8],
+  remaining: usize,
+}
+
+impl<'a> Iterator for HVIFViewPaths<'a> {
+  type Item = HVIFPathView<'a>;
+
+  fn next(&mut self) -> Option<HVIFPathView<'a>>
+  {
+    if self.remaining == 0 {
+      return None
+    }
+    let len = hvif_view_path_len(self.rest).expect("the view checked every path");
+    let (bytes, rest) = self.rest.split_at(len);
+    self.rest = rest;
+    self.remaining -= 1;
+    Some(HVIFPathView { bytes })
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>)
+  {
+    (self.remaining, Some(self.remaining))
+  }
+}
+
+impl<'a> ExactSizeIterator for HVIFViewPaths<'a> {}
+
+#[derive(Debug, Copy, Clone)]
+/// A path of a viewed image
+pub struct HVIFPathView<'a> {
+  bytes: &'a [u8],
+}
+
+impl<'a> HVIFPathView<'a> {
+  /// The bytes of the path, starting with its flags
+  pub fn bytes(&self) -> &'a [u8]
+  {
+    self.bytes
+  }
+
+  /// The path's flags
+  pub fn flags(&self) -> u8
+  {
+    self.bytes[0]
+  }
+
+  /// Whether the path's last point is connected back to its first point
+  pub fn closed(&self) -> bool
+  {
+    HVIF_PATH_FLAG_CLOSED.is_set_on(self.flags())
+  }
+
+  /// How many points the path has
+  pub fn len(&self) -> usize
+  {
+    self.bytes[1] as usize
+  }
+
+  /// Whether the path has no points
+  pub fn is_empty(&self) -> bool
+  {
+    self.len() == 0
+  }
+
+  /// The points of the path, decoded one at a time
+  pub fn points(&self) -> HVIFViewPoints<'a>
+  {
+    let command_len = match HVIF_PATH_FLAG_USES_COMMANDS.is_set_on(self.flags()) {
+      true  => self.len().div_ceil(4),
+      false => 0,
+    };
+    HVIFViewPoints {
+      commands: &self.bytes[2..2 + command_len],
+      points: &self.bytes[2 + command_len..],
+      flags: self.flags(),
+      offset: 0,
+      index: 0,
+      count: self.len(),
+    }
+  }
+
+  /// Decodes the path
+  pub fn to_path(&self) -> HVIFPath
+  {
+    HVIFPath { closed: self.closed(), points: self.points().collect() }
+  }
+}
+
+#[derive(Debug, Clone)]
+/// The points of a viewed path
+pub struct HVIFViewPoints<'a> {
+  commands: &'a [u8],
+  points: &'a [u8],
+  flags: u8,
+  offset: usize,
+  index: usize,
+  count: usize,
+}
+
+impl<'a> Iterator for HVIFViewPoints<'a> {
+  type Item = HVIFPointCommand;
+
+  fn next(&mut self) -> Option<HVIFPointCommand>
+  {
+    if self.index == self.count {
+      return None
+    }
+    let command = match HVIF_PATH_FLAG_USES_COMMANDS.is_set_on(self.flags) {
+      true  => hvif_view_command(self.commands, self.index),
+      false if HVIF_PATH_FLAG_NO_CURVES.is_set_on(self.flags) => 2,
+      false => 3,
+    };
+    self.index += 1;
+
+    let (bytes, at) = (self.points, &mut self.offset);
+    Some(match command {
+      0 => HVIFPointCommand::HLine { x: hvif_view_coord(bytes, at) },
+      1 => HVIFPointCommand::VLine { y: hvif_view_coord(bytes, at) },
+      2 => HVIFPointCommand::Line { point: hvif_view_point(bytes, at) },
+      _ => {
+        let point = hvif_view_point(bytes, at);
+        let point_in = hvif_view_point(bytes, at);
+        let point_out = hvif_view_point(bytes, at);
+        HVIFPointCommand::Curve { point_in, point, point_out }
+      },
+    })
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>)
+  {
+    (self.count - self.index, Some(self.count - self.index))
+  }
+}
+
+impl<'a> ExactSizeIterator for HVIFViewPoints<'a> {}
+
+#[derive(Debug, Clone)]
+/// The shapes of a viewed image
+pub struct HVIFViewShapes<'a> {
+  rest: &'a [u8],
+  remaining: usize,
+  style_count: usize,
+  path_count: usize,
+}
+
+impl<'a> Iterator for HVIFViewShapes<'a> {
+  type Item = HVIFShapeView<'a>;
+
+  fn next(&mut self) -> Option<HVIFShapeView<'a>>
+  {
+    if self.remaining == 0 {
+      return None
+    }
+    let len = hvif_view_shape_len(self.rest, self.style_count, self.path_count).expect("the view checked every shape");
+    let (bytes, rest) = self.rest.split_at(len);
+    self.rest = rest;
+    self.remaining -= 1;
+    Some(HVIFShapeView { bytes })
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>)
+  {
+    (self.remaining, Some(self.remaining))
+  }
+}
+
+impl<'a> ExactSizeIterator for HVIFViewShapes<'a> {}
+
+#[derive(Debug, Copy, Clone)]
+/// A shape of a viewed image
+pub struct HVIFShapeView<'a> {
+  bytes: &'a [u8],
+}
+
+impl<'a> HVIFShapeView<'a> {
+  /// The bytes of the shape, starting with its type
+  pub fn bytes(&self) -> &'a [u8]
+  {
+    self.bytes
+  }
+
+  /// The index of the style the shape is filled with
+  pub fn style_index(&self) -> u8
+  {
+    self.bytes[1]
+  }
+
+  /// The indices of the paths making up the shape
+  pub fn path_indices(&self) -> &'a [u8]
+  {
+    &self.bytes[3..3 + self.bytes[2] as usize]
+  }
+
+  /// The shape's flags
+  pub fn flags(&self) -> u8
+  {
+    self.bytes[3 + self.bytes[2] as usize]
+  }
+
+  /// Whether the shape's points are snapped to the pixel grid
+  pub fn hinting(&self) -> bool
+  {
+    HVIF_SHAPE_FLAG_HINTING.is_set_on(self.flags())
+  }
+
+  /// The shape's transformation matrix, if it has one
+  pub fn transform(&self) -> Option<HVIFMatrix>
+  {
+    match HVIF_SHAPE_FLAG_TRANSFORM.is_set_on(self.flags()) {
+      true  => Some(hvif_view_matrix(&self.bytes[self.modifiers()..])),
+      false => None,
+    }
+  }
+
+  /// The shape's translation, if it has one
+  pub fn translation(&self) -> Option<HVIFPoint>
+  {
+    match HVIF_SHAPE_FLAG_TRANSLATION.is_set_on(self.flags()) {
+      true  => Some(hvif_view_point(self.bytes, &mut self.translation_offset())),
+      false => None,
+    }
+  }
+
+  /// The minimum and maximum scales the shape shows at, if it only shows at some
+  pub fn lod_scale(&self) -> Option<(f32, f32)>
+  {
+    let offset = self.lod_scale_offset();
+    match HVIF_SHAPE_FLAG_LOD_SCALE.is_set_on(self.flags()) {
+      true  => Some((self.bytes[offset] as f32 / 63.75, self.bytes[offset + 1] as f32 / 63.75)),
+      false => None,
+    }
+  }
+
+  /// The shape's transformers, decoded one at a time
+  pub fn transformers(&self) -> HVIFViewTransformers<'a>
+  {
+    let mut offset = self.lod_scale_offset();
+    if HVIF_SHAPE_FLAG_LOD_SCALE.is_set_on(self.flags()) {
+      offset += 2;
+    }
+    match HVIF_SHAPE_FLAG_HAS_TRANSFORMERS.is_set_on(self.flags()) {
+      true  => HVIFViewTransformers { rest: &self.bytes[offset + 1..], remaining: self.bytes[offset] as usize },
+      false => HVIFViewTransformers { rest: &[], remaining: 0 },
+    }
+  }
+
+  /// Whether the shape shows at a scale, as `HVIFShape::visible_at` tells
+  pub fn visible_at(&self, scale: f32) -> bool
+  {
+    self.lod_scale().is_none_or(|(min, max)| min <= scale && scale <= max)
+  }
+
+  /// Decodes the shape, with its modifiers in the order the parser gives them
+  pub fn to_shape(&self) -> HVIFShape
+  {
+    let mut modifiers = Vec::new();
+    if self.hinting() {
+      modifiers.push(HVIFShapeModifier::HVIFHinting);
+    }
+    if let Some(matrix) = self.transform() {
+      modifiers.push(HVIFShapeModifier::HVIFTransformMatrix(matrix));
+    }
+    if let Some(offset) = self.translation() {
+      modifiers.push(HVIFShapeModifier::HVIFTranslation(offset));
+    }
+    if let Some((min, max)) = self.lod_scale() {
+      modifiers.push(HVIFShapeModifier::HVIFLODScale { min, max });
+    }
+    if HVIF_SHAPE_FLAG_HAS_TRANSFORMERS.is_set_on(self.flags()) {
+      modifiers.push(HVIFShapeModifier::HVIFTransformerList(self.transformers().collect()));
+    }
+    HVIFShape { style_index: self.style_index(), path_indices: self.path_indices().to_vec(), modifiers }
+  }
+
+  /// Where the modifiers start, after the flags
+  fn modifiers(&self) -> usize
+  {
+    4 + self.bytes[2] as usize
+  }
+
+  fn translation_offset(&self) -> usize
+  {
+    match HVIF_SHAPE_FLAG_TRANSFORM.is_set_on(self.flags()) {
+      true  => self.modifiers() + HVIF_VIEW_MATRIX_SIZE,
+      false => self.modifiers(),
+    }
+  }
+
+  fn lod_scale_offset(&self) -> usize
+  {
+    let mut offset = self.translation_offset();
+    if HVIF_SHAPE_FLAG_TRANSLATION.is_set_on(self.flags()) {
+      hvif_view_point(self.bytes, &mut offset);
+    }
+    offset
+  }
+}
+
+#[derive(Debug, Clone)]
+/// The transformers of a viewed shape
+pub struct HVIFViewTransformers<'a> {
+  rest: &'a [u8],
+  remaining: usize,
+}
+
+impl<'a> Iterator for HVIFViewTransformers<'a> {
+  type Item = HVIFTransformer;
+
+  fn next(&mut self) -> Option<HVIFTransformer>
+  {
+    if self.remaining == 0 {
+      return None
+    }
+    self.remaining -= 1;
+    let bytes = self.rest;
+    let (transformer, len) = match bytes[0] {
+      HVIF_VIEW_TRANSFORMER_AFFINE => (HVIFTransformer::Affine(hvif_view_matrix(&bytes[1..])), HVIF_VIEW_MATRIX_SIZE),
+      HVIF_VIEW_TRANSFORMER_CONTOUR => (HVIFTransformer::Contour {
+        width: bytes[1] as f32 - 128.0,
+        line_join: line_join_from_u8(bytes[2]).expect("the view checked the line join"),
+        miter_limit: bytes[3],
+      }, 3),
+      HVIF_VIEW_TRANSFORMER_STROKE => (HVIFTransformer::Stroke {
+        width: bytes[1] as f32 - 128.0,
+        line_join: line_join_from_u8(bytes[2] & 15).expect("the view checked the line join"),
+        line_cap: line_cap_from_u8(bytes[2] >> 4).expect("the view checked the line cap"),
+        miter_limit: bytes[3],
+      }, 3),
+      _ => (HVIFTransformer::Perspective, 0),
+    };
+    self.rest = &bytes[1 + len..];
+    Some(transformer)
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>)
+  {
+    (self.remaining, Some(self.remaining))
+  }
+}
+
+impl<'a> ExactSizeIterator for HVIFViewTransformers<'a> {}
@@ -0,0 +1,73 @@
+//! Writers for HVIF images
+pub mod style;
+pub mod path;
+pub mod shape;
+pub mod util;
+
+use std::fmt;
+
+use types::*;
+use validate::{HVIFDiagnostic, HVIFSeverity};
+
+use self::style::hvif_style;
+use self::path::hvif_path;
+use self::shape::hvif_shape;
+
+/// The largest number of entries a single HVIF list can hold, as its length is stored in one byte
+pub const HVIF_MAX_LIST_LENGTH: usize = 255;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// Reasons an HVIF image can fail to be written
+pub enum HVIFWriteError {
+  /// Validating the image found an error, the first of which is given
+  Invalid(HVIFDiagnostic),
+}
+
+impl fmt::Display for HVIFWriteError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      HVIFWriteError::Invalid(diagnostic) =>
+        write!(f, "invalid image, {}", diagnostic),
+    }
+  }
+}
+
+impl ::std::error::Error for HVIFWriteError {}
+
+/// Writes an entire HVIF image, after validating it
+///
+/// Images with errors are refused, while warnings are ignored. Validating first is what lets the writers
+/// of single entries take for granted that every list fits and that no shape has conflicting modifiers.
+pub fn hvif_image(image: &HVIFImage) -> Result<Vec<u8>, HVIFWriteError>
+{
+  if let Some(diagnostic) = image.validate().into_iter().find(|diagnostic| diagnostic.severity == HVIFSeverity::Error) {
+    return Err(HVIFWriteError::Invalid(diagnostic))
+  }
+
+  let mut out = Vec::new();
+  out.extend_from_slice(b"ncif");
+
+  hvif_list_length(&mut out, image.styles.len());
+  for style in &image.styles {
+    hvif_style(&mut out, style);
+  }
+
+  hvif_list_length(&mut out, image.paths.len());
+  for path in &image.paths {
+    hvif_path(&mut out, path);
+  }
+
+  hvif_list_length(&mut out, image.shapes.len());
+  for shape in &image.shapes {
+    hvif_shape(&mut out, shape);
+  }
+
+  Ok(out)
+}
+
+/// Writes the single byte length of a list, which validation has checked fits in it
+pub fn hvif_list_length(out: &mut Vec<u8>, count: usize)
+{
+  debug_assert!(count <= HVIF_MAX_LIST_LENGTH, "a list of {} entries cannot be written", count);
+  out.push(count as u8);
+}
@@ -0,0 +1,100 @@
+//! Writer for HVIF paths
+use types::*;
+
+use writer::*;
+use writer::util::*;
+
+/// Writes an HVIF path, choosing whichever of the three path encodings is shortest
+///
+/// The path must hold no more points than a list can, as validating its image checks.
+pub fn hvif_path(out: &mut Vec<u8>, path: &HVIFPath)
+{
+  let mut flags = 0;
+  if path.closed {
+    flags = HVIF_PATH_FLAG_CLOSED.set_on(flags);
+  }
+
+  // Every path can be written with commands, or as a list of curves
+  let mut candidates = vec![
+    (HVIF_PATH_FLAG_USES_COMMANDS.set_on(flags), hvif_path_with_commands(&path.points)),
+    (flags, hvif_path_curves(path)),
+  ];
+  // Only paths without any curves can be written as a list of plain points
+  if path.points.iter().all(|p| !is_curve(p)) {
+    candidates.push((HVIF_PATH_FLAG_NO_CURVES.set_on(flags), hvif_path_no_curves(path)));
+  }
+  // Prefer the later candidates when they are no longer, as they don't need a command section
+  let (flags, body) = candidates.into_iter().rev().min_by_key(|candidate| candidate.1.len()).unwrap();
+
+  out.push(flags);
+  hvif_list_length(out, path.points.len());
+  out.extend_from_slice(&body);
+}
+
+/// Whether a point command actually bends, rather than being a straight line stored as a curve
+fn is_curve(command: &HVIFPointCommand) -> bool
+{
+  match *command {
+    HVIFPointCommand::Curve { point_in, point, point_out } =>
+      !same_point(&point_in, &point) || !same_point(&point_out, &point),
+    _ => false,
+  }
+}
+
+fn same_point(a: &HVIFPoint, b: &HVIFPoint) -> bool
+{
+  a.x == b.x && a.y == b.y
+}
+
+fn hvif_path_with_commands(commands: &[HVIFPointCommand]) -> Vec<u8>
+{
+  let mut command_bytes = vec![0u8; commands.len().div_ceil(4)];
+  let mut points = Vec::new();
+  for (index, command) in commands.iter().enumerate() {
+    let command_type: u8 = match *command {
+      HVIFPointCommand::HLine { x } => {
+        hvif_path_coord(&mut points, x);
+        0
+      },
+      HVIFPointCommand::VLine { y } => {
+        hvif_path_coord(&mut points, y);
+        1
+      },
+      HVIFPointCommand::Line { ref point } => {
+        hvif_point(&mut points, point);
+        2
+      },
+      HVIFPointCommand::Curve { ref point_in, ref point, ref point_out } => {
+        hvif_point(&mut points, point);
+        hvif_point(&mut points, point_in);
+        hvif_point(&mut points, point_out);
+        3
+      },
+    };
+    // Commands are packed four to a byte, starting from the least significant bits
+    command_bytes[index / 4] |= command_type << ((index % 4) * 2);
+  }
+
+  command_bytes.extend_from_slice(&points);
+  command_bytes
+}
+
+fn hvif_path_no_curves(path: &HVIFPath) -> Vec<u8>
+{
+  let mut body = Vec::new();
+  for vertex in path.vertices() {
+    hvif_point(&mut body, &vertex.point);
+  }
+  body
+}
+
+fn hvif_path_curves(path: &HVIFPath) -> Vec<u8>
+{
+  let mut body = Vec::new();
+  for vertex in path.vertices() {
+    hvif_point(&mut body, &vertex.point);
+    hvif_point(&mut body, &vertex.point_in);
+    hvif_point(&mut body, &vertex.point_out);
+  }
+  body
+}
@@ -0,0 +1,127 @@
+//! Writer for HVIF shapes
+use types::*;
+
+use writer::*;
+use writer::util::*;
+
+/// Writes a shape modifier if it is of the kind the writer handles, returning whether it was
+type ModifierWriter = fn(&mut Vec<u8>, &HVIFShapeModifier) -> bool;
+
+/// Writes an HVIF shape, setting its flags from the modifiers it carries
+///
+/// The shape must be one that validates, holding at most one of each modifier, not both a matrix and
+/// a translation, and no more paths or transformers than a list can.
+pub fn hvif_shape(out: &mut Vec<u8>, shape: &HVIFShape)
+{
+  out.push(0x0a); // There is only one shape type, SHAPE_TYPE_PATH_SOURCE
+  out.push(shape.style_index);
+  hvif_list_length(out, shape.path_indices.len());
+  out.extend_from_slice(&shape.path_indices);
+
+  let mut flags = 0;
+  let mut body = Vec::new();
+
+  // Order of these writers matter, and must match the parser!
+  let writers_per_flags : Vec<(HVIFFlag, ModifierWriter)> = vec![
+    (HVIF_SHAPE_FLAG_HINTING         , hvif_shape_modifier_hinting),
+    (HVIF_SHAPE_FLAG_TRANSFORM       , hvif_shape_modifier_transform),
+    (HVIF_SHAPE_FLAG_TRANSLATION     , hvif_shape_modifier_translation),
+    (HVIF_SHAPE_FLAG_LOD_SCALE       , hvif_shape_modifier_lod_scale),
+    (HVIF_SHAPE_FLAG_HAS_TRANSFORMERS, hvif_shape_modifier_has_transformers),
+  ];
+  for (flag, writer) in writers_per_flags {
+    for modifier in &shape.modifiers {
+      if writer(&mut body, modifier) {
+        flags = flag.set_on(flags);
+      }
+    }
+  }
+
+  out.push(flags);
+  out.extend_from_slice(&body);
+}
+
+// Each modifier writer returns whether the modifier was of its kind, and so was written
+
+fn hvif_shape_modifier_hinting(_: &mut Vec<u8>, modifier: &HVIFShapeModifier) -> bool
+{
+  matches!(*modifier, HVIFShapeModifier::HVIFHinting)
+}
+
+fn hvif_shape_modifier_transform(out: &mut Vec<u8>, modifier: &HVIFShapeModifier) -> bool
+{
+  match *modifier {
+    HVIFShapeModifier::HVIFTransformMatrix(ref matrix) => {
+      hvif_shape_matrix(out, matrix);
+      true
+    },
+    _ => false,
+  }
+}
+
+fn hvif_shape_modifier_translation(out: &mut Vec<u8>, modifier: &HVIFShapeModifier) -> bool
+{
+  match *modifier {
+    HVIFShapeModifier::HVIFTranslation(ref point) => {
+      hvif_point(out, point);
+      true
+    },
+    _ => false,
+  }
+}
+
+fn hvif_shape_modifier_lod_scale(out: &mut Vec<u8>, modifier: &HVIFShapeModifier) -> bool
+{
+  match *modifier {
+    HVIFShapeModifier::HVIFLODScale { min, max } => {
+      out.push(hvif_lod_scale_byte(min));
+      out.push(hvif_lod_scale_byte(max));
+      true
+    },
+    _ => false,
+  }
+}
+
+fn hvif_lod_scale_byte(scale: f32) -> u8
+{
+  (scale * 63.75).round().clamp(0.0, 255.0) as u8
+}
+
+fn hvif_shape_modifier_has_transformers(out: &mut Vec<u8>, modifier: &HVIFShapeModifier) -> bool
+{
+  match *modifier {
+    HVIFShapeModifier::HVIFTransformerList(ref transformers) => {
+      hvif_list_length(out, transformers.len());
+      for transformer in transformers {
+        hvif_shape_modifier_transformer(out, transformer);
+      }
+      true
+    },
+    _ => false,
+  }
+}
+
+fn hvif_shape_modifier_transformer(out: &mut Vec<u8>, transformer: &HVIFTransformer)
+{
+  match *transformer {
+    HVIFTransformer::Affine(ref matrix) => {
+      out.push(20);
+      hvif_shape_matrix(out, matrix);
+    },
+    HVIFTransformer::Contour { width, line_join, miter_limit } => {
+      out.extend_from_slice(&[21, hvif_transformer_width_byte(width), line_join as u8, miter_limit]);
+    },
+    HVIFTransformer::Perspective => {
+      out.push(22);
+    },
+    HVIFTransformer::Stroke { width, line_join, line_cap, miter_limit } => {
+      let line_opts = (line_join as u8) | ((line_cap as u8) << 4);
+      out.extend_from_slice(&[23, hvif_transformer_width_byte(width), line_opts, miter_limit]);
+    },
+  }
+}
+
+fn hvif_transformer_width_byte(width: f32) -> u8
+{
+  (width + 128.0).round().clamp(0.0, 255.0) as u8
+}
@@ -0,0 +1,72 @@
+//! Writer for HVIF styles
+use types::*;
+
+use writer::*;
+use writer::util::*;
+
+/// Writes an HVIF style
+///
+/// A gradient must hold no more colors than a list can, as validating its image checks.
+pub fn hvif_style(out: &mut Vec<u8>, style: &HVIFStyle)
+{
+  match *style {
+    HVIFStyle::SolidColor { red, green, blue, alpha } => {
+      out.extend_from_slice(&[1, red, green, blue, alpha]);
+    },
+    HVIFStyle::Gradient(ref gradient) => {
+      out.push(2);
+      hvif_style_gradient(out, gradient);
+    },
+    HVIFStyle::SolidColorNoAlpha { red, green, blue } => {
+      out.extend_from_slice(&[3, red, green, blue]);
+    },
+    HVIFStyle::SolidGray { value, alpha } => {
+      out.extend_from_slice(&[4, value, alpha]);
+    },
+    HVIFStyle::SolidGrayNoAlpha { value } => {
+      out.extend_from_slice(&[5, value]);
+    },
+  }
+}
+
+fn hvif_style_gradient(out: &mut Vec<u8>, gradient: &HVIFGradient)
+{
+  let flags = hvif_style_gradient_flags(gradient);
+  out.push(gradient.gradient_type as u8);
+  out.push(flags);
+  hvif_list_length(out, gradient.colors.len());
+  if let Some(ref matrix) = gradient.transform {
+    hvif_shape_matrix(out, matrix);
+  }
+
+  let no_alpha = HVIF_GRADIENT_FLAG_NO_ALPHA.is_set_on(flags);
+  let grayscale = HVIF_GRADIENT_FLAG_GRAYS.is_set_on(flags);
+  for color in &gradient.colors {
+    out.push(color.stop_offset);
+    match grayscale {
+      true  => out.push(color.red),
+      false => out.extend_from_slice(&[color.red, color.green, color.blue]),
+    }
+    if !no_alpha {
+      out.push(color.alpha);
+    }
+  }
+}
+
+/// Picks the most compact set of flags that can still represent every color of the gradient
+///
+/// Colors are only ever written with 8 bits per channel
+fn hvif_style_gradient_flags(gradient: &HVIFGradient) -> u8
+{
+  let mut flags = 0;
+  if gradient.transform.is_some() {
+    flags = HVIF_GRADIENT_FLAG_TRANSFORM.set_on(flags);
+  }
+  if gradient.colors.iter().all(|c| c.alpha == 0b1111_1111) {
+    flags = HVIF_GRADIENT_FLAG_NO_ALPHA.set_on(flags);
+  }
+  if gradient.colors.iter().all(|c| c.red == c.green && c.green == c.blue) {
+    flags = HVIF_GRADIENT_FLAG_GRAYS.set_on(flags);
+  }
+  flags
+}
@@ -0,0 +1,37 @@
+//! Utility writers used throughout the HVIF format
+
+use types::*;
+use parser::util::hvif_coord_to_bytes;
+
+/// Writes an HVIF point
+pub fn hvif_point(out: &mut Vec<u8>, point: &HVIFPoint)
+{
+  hvif_path_coord(out, point.x);
+  hvif_path_coord(out, point.y);
+}
+
+/// Writes an HVIF coordinate, in one byte if possible and two otherwise
+pub fn hvif_path_coord(out: &mut Vec<u8>, coord: f32)
+{
+  let (first, second) = hvif_coord_to_bytes(coord);
+  out.push(first);
+  if let Some(second) = second {
+    out.push(second);
+  }
+}
+
+/// Writes an HVIF affine matrix
+pub fn hvif_shape_matrix(out: &mut Vec<u8>, matrix: &HVIFMatrix)
+{
+  for value in &[matrix.x1, matrix.y1, matrix.z1, matrix.x2, matrix.y2, matrix.z2] {
+    hvif_shape_f24(out, value);
+  }
+}
+
+/// Writes an HVIF 24-bit float
+pub fn hvif_shape_f24(out: &mut Vec<u8>, value: &HVIFf24)
+{
+  out.push(value.fst);
+  out.push(value.snd);
+  out.push(value.thr);
+}
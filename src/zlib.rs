@@ -0,0 +1,444 @@
+//! Compression and decompression in the zlib format, as used by PNG images and Haiku packages
+//!
+//! Data is compressed into a single deflate block with the fixed Huffman codes,
+//! finding repeated runs with hash chains over a 32 KiB window.
+//! Decompression reads any deflate stream, with stored, fixed or dynamic blocks.
+
+/// The largest distance back a match may reach
+const WINDOW_SIZE: usize = 32768;
+/// The shortest and longest runs deflate can express as matches
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// How many earlier positions are tried for each match, trading speed for size
+const MAX_CHAIN: usize = 128;
+const HASH_BITS: u32 = 15;
+
+/// The first length of each length code from 257, and its number of extra bits
+const LENGTH_BASES: [(u16, u8); 29] = [
+  (3, 0), (4, 0), (5, 0), (6, 0), (7, 0), (8, 0), (9, 0), (10, 0), (11, 1), (13, 1), (15, 1), (17, 1),
+  (19, 2), (23, 2), (27, 2), (31, 2), (35, 3), (43, 3), (51, 3), (59, 3), (67, 4), (83, 4), (99, 4), (115, 4),
+  (131, 5), (163, 5), (195, 5), (227, 5), (258, 0),
+];
+
+/// The first distance of each distance code, and its number of extra bits
+const DISTANCE_BASES: [(u16, u8); 30] = [
+  (1, 0), (2, 0), (3, 0), (4, 0), (5, 1), (7, 1), (9, 2), (13, 2), (17, 3), (25, 3), (33, 4), (49, 4),
+  (65, 5), (97, 5), (129, 6), (193, 6), (257, 7), (385, 7), (513, 8), (769, 8), (1025, 9), (1537, 9),
+  (2049, 10), (3073, 10), (4097, 11), (6145, 11), (8193, 12), (12289, 12), (16385, 13), (24577, 13),
+];
+
+/// Compresses data into a zlib stream
+pub fn compress(data: &[u8]) -> Vec<u8>
+{
+  let mut out = BitWriter { bytes: vec![0x78, 0x9c], buffer: 0, count: 0 };
+
+  // A single, final block with the fixed codes
+  out.write(1, 1);
+  out.write(1, 2);
+
+  let mut head = vec![usize::MAX; 1 << HASH_BITS];
+  let mut previous = vec![usize::MAX; WINDOW_SIZE];
+  let mut position = 0;
+  while position < data.len() {
+    let (length, distance) = longest_match(data, position, &head, &previous);
+    let step = match length >= MIN_MATCH {
+      true  => { write_match(&mut out, length, distance); length },
+      false => { write_symbol(&mut out, data[position] as u16); 1 },
+    };
+    for inserted in position..(position + step) {
+      if inserted + MIN_MATCH <= data.len() {
+        let hash = hash(&data[inserted..]);
+        previous[inserted % WINDOW_SIZE] = head[hash];
+        head[hash] = inserted;
+      }
+    }
+    position += step;
+  }
+  write_symbol(&mut out, 256);
+
+  let mut bytes = out.finish();
+  bytes.extend_from_slice(&adler32(data).to_be_bytes());
+  bytes
+}
+
+fn hash(data: &[u8]) -> usize
+{
+  let value = ((data[0] as u32) << 16) | ((data[1] as u32) << 8) | (data[2] as u32);
+  (value.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+}
+
+/// The longest earlier run matching the data at a position, as its length and distance back
+fn longest_match(data: &[u8], position: usize, head: &[usize], previous: &[usize]) -> (usize, usize)
+{
+  if position + MIN_MATCH > data.len() {
+    return (0, 0)
+  }
+  let limit = (data.len() - position).min(MAX_MATCH);
+  let (mut best_length, mut best_distance) = (0, 0);
+  let mut candidate = head[hash(&data[position..])];
+  let mut chain = 0;
+  while candidate != usize::MAX && candidate < position && position - candidate <= WINDOW_SIZE && chain < MAX_CHAIN {
+    let length = data[candidate..].iter().zip(&data[position..position + limit]).take_while(|&(a, b)| a == b).count();
+    if length > best_length {
+      best_length = length;
+      best_distance = position - candidate;
+      if length == limit {
+        break
+      }
+    }
+    let next = previous[candidate % WINDOW_SIZE];
+    // Entries of the chain only ever go further back, unless the window has wrapped around
+    if next >= candidate {
+      break
+    }
+    candidate = next;
+    chain += 1;
+  }
+  (best_length, best_distance)
+}
+
+fn write_match(out: &mut BitWriter, length: usize, distance: usize)
+{
+  let code = LENGTH_BASES.iter().rposition(|&(base, _)| (base as usize) <= length).unwrap();
+  let (base, extra) = LENGTH_BASES[code];
+  write_symbol(out, 257 + code as u16);
+  out.write((length - base as usize) as u32, extra);
+
+  let code = DISTANCE_BASES.iter().rposition(|&(base, _)| (base as usize) <= distance).unwrap();
+  let (base, extra) = DISTANCE_BASES[code];
+  out.write_reversed(code as u32, 5);
+  out.write((distance - base as usize) as u32, extra);
+}
+
+/// Writes a literal or length symbol with its fixed Huffman code
+fn write_symbol(out: &mut BitWriter, symbol: u16)
+{
+  let symbol = symbol as u32;
+  match symbol {
+    0..=143   => out.write_reversed(0x30 + symbol, 8),
+    144..=255 => out.write_reversed(0x190 + symbol - 144, 9),
+    256..=279 => out.write_reversed(symbol - 256, 7),
+    _         => out.write_reversed(0xc0 + symbol - 280, 8),
+  }
+}
+
+/// The order in which the lengths of the code length code are stored in a dynamic block
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/// Decompresses a zlib stream, or gives nothing if it is malformed or its checksum does not match
+pub fn decompress(data: &[u8]) -> Option<Vec<u8>>
+{
+  // The header names deflate with a window of at most 32 KiB, without a preset dictionary
+  let (method, flags) = (*data.first()?, *data.get(1)?);
+  if method & 0x0f != 8 || method >> 4 > 7 || flags & 0x20 != 0 || !((method as u16) << 8 | flags as u16).is_multiple_of(31) {
+    return None
+  }
+
+  let mut input = BitReader { bytes: &data[2..], position: 0, buffer: 0, count: 0 };
+  let mut out = Vec::new();
+  loop {
+    let last = input.read(1)? == 1;
+    match input.read(2)? {
+      0 => inflate_stored(&mut input, &mut out)?,
+      1 => inflate_block(&mut input, &mut out, &Huffman::fixed_literals(), &Huffman::fixed_distances())?,
+      2 => {
+        let (literals, distances) = inflate_dynamic_codes(&mut input)?;
+        inflate_block(&mut input, &mut out, &literals, &distances)?
+      },
+      _ => return None,
+    }
+    if last {
+      break
+    }
+  }
+
+  let checksum = input.bytes.get(input.position..input.position + 4)?;
+  match u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]) == adler32(&out) {
+    true  => Some(out),
+    false => None,
+  }
+}
+
+fn inflate_stored(input: &mut BitReader, out: &mut Vec<u8>) -> Option<()>
+{
+  // Stored blocks start on a byte boundary, with their length and its complement
+  input.align();
+  let header = input.bytes.get(input.position..input.position + 4)?;
+  let length = u16::from_le_bytes([header[0], header[1]]);
+  if length != !u16::from_le_bytes([header[2], header[3]]) {
+    return None
+  }
+  let start = input.position + 4;
+  out.extend_from_slice(input.bytes.get(start..start + length as usize)?);
+  input.position = start + length as usize;
+  Some(())
+}
+
+/// Reads the literal and length code, and the distance code, of a dynamic block
+fn inflate_dynamic_codes(input: &mut BitReader) -> Option<(Huffman, Huffman)>
+{
+  let literal_count = input.read(5)? as usize + 257;
+  let distance_count = input.read(5)? as usize + 1;
+  let code_length_count = input.read(4)? as usize + 4;
+  if literal_count > 286 || distance_count > 30 {
+    return None
+  }
+
+  let mut code_lengths = [0u8; 19];
+  for &symbol in &CODE_LENGTH_ORDER[..code_length_count] {
+    code_lengths[symbol] = input.read(3)? as u8;
+  }
+  let code_length_code = Huffman::new(&code_lengths)?;
+
+  // The lengths of both codes are run length coded together
+  let mut lengths = Vec::with_capacity(literal_count + distance_count);
+  while lengths.len() < literal_count + distance_count {
+    let (value, repeat) = match code_length_code.decode(input)? {
+      symbol @ 0..=15 => (symbol as u8, 1),
+      16 => (*lengths.last()?, 3 + input.read(2)?),
+      17 => (0, 3 + input.read(3)?),
+      _  => (0, 11 + input.read(7)?),
+    };
+    lengths.extend((0..repeat).map(|_| value));
+  }
+  if lengths.len() > literal_count + distance_count || lengths[256] == 0 {
+    return None
+  }
+  Some((Huffman::new(&lengths[..literal_count])?, Huffman::new(&lengths[literal_count..])?))
+}
+
+fn inflate_block(input: &mut BitReader, out: &mut Vec<u8>, literals: &Huffman, distances: &Huffman) -> Option<()>
+{
+  loop {
+    let symbol = literals.decode(input)? as usize;
+    if symbol < 256 {
+      out.push(symbol as u8);
+      continue
+    }
+    if symbol == 256 {
+      return Some(())
+    }
+
+    let &(base, extra) = LENGTH_BASES.get(symbol - 257)?;
+    let length = base as usize + input.read(extra)? as usize;
+    let &(base, extra) = DISTANCE_BASES.get(distances.decode(input)? as usize)?;
+    let distance = base as usize + input.read(extra)? as usize;
+    if distance > out.len() {
+      return None
+    }
+    // Matches may overlap the bytes they copy, repeating them
+    let start = out.len() - distance;
+    for index in start..start + length {
+      let byte = out[index];
+      out.push(byte);
+    }
+  }
+}
+
+/// A canonical Huffman code, as the number of codes of each length and the symbols in code order
+struct Huffman {
+  counts: [u16; 16],
+  symbols: Vec<u16>,
+}
+
+impl Huffman {
+  /// Builds the code with the given code length for each symbol, where zero leaves a symbol out
+  fn new(lengths: &[u8]) -> Option<Huffman> {
+    let mut counts = [0u16; 16];
+    for &length in lengths {
+      counts[length as usize] += 1;
+    }
+    counts[0] = 0;
+
+    // Too many codes of some length cannot be decoded, while too few are allowed
+    let mut left = 1i32;
+    for &count in &counts[1..] {
+      left = (left << 1) - count as i32;
+      if left < 0 {
+        return None
+      }
+    }
+
+    let mut offsets = [0u16; 16];
+    for length in 1..15 {
+      offsets[length + 1] = offsets[length] + counts[length];
+    }
+    let mut symbols = vec![0u16; lengths.len()];
+    for (symbol, &length) in lengths.iter().enumerate() {
+      if length != 0 {
+        symbols[offsets[length as usize] as usize] = symbol as u16;
+        offsets[length as usize] += 1;
+      }
+    }
+    Some(Huffman { counts, symbols })
+  }
+
+  fn fixed_literals() -> Huffman {
+    let lengths: Vec<u8> = (0..288).map(|symbol| match symbol {
+      0..=143 => 8,
+      144..=255 => 9,
+      256..=279 => 7,
+      _ => 8,
+    }).collect();
+    Huffman::new(&lengths).unwrap()
+  }
+
+  fn fixed_distances() -> Huffman {
+    Huffman::new(&[5; 30]).unwrap()
+  }
+
+  /// Reads one symbol, a bit at a time, from the most significant bit of its code
+  fn decode(&self, input: &mut BitReader) -> Option<u16> {
+    let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+    for &count in &self.counts[1..] {
+      code |= input.read(1)? as i32;
+      let count = count as i32;
+      if code - count < first {
+        return self.symbols.get((index + code - first) as usize).cloned()
+      }
+      index += count;
+      first = (first + count) << 1;
+      code <<= 1;
+    }
+    None
+  }
+}
+
+/// Unpacks values from bytes from their least significant bit upward, as deflate stores them
+struct BitReader<'a> {
+  bytes: &'a [u8],
+  position: usize,
+  buffer: u32,
+  count: u8,
+}
+
+impl<'a> BitReader<'a> {
+  fn read(&mut self, bits: u8) -> Option<u32> {
+    while self.count < bits {
+      self.buffer |= (*self.bytes.get(self.position)? as u32) << self.count;
+      self.position += 1;
+      self.count += 8;
+    }
+    let value = self.buffer & ((1u32 << bits) - 1);
+    self.buffer >>= bits;
+    self.count -= bits;
+    Some(value)
+  }
+
+  /// Drops the bits left in the current byte
+  fn align(&mut self) {
+    self.buffer = 0;
+    self.count = 0;
+  }
+}
+
+/// The Adler-32 checksum of some data, which ends a zlib stream
+pub fn adler32(data: &[u8]) -> u32
+{
+  let (mut a, mut b) = (1u32, 0u32);
+  // Sums are taken modulo 65521 often enough that they never overflow
+  for chunk in data.chunks(5552) {
+    for &byte in chunk {
+      a += byte as u32;
+      b += a;
+    }
+    a %= 65521;
+    b %= 65521;
+  }
+  (b << 16) | a
+}
+
+/// Packs values into bytes from their least significant bit upward, as deflate does
+struct BitWriter {
+  bytes: Vec<u8>,
+  buffer: u32,
+  count: u8,
+}
+
+impl BitWriter {
+  fn write(&mut self, value: u32, bits: u8) {
+    for bit in 0..bits {
+      self.buffer |= ((value >> bit) & 1) << self.count;
+      self.count += 1;
+      if self.count == 8 {
+        self.bytes.push(self.buffer as u8);
+        self.buffer = 0;
+        self.count = 0;
+      }
+    }
+  }
+
+  /// Writes a Huffman code, which deflate stores from its most significant bit
+  fn write_reversed(&mut self, code: u32, bits: u8) {
+    self.write(code.reverse_bits() >> (32 - bits as u32), bits);
+  }
+
+  fn finish(mut self) -> Vec<u8> {
+    if self.count > 0 {
+      self.bytes.push(self.buffer as u8);
+    }
+    self.bytes
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const TEXT: &[u8] = b"Haiku Vector Icon Format, as written by Icon-O-Matic. \
+    Haiku Vector Icon Format, as written by Icon-O-Matic. \
+    Haiku Vector Icon Format, as written by Icon-O-Matic. styles, paths and shapes";
+
+  /// `TEXT` as zlib compresses it at its best, in one block with dynamic codes
+  const DYNAMIC: [u8; 85] = [
+    0x78, 0xda, 0xbd, 0xcb, 0xc1, 0x09, 0x80, 0x30, 0x0c, 0x05, 0xd0, 0x55, 0xfe, 0x00, 0xad, 0x63, 0x48, 0x3d,
+    0x88, 0x37, 0xef, 0xb1, 0x06, 0x1a, 0xd4, 0xb6, 0x34, 0x11, 0xe9, 0xf6, 0x82, 0x43, 0x78, 0x7e, 0xbc, 0x40,
+    0x72, 0xdc, 0x58, 0x39, 0x5a, 0x69, 0x98, 0x62, 0xc9, 0x18, 0x4b, 0xbb, 0xc8, 0x1c, 0x48, 0xf1, 0x34, 0x31,
+    0xe3, 0x8c, 0xad, 0x7f, 0xe4, 0x17, 0x3f, 0x93, 0x49, 0x1c, 0x10, 0x7e, 0x5c, 0x6a, 0xfd, 0x64, 0x75, 0xa8,
+    0x64, 0x49, 0x41, 0x79, 0x87, 0x26, 0xaa, 0xac, 0x2f, 0x8b, 0xa6, 0x40, 0xaf,
+  ];
+
+  /// Stores data in a zlib stream of stored blocks, split where given
+  fn stored(data: &[u8], split: usize) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    for (block, last) in [(&data[..split], 0u8), (&data[split..], 1)] {
+      out.push(last);
+      out.extend_from_slice(&(block.len() as u16).to_le_bytes());
+      out.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+      out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+  }
+
+  #[test]
+  fn fixed_blocks_round_trip() {
+    let long: Vec<u8> = (0..70_000u64).map(|index| (index * index % 251) as u8).collect();
+    for data in [&b""[..], b"a", TEXT, &long] {
+      let compressed = compress(data);
+      assert_eq!(decompress(&compressed).as_deref(), Some(data));
+    }
+    assert!(compress(TEXT).len() < TEXT.len());
+  }
+
+  #[test]
+  fn reads_stored_and_dynamic_blocks() {
+    assert_eq!(decompress(&stored(TEXT, 20)).as_deref(), Some(TEXT));
+    assert_eq!(decompress(&stored(TEXT, 0)).as_deref(), Some(TEXT));
+    assert_eq!(decompress(&DYNAMIC).as_deref(), Some(TEXT));
+  }
+
+  #[test]
+  fn rejects_damaged_streams() {
+    let mut damaged = DYNAMIC;
+    damaged[84] ^= 1;
+    assert_eq!(decompress(&damaged), None);
+    for cut in 0..DYNAMIC.len() {
+      assert_eq!(decompress(&DYNAMIC[..cut]), None);
+    }
+    let mut stored = stored(TEXT, 20);
+    // A stored length that does not match its complement
+    stored[3] ^= 1;
+    assert_eq!(decompress(&stored), None);
+  }
+}
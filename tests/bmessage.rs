@@ -0,0 +1,173 @@
+extern crate hvif_rs;
+
+use hvif_rs::bmessage::*;
+
+/// A field laid out by hand: its name, type code, whether its items are packed, and its items
+type Field<'a> = (&'a str, u32, bool, Vec<Vec<u8>>);
+
+/// Flattens a message by hand, in either byte order, as Haiku lays it out
+fn flattened(big_endian: bool, what: u32, fields: &[Field]) -> Vec<u8> {
+  let u16_bytes = |value: u16| if big_endian { value.to_be_bytes() } else { value.to_le_bytes() };
+  let u32_bytes = |value: u32| if big_endian { value.to_be_bytes() } else { value.to_le_bytes() };
+
+  let mut headers = Vec::new();
+  let mut data = Vec::new();
+  for &(name, type_code, fixed_size, ref items) in fields {
+    let offset = data.len();
+    data.extend_from_slice(name.as_bytes());
+    data.push(0);
+    let values_offset = data.len();
+    for item in items {
+      if !fixed_size {
+        data.extend_from_slice(&u32_bytes(item.len() as u32));
+      }
+      data.extend_from_slice(item);
+    }
+    headers.extend_from_slice(&u16_bytes(if fixed_size { 3 } else { 1 }));
+    headers.extend_from_slice(&u16_bytes(name.len() as u16 + 1));
+    headers.extend_from_slice(&u32_bytes(type_code));
+    headers.extend_from_slice(&u32_bytes(items.len() as u32));
+    headers.extend_from_slice(&u32_bytes((data.len() - values_offset) as u32));
+    headers.extend_from_slice(&u32_bytes(offset as u32));
+    headers.extend_from_slice(&u32_bytes(u32::MAX));
+  }
+
+  let mut out = Vec::new();
+  out.extend_from_slice(&u32_bytes(0x484d_4631));
+  out.extend_from_slice(&u32_bytes(what));
+  out.extend_from_slice(&u32_bytes(1));
+  for _ in 0..6 {
+    out.extend_from_slice(&u32_bytes(u32::MAX));
+  }
+  out.extend_from_slice(&u32_bytes(data.len() as u32));
+  out.extend_from_slice(&u32_bytes(fields.len() as u32));
+  // An empty hash table, which readers do not need to find fields
+  out.extend_from_slice(&u32_bytes(0));
+  out.extend_from_slice(&headers);
+  out.extend_from_slice(&data);
+  out
+}
+
+fn every_value() -> Vec<(&'static str, BMessageValue)> {
+  let mut nested = BMessage::new(0x6e65_7374);
+  nested.add("depth", BMessageValue::Int32(2)).unwrap();
+  vec![
+    ("bool", BMessageValue::Bool(true)),
+    ("int8", BMessageValue::Int8(-8)),
+    ("int16", BMessageValue::Int16(-1600)),
+    ("int32", BMessageValue::Int32(-320_000)),
+    ("int64", BMessageValue::Int64(-64_000_000_000)),
+    ("uint8", BMessageValue::UInt8(200)),
+    ("uint16", BMessageValue::UInt16(60_000)),
+    ("uint32", BMessageValue::UInt32(4_000_000_000)),
+    ("uint64", BMessageValue::UInt64(u64::MAX - 1)),
+    ("float", BMessageValue::Float(0.25)),
+    ("double", BMessageValue::Double(-1.0e100)),
+    ("string", BMessageValue::String("Icon-O-Matic".to_string())),
+    ("point", BMessageValue::Point { x: 1.5, y: -2.0 }),
+    ("rect", BMessageValue::Rect { left: 0.0, top: 1.0, right: 63.0, bottom: 64.0 }),
+    ("color", BMessageValue::Color { red: 1, green: 2, blue: 3, alpha: 4 }),
+    ("message", BMessageValue::Message(nested)),
+    ("raw", BMessageValue::Raw(vec![0xde, 0xad, 0xbe, 0xef])),
+    ("data", BMessageValue::Data { type_code: 0x5445_5354, data: vec![9, 8, 7] }),
+  ]
+}
+
+#[test]
+fn every_value_type_round_trips() {
+  let mut message = BMessage::new(0x7465_7374);
+  for (name, value) in every_value() {
+    message.add(name, value).unwrap();
+  }
+  // Fields holding several items, of one size and of differing sizes
+  message.add("strings", BMessageValue::String("a".to_string())).unwrap();
+  message.add("strings", BMessageValue::String("bcd".to_string())).unwrap();
+  message.add("points", BMessageValue::Point { x: 0.0, y: 1.0 }).unwrap();
+  message.add("points", BMessageValue::Point { x: 2.0, y: 3.0 }).unwrap();
+  message.add("raws", BMessageValue::Raw(vec![1])).unwrap();
+  message.add("raws", BMessageValue::Raw(vec![2, 3])).unwrap();
+
+  let read = BMessage::unflatten(&message.flatten()).unwrap();
+  assert_eq!(read, message);
+  assert_eq!(read.find_int32("int32", 0), Some(-320_000));
+  assert_eq!(read.find_string("strings", 1), Some("bcd"));
+  assert_eq!(read.find_point("points", 1), Some((2.0, 3.0)));
+  assert_eq!(read.find_color("color", 0), Some([1, 2, 3, 4]));
+  assert_eq!(read.find_data("data", 0x5445_5354, 0), Some(&[9, 8, 7][..]));
+  assert_eq!(read.find_message("message", 0).and_then(|nested| nested.find_int32("depth", 0)), Some(2));
+}
+
+#[test]
+fn empty_items_round_trip() {
+  let mut message = BMessage::new(0);
+  message.add("empty", BMessageValue::Raw(Vec::new())).unwrap();
+  message.add("nothing", BMessageValue::String(String::new())).unwrap();
+  message.add("nothing", BMessageValue::String(String::new())).unwrap();
+  assert_eq!(BMessage::unflatten(&message.flatten()).unwrap(), message);
+}
+
+#[test]
+fn reads_big_endian_messages() {
+  let nested = flattened(true, 0x6e65_7374, &[("depth", B_INT32_TYPE, true, vec![2i32.to_be_bytes().to_vec()])]);
+  let doubles = [1.0f64, -0.5].iter().flat_map(|value| value.to_be_bytes().to_vec()).collect();
+  let input = flattened(true, 0x7465_7374, &[
+    ("int16", B_INT16_TYPE, true, vec![(-1600i16).to_be_bytes().to_vec()]),
+    ("int32", B_INT32_TYPE, true, vec![(-5i32).to_be_bytes().to_vec(), 7i32.to_be_bytes().to_vec()]),
+    ("uint64", B_UINT64_TYPE, true, vec![(u64::MAX - 1).to_be_bytes().to_vec()]),
+    ("float", B_FLOAT_TYPE, true, vec![0.25f32.to_be_bytes().to_vec()]),
+    ("double", B_DOUBLE_TYPE, true, vec![(-1.0e100f64).to_be_bytes().to_vec()]),
+    ("point", B_POINT_TYPE, true, vec![[1.5f32.to_be_bytes(), (-2.0f32).to_be_bytes()].concat()]),
+    ("color", B_RGB_COLOR_TYPE, true, vec![vec![1, 2, 3, 4]]),
+    ("string", B_STRING_TYPE, false, vec![b"haiku\0".to_vec()]),
+    ("message", B_MESSAGE_TYPE, false, vec![nested]),
+    ("transformation", B_DOUBLE_TYPE, true, vec![doubles]),
+  ]);
+
+  let message = BMessage::unflatten(&input).unwrap();
+  assert_eq!(message.what, 0x7465_7374);
+  assert_eq!(message.find("int16", 0), Some(&BMessageValue::Int16(-1600)));
+  assert_eq!(message.find_int32("int32", 1), Some(7));
+  assert_eq!(message.find("uint64", 0), Some(&BMessageValue::UInt64(u64::MAX - 1)));
+  assert_eq!(message.find_float("float", 0), Some(0.25));
+  assert_eq!(message.find_double("double", 0), Some(-1.0e100));
+  assert_eq!(message.find_point("point", 0), Some((1.5, -2.0)));
+  assert_eq!(message.find_color("color", 0), Some([1, 2, 3, 4]));
+  assert_eq!(message.find_string("string", 0), Some("haiku"));
+  assert_eq!(message.find_message("message", 0).and_then(|nested| nested.find_int32("depth", 0)), Some(2));
+  // Numbers kept as data are brought into little endian order
+  let little: Vec<u8> = [1.0f64, -0.5].iter().flat_map(|value| value.to_le_bytes().to_vec()).collect();
+  assert_eq!(message.find_data("transformation", B_DOUBLE_TYPE, 0), Some(&little[..]));
+}
+
+#[test]
+fn rejects_malformed_and_cut_short_messages() {
+  assert_eq!(BMessage::unflatten(b"not a message").err(), Some(BMessageError::NotMessage));
+
+  // Packed items of no size cannot be counted
+  let empty_items = flattened(false, 0, &[("empty", B_RAW_TYPE, true, vec![Vec::new()])]);
+  assert!(matches!(BMessage::unflatten(&empty_items), Err(BMessageError::Malformed { .. })));
+  // Nor can a field be without items, packed or not
+  for &fixed_size in &[true, false] {
+    let no_items = flattened(false, 0, &[("none", B_STRING_TYPE, fixed_size, Vec::new())]);
+    assert!(matches!(BMessage::unflatten(&no_items), Err(BMessageError::Malformed { .. })));
+  }
+
+  let mut message = BMessage::new(0);
+  for (name, value) in every_value() {
+    message.add(name, value).unwrap();
+  }
+  let flat = message.flatten();
+  for cut in 4..flat.len() {
+    assert!(BMessage::unflatten(&flat[..cut]).is_err(), "cut at {}", cut);
+  }
+}
+
+#[test]
+fn fields_hold_values_of_one_type() {
+  let mut message = BMessage::new(0);
+  message.add("count", BMessageValue::Int32(1)).unwrap();
+  assert_eq!(message.add("count", BMessageValue::Float(1.0)),
+    Err(BMessageError::WrongType { field_type: B_INT32_TYPE, value_type: B_FLOAT_TYPE }));
+  assert_eq!(message.remove("count"), Some(vec![BMessageValue::Int32(1)]));
+  assert!(message.values("count").is_empty());
+}
@@ -0,0 +1,79 @@
+extern crate hvif_rs;
+
+use hvif_rs::IconBuilder;
+use hvif_rs::builder::*;
+use hvif_rs::types::*;
+
+fn triangle() -> HVIFPath {
+  HVIFPath { closed: true, points: vec![
+    HVIFPointCommand::Line { point: HVIFPoint { x: 8.0, y: 56.0 } },
+    HVIFPointCommand::Line { point: HVIFPoint { x: 32.0, y: 8.0 } },
+    HVIFPointCommand::Line { point: HVIFPoint { x: 56.0, y: 56.0 } },
+  ] }
+}
+
+#[test]
+fn builds_images_with_handles_for_indices() {
+  let mut builder = IconBuilder::new();
+  let red = builder.style(HVIFStyle::SolidColorNoAlpha { red: 255, green: 0, blue: 0 }).unwrap();
+  let gray = builder.style(HVIFStyle::SolidGrayNoAlpha { value: 128 }).unwrap();
+  let path = builder.path(triangle()).unwrap();
+  builder.shape(gray, &[path]).unwrap();
+  // Modifiers in the order the format stores them, so that they read back the same
+  builder.shape_with_modifiers(red, &[path, path], vec![
+    HVIFShapeModifier::HVIFHinting,
+    HVIFShapeModifier::HVIFTranslation(HVIFPoint { x: 1.0, y: 2.0 }),
+  ]).unwrap();
+  assert_eq!((red.index(), gray.index(), path.index()), (0, 1, 0));
+
+  let image = builder.build().unwrap();
+  assert_eq!((image.styles.len(), image.paths.len(), image.shapes.len()), (2, 1, 2));
+  assert_eq!((image.shapes[0].style_index, &image.shapes[0].path_indices), (1, &vec![0]));
+  assert_eq!((image.shapes[1].style_index, &image.shapes[1].path_indices), (0, &vec![0, 0]));
+  let bytes = hvif_rs::writer::hvif_image(&image).unwrap();
+  assert_eq!(format!("{:?}", hvif_rs::parse(&bytes).unwrap()), format!("{:?}", image));
+}
+
+#[test]
+fn refuses_handles_of_other_builders() {
+  let mut builder = IconBuilder::new();
+  let mut other = IconBuilder::new();
+  let style = builder.style(HVIFStyle::SolidGrayNoAlpha { value: 0 }).unwrap();
+  let path = builder.path(triangle()).unwrap();
+  let other_style = other.style(HVIFStyle::SolidGrayNoAlpha { value: 0 }).unwrap();
+  let other_path = other.path(triangle()).unwrap();
+  // The handles of the other builder have the same indices, but are not this builder's
+  assert_eq!((other_style.index(), other_path.index()), (style.index(), path.index()));
+  assert_eq!(builder.shape(other_style, &[path]), Err(HVIFBuildError::UnknownHandle));
+  assert_eq!(builder.shape(style, &[path, other_path]), Err(HVIFBuildError::UnknownHandle));
+  assert!(builder.build().unwrap().shapes.is_empty());
+}
+
+#[test]
+fn enforces_the_limits_of_the_format() {
+  let mut builder = IconBuilder::new();
+  let style = builder.style(HVIFStyle::SolidGrayNoAlpha { value: 0 }).unwrap();
+  for _ in 1..255 {
+    builder.style(HVIFStyle::SolidGrayNoAlpha { value: 1 }).unwrap();
+  }
+  assert_eq!(builder.style(HVIFStyle::SolidGrayNoAlpha { value: 2 }), Err(HVIFBuildError::TooMany { what: "styles" }));
+
+  let mut points = triangle();
+  points.points = points.points.into_iter().cycle().take(256).collect();
+  assert_eq!(builder.path(points), Err(HVIFBuildError::TooMany { what: "points in a path" }));
+
+  let path = builder.path(triangle()).unwrap();
+  assert_eq!(builder.shape(style, &[path; 256]), Err(HVIFBuildError::TooMany { what: "paths in a shape" }));
+  let conflicting = vec![
+    HVIFShapeModifier::HVIFTransformMatrix(HVIFMatrix::from([1.0, 0.0, 0.0, 1.0, 0.0, 0.0])),
+    HVIFShapeModifier::HVIFTranslation(HVIFPoint { x: 1.0, y: 2.0 }),
+  ];
+  assert_eq!(builder.shape_with_modifiers(style, &[path], conflicting), Err(HVIFBuildError::ConflictingModifiers));
+  let twice = vec![HVIFShapeModifier::HVIFHinting, HVIFShapeModifier::HVIFHinting];
+  assert_eq!(builder.shape_with_modifiers(style, &[path], twice), Err(HVIFBuildError::ConflictingModifiers));
+
+  // Styles left unused are only warned about, so the image still builds
+  builder.shape(style, &[path]).unwrap();
+  let image = builder.build().unwrap();
+  assert_eq!((image.styles.len(), image.shapes.len()), (255, 1));
+}
@@ -0,0 +1,13 @@
+use std::process::Command;
+
+const SAMPLE_JSON: &str = include_str!("../data/sample.json");
+
+#[test]
+fn dumps_images_as_json_in_the_documented_schema() {
+  let output = Command::new(env!("CARGO_BIN_EXE_hvif"))
+    .args(["dump", "--json", concat!(env!("CARGO_MANIFEST_DIR"), "/data/sample.hvif")])
+    .output()
+    .unwrap();
+  assert!(output.status.success());
+  assert_eq!(String::from_utf8(output.stdout).unwrap(), SAMPLE_JSON);
+}
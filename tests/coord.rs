@@ -0,0 +1,71 @@
+extern crate hvif_rs;
+
+use hvif_rs::parser::util::*;
+use hvif_rs::types::*;
+
+const BEE_ICON: &[u8] = include_bytes!("../data/beeicon.hvif");
+
+#[test]
+fn decodes_one_and_two_byte_coordinates() {
+  assert_eq!(hvif_coord_from_bytes(0x00, 0), -32.0);
+  assert_eq!(hvif_coord_from_bytes(0x20, 0), 0.0);
+  assert_eq!(hvif_coord_from_bytes(0x7f, 0), 95.0);
+  assert_eq!(hvif_coord_from_bytes(0x80, 0x00), -128.0);
+  // The high flag bit is not part of the value, and the first byte is the most significant
+  assert_eq!(hvif_coord_from_bytes(0xbc, 0x53), (0x3c53 as f32) / 102.0 - 128.0);
+}
+
+#[test]
+fn encodes_in_the_shortest_form() {
+  assert_eq!(hvif_coord_to_bytes(-32.0), (0x00, None));
+  assert_eq!(hvif_coord_to_bytes(95.0), (0x7f, None));
+  assert_eq!(hvif_coord_to_bytes(96.0), (0xd9, Some(0x40)));
+  assert_eq!(hvif_coord_to_bytes(-33.0), (0xa5, Some(0xda)));
+  assert_eq!(hvif_coord_to_bytes(0.5), (0xb3, Some(0x33)));
+  assert_eq!(hvif_coord_to_bytes(-1000.0), (0x80, Some(0x00)));
+  assert_eq!(hvif_coord_to_bytes(1000.0), (0xff, Some(0xff)));
+}
+
+#[test]
+fn every_coordinate_round_trips() {
+  for first in 0..0x80u8 {
+    let coord = hvif_coord_from_bytes(first, 0);
+    assert_eq!(hvif_coord_to_bytes(coord), (first, None));
+  }
+  for value in 0..0x8000u16 {
+    let (first, second) = (0x80 | (value >> 8) as u8, value as u8);
+    let coord = hvif_coord_from_bytes(first, second);
+    let (re_first, re_second) = hvif_coord_to_bytes(coord);
+    assert_eq!(hvif_coord_from_bytes(re_first, re_second.unwrap_or(0)), coord);
+  }
+}
+
+#[test]
+fn bee_icon_coordinates_match_the_reference() {
+  let image = hvif_rs::parse(BEE_ICON).unwrap();
+  let first_path: Vec<_> = image.paths[0].points.iter().map(|command| match *command {
+    HVIFPointCommand::Line { point } => (point.x, point.y),
+    HVIFPointCommand::Curve { point, .. } => (point.x, point.y),
+    HVIFPointCommand::HLine { x } => (x, f32::NAN),
+    HVIFPointCommand::VLine { y } => (f32::NAN, y),
+  }).collect();
+  assert_eq!(first_path[0], (11.0, 54.0));
+  assert_eq!(first_path[1], (35.0, 54.0));
+  assert_eq!(first_path[3], (28.0, 7.0));
+  assert_eq!(first_path[4].0, 10.0);
+
+  match image.paths[0].points[1] {
+    HVIFPointCommand::Curve { point_in, point_out, .. } => {
+      assert_eq!(point_in.x, (0x3c53 as f32) / 102.0 - 128.0);
+      assert_eq!(point_in.y, (0x4941 as f32) / 102.0 - 128.0);
+      assert_eq!((point_out.x, point_out.y), (60.0, 50.0));
+    },
+    other => panic!("expected a curve, found {:?}", other),
+  }
+}
+
+#[test]
+fn bee_icon_writes_back_byte_for_byte() {
+  let image = hvif_rs::parse(BEE_ICON).unwrap();
+  assert_eq!(hvif_rs::writer::hvif_image(&image).unwrap(), BEE_ICON);
+}
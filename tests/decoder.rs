@@ -0,0 +1,103 @@
+extern crate hvif_rs;
+
+use hvif_rs::error::HVIFError;
+use hvif_rs::parser::{HVIFDecoder, HVIFDecoded};
+
+const BEE_ICON: &[u8] = include_bytes!("../data/beeicon.hvif");
+
+/// The entries of an image in the order `parse` keeps them, as the decoder yields them
+fn entries(image: hvif_rs::types::HVIFImage) -> Vec<String> {
+  let styles = image.styles.into_iter().map(|style| format!("{:?}", HVIFDecoded::Style(style)));
+  let paths = image.paths.into_iter().map(|path| format!("{:?}", HVIFDecoded::Path(path)));
+  let shapes = image.shapes.into_iter().map(|shape| format!("{:?}", HVIFDecoded::Shape(shape)));
+  styles.chain(paths).chain(shapes).collect()
+}
+
+/// Feeds an image in chunks of the given sizes, taken in turn, decoding all it can after each
+fn decode_in_chunks(input: &[u8], sizes: &[usize]) -> (Vec<String>, HVIFDecoder) {
+  let mut decoder = HVIFDecoder::new();
+  let mut decoded = Vec::new();
+  let mut fed = 0;
+  for &size in sizes.iter().cycle() {
+    let end = (fed + size).min(input.len());
+    decoder.feed(&input[fed..end]);
+    fed = end;
+    loop {
+      match decoder.decode().unwrap() {
+        HVIFDecoded::NeedMore { .. } => break,
+        HVIFDecoded::Done => return (decoded, decoder),
+        entry => decoded.push(format!("{:?}", entry)),
+      }
+    }
+    assert!(fed < input.len(), "the whole image was fed without it being done");
+  }
+  unreachable!()
+}
+
+#[test]
+fn decodes_an_image_fed_a_byte_at_a_time() {
+  let expected = entries(hvif_rs::parse(BEE_ICON).unwrap());
+  assert_eq!(expected.len(), 7);
+
+  let mut decoder = HVIFDecoder::new();
+  let mut decoded = Vec::new();
+  // Where the bytes were fed up to when the decoder asked for more, and how many more it asked for
+  let mut asked: Vec<(usize, usize)> = Vec::new();
+  for (fed, byte) in BEE_ICON.iter().enumerate().map(|(index, byte)| (index + 1, byte)) {
+    decoder.feed(&[*byte]);
+    loop {
+      match decoder.decode().unwrap() {
+        HVIFDecoded::NeedMore { needed } => { asked.push((fed, needed)); break },
+        HVIFDecoded::Done => { asked.clear(); break },
+        entry => {
+          // No request since the last entry asked for more bytes than it took to decode this one
+          for &(at, needed) in &asked {
+            assert!(at + needed <= fed, "asked for {} bytes at {}, but {} was enough", needed, at, fed);
+          }
+          asked.clear();
+          decoded.push(format!("{:?}", entry));
+        },
+      }
+    }
+  }
+  assert_eq!(decoded, expected);
+  assert!(matches!(decoder.decode(), Ok(HVIFDecoded::Done)));
+  assert_eq!(decoder.offset(), BEE_ICON.len());
+  assert!(decoder.finish().is_ok());
+}
+
+#[test]
+fn decodes_an_image_fed_in_chunks() {
+  let expected = entries(hvif_rs::parse(BEE_ICON).unwrap());
+  for sizes in &[vec![BEE_ICON.len()], vec![2], vec![5, 1], vec![13, 3, 40]] {
+    let (decoded, _) = decode_in_chunks(BEE_ICON, sizes);
+    assert_eq!(decoded, expected, "chunks of {:?}", sizes);
+  }
+
+  // Bytes fed past the end of the image are left for whatever follows it
+  let mut input = BEE_ICON.to_vec();
+  input.extend_from_slice(b"next");
+  let (decoded, decoder) = decode_in_chunks(&input, &[7]);
+  assert_eq!(decoded, expected);
+  assert_eq!(decoder.offset(), BEE_ICON.len());
+  assert!(b"next".starts_with(decoder.buffered()));
+}
+
+#[test]
+fn finishing_an_image_cut_short_fails_as_parse_does() {
+  for &cut in &[0, 3, 6, 60, BEE_ICON.len() - 1] {
+    let mut decoder = HVIFDecoder::new();
+    decoder.feed(&BEE_ICON[..cut]);
+    let error = decoder.finish().unwrap_err();
+    assert!(matches!(error, HVIFError::Truncated { offset, needed: Some(_) } if offset == cut), "cut at {}: {:?}", cut, error);
+    assert_eq!(hvif_rs::parse(&BEE_ICON[..cut]).err(), Some(error), "cut at {}", cut);
+  }
+
+  // Once failed, the decoder keeps failing with the same error
+  let mut decoder = HVIFDecoder::new();
+  decoder.feed(b"nope");
+  let error = decoder.decode().unwrap_err();
+  decoder.feed(BEE_ICON);
+  assert_eq!(decoder.decode().unwrap_err(), error);
+  assert_eq!(decoder.finish(), Err(error));
+}
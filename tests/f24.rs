@@ -0,0 +1,47 @@
+extern crate hvif_rs;
+
+use hvif_rs::types::HVIFf24;
+
+fn f24(bits: u32) -> HVIFf24 {
+  HVIFf24 { fst: (bits >> 16) as u8, snd: (bits >> 8) as u8, thr: bits as u8 }
+}
+
+#[test]
+fn every_f24_round_trips_through_f32() {
+  for bits in 0..(1u32 << 24) {
+    let value = f32::from(f24(bits));
+    assert_eq!(HVIFf24::from(value), f24(bits), "f24 {:06x} decoded to {}", bits, value);
+  }
+}
+
+#[test]
+fn decodes_known_values() {
+  assert_eq!(f32::from(f24(0x000000)), 0.0);
+  assert_eq!(f32::from(f24(0x400000)), 1.0);
+  assert_eq!(f32::from(f24(0xc00000)), -1.0);
+  assert_eq!(f32::from(f24(0x3fffff)), 1.0 - 1.0 / 262144.0);
+  assert_eq!(f32::from(f24(0x420000)), 2.0);
+  assert_eq!(f32::from(f24(0x410000)), 1.5);
+}
+
+#[test]
+fn encodes_zero_and_out_of_range_values() {
+  assert_eq!(HVIFf24::from(0.0), f24(0x000000));
+  assert_eq!(HVIFf24::from(-0.0), f24(0x000000));
+  assert_eq!(HVIFf24::from(1.0e-20), f24(0x000000));
+  assert_eq!(HVIFf24::from(f32::NAN), f24(0x000000));
+  assert_eq!(HVIFf24::from(1.0e20), f24(0x7fffff));
+  assert_eq!(HVIFf24::from(-1.0e20), f24(0xffffff));
+  assert_eq!(HVIFf24::from(f32::INFINITY), f24(0x7fffff));
+}
+
+#[test]
+fn encoding_rounds_to_nearest() {
+  let step = 1.0 / 131072.0;
+  assert_eq!(HVIFf24::from(1.0 + step * 0.4), f24(0x400000));
+  assert_eq!(HVIFf24::from(1.0 + step * 0.6), f24(0x400001));
+  assert_eq!(HVIFf24::from(1.0 + step * 0.5), f24(0x400000));
+  assert_eq!(HVIFf24::from(1.0 + step * 1.5), f24(0x400002));
+  // Rounding up the largest mantissa carries into the exponent
+  assert_eq!(HVIFf24::from(2.0 - step * 0.25), f24(0x420000));
+}
@@ -0,0 +1,136 @@
+extern crate hvif_rs;
+
+use hvif_rs::hpkg::*;
+use hvif_rs::types::HVIF_VECTOR_ICON_TYPE;
+
+const BEE_ICON: &[u8] = include_bytes!("../data/beeicon.hvif");
+
+/// An attribute tag of the table of contents, as an unsigned LEB128 number, laid out as HPKG_ATTRIBUTE_TAG_COMPOSE does
+fn tag(out: &mut Vec<u8>, id: u64, value_type: u64, encoding: u64, has_children: bool) {
+  let mut tag = ((id << 6) | (value_type << 3) | (encoding << 1) | has_children as u64) + 1;
+  while tag >= 0x80 {
+    out.push((tag as u8 & 0x7f) | 0x80);
+    tag >>= 7;
+  }
+  out.push(tag as u8);
+}
+
+/// A table of contents holding `apps/Bee`, whose icon attribute holds an icon, and its string table
+fn toc(icon: &[u8]) -> (Vec<u8>, u64) {
+  // One shared string, then the empty string ending the table
+  let mut toc = b"BEOS:ICON\0\0".to_vec();
+  let strings_length = toc.len() as u64;
+  tag(&mut toc, 0, 3, 0, true);
+  toc.extend_from_slice(b"apps\0");
+  tag(&mut toc, 0, 3, 0, true);
+  toc.extend_from_slice(b"Bee\0");
+  tag(&mut toc, 11, 3, 1, true);
+  toc.push(0);
+  tag(&mut toc, 12, 2, 2, false);
+  toc.extend_from_slice(&HVIF_VECTOR_ICON_TYPE.to_be_bytes());
+  tag(&mut toc, 13, 4, 0, false);
+  toc.push(icon.len() as u8);
+  toc.extend_from_slice(icon);
+  toc.extend_from_slice(&[0, 0, 0, 0]);
+  (toc, strings_length)
+}
+
+/// A package with the given heap, after filling in the header
+fn package(compression: u16, chunk_size: u32, heap: &[u8], uncompressed_size: u64, toc_length: u64, strings_length: u64)
+  -> Vec<u8>
+{
+  let mut out = Vec::new();
+  out.extend_from_slice(b"hpkg");
+  out.extend_from_slice(&80u16.to_be_bytes());
+  out.extend_from_slice(&2u16.to_be_bytes());
+  out.extend_from_slice(&(80 + heap.len() as u64).to_be_bytes());
+  out.extend_from_slice(&0u16.to_be_bytes());
+  out.extend_from_slice(&compression.to_be_bytes());
+  out.extend_from_slice(&chunk_size.to_be_bytes());
+  out.extend_from_slice(&(heap.len() as u64).to_be_bytes());
+  out.extend_from_slice(&uncompressed_size.to_be_bytes());
+  // No package attributes, nor strings for them
+  out.extend_from_slice(&[0; 16]);
+  out.extend_from_slice(&toc_length.to_be_bytes());
+  out.extend_from_slice(&strings_length.to_be_bytes());
+  out.extend_from_slice(&1u64.to_be_bytes());
+  out.extend_from_slice(heap);
+  out
+}
+
+#[test]
+fn reads_icons_from_an_uncompressed_heap() {
+  let (toc, strings_length) = toc(BEE_ICON);
+  let input = package(0, 0x10000, &toc, toc.len() as u64, toc.len() as u64, strings_length);
+  let icons = package_icons(&input).unwrap();
+  assert_eq!(icons.len(), 1);
+  assert_eq!(icons[0].path, "apps/Bee");
+  assert_eq!(format!("{:?}", icons[0].image), format!("{:?}", hvif_rs::parse(BEE_ICON).unwrap()));
+}
+
+#[test]
+fn reads_tags_as_haiku_lays_them_out() {
+  // The tags as HPKG_ATTRIBUTE_TAG_COMPOSE gives them, with names inline rather than in the string table:
+  // a directory entry is 0x1a, a file attribute (id 11) 730, its type (id 12) 789, and its data (id 13) 865
+  let mut toc = b"\0".to_vec();
+  toc.extend_from_slice(b"\x1aapps\0\x1aBee\0");
+  toc.extend_from_slice(b"\xda\x05BEOS:ICON\0");
+  toc.extend_from_slice(b"\x95\x06");
+  toc.extend_from_slice(&HVIF_VECTOR_ICON_TYPE.to_be_bytes());
+  toc.extend_from_slice(b"\xe1\x06");
+  toc.push(BEE_ICON.len() as u8);
+  toc.extend_from_slice(BEE_ICON);
+  toc.extend_from_slice(&[0, 0, 0, 0]);
+  let input = package(0, 0x10000, &toc, toc.len() as u64, toc.len() as u64, 1);
+  let icons = package_icons(&input).unwrap();
+  assert_eq!(icons.iter().map(|icon| icon.path.as_str()).collect::<Vec<_>>(), vec!["apps/Bee"]);
+}
+
+#[test]
+fn reads_icons_from_chunks_of_a_compressed_heap() {
+  // Chunks that compression would not shrink are stored as they are, followed by the table of chunk sizes
+  let (mut heap, strings_length) = toc(BEE_ICON);
+  let length = heap.len() as u64;
+  let chunk_size = 64;
+  for _ in 0..(heap.len() - 1) / chunk_size {
+    heap.extend_from_slice(&(chunk_size as u16 - 1).to_be_bytes());
+  }
+  let input = package(1, chunk_size as u32, &heap, length, length, strings_length);
+  let icons = package_icons(&input).unwrap();
+  assert_eq!(icons.iter().map(|icon| icon.path.as_str()).collect::<Vec<_>>(), vec!["apps/Bee"]);
+}
+
+#[test]
+fn rejects_malformed_packages() {
+  let (toc, strings_length) = toc(BEE_ICON);
+  let length = toc.len() as u64;
+  assert_eq!(package_icons(b"not a package").err(), Some(HVIFPackageError::NotPackage));
+
+  // A heap claiming far more chunks than it can hold
+  let input = package(1, 1, &toc, 1 << 62, length, strings_length);
+  assert!(matches!(package_icons(&input), Err(HVIFPackageError::Truncated { .. })));
+  let input = package(0, 1, &toc, 1 << 62, length, strings_length);
+  assert!(matches!(package_icons(&input), Err(HVIFPackageError::Truncated { .. })));
+
+  // Directories nested past any real tree
+  let mut deep = b"\0".to_vec();
+  for _ in 0..1000 {
+    tag(&mut deep, 0, 3, 0, true);
+    deep.extend_from_slice(b"d\0");
+  }
+  deep.extend_from_slice(&[0; 1001]);
+  let input = package(0, 0x10000, &deep, deep.len() as u64, deep.len() as u64, 1);
+  assert!(matches!(package_icons(&input), Err(HVIFPackageError::MalformedTOC { .. })));
+
+  let mut broken = BEE_ICON.to_vec();
+  broken[4] = 0xff;
+  let (toc, strings_length) = self::toc(&broken[..100]);
+  let input = package(0, 0x10000, &toc, toc.len() as u64, toc.len() as u64, strings_length);
+  assert!(matches!(package_icons(&input), Err(HVIFPackageError::Icon { ref path, .. }) if path == "apps/Bee"));
+
+  let (toc, strings_length) = self::toc(BEE_ICON);
+  let input = package(0, 0x10000, &toc, length, length, strings_length);
+  for cut in 0..input.len() {
+    assert!(package_icons(&input[..cut]).is_err(), "cut at {}", cut);
+  }
+}
@@ -0,0 +1,172 @@
+extern crate hvif_rs;
+
+use hvif_rs::bmessage::*;
+use hvif_rs::imsg::{self, IMSGError};
+use hvif_rs::types::*;
+
+const BEE_ICON: &[u8] = include_bytes!("../data/beeicon.hvif");
+
+fn point(x: f32, y: f32) -> HVIFPoint {
+  HVIFPoint { x, y }
+}
+
+fn add(message: &mut BMessage, name: &str, value: BMessageValue) {
+  message.add(name, value).unwrap();
+}
+
+fn vertices(image: &HVIFImage) -> Vec<(bool, String)> {
+  image.paths.iter().map(|path| (path.closed, format!("{:?}", path.vertices()))).collect()
+}
+
+#[test]
+fn bee_icon_round_trips() {
+  let image = hvif_rs::parse(BEE_ICON).unwrap();
+  let read = imsg::parse(&imsg::imsg_image(&image)).unwrap();
+  assert_eq!(format!("{:?}", read.styles), format!("{:?}", image.styles));
+  assert_eq!(format!("{:?}", read.shapes), format!("{:?}", image.shapes));
+  // Lines written as commands come back as plain lines, through the same vertices
+  assert_eq!(vertices(&read), vertices(&image));
+}
+
+fn sample_image() -> HVIFImage {
+  let matrix = HVIFMatrix::from([0.5, 0.25, -0.25, 0.5, 12.0, -4.0]);
+  HVIFImage {
+    styles: vec![
+      HVIFStyle::SolidColor { red: 10, green: 20, blue: 30, alpha: 128 },
+      HVIFStyle::Gradient(HVIFGradient {
+        gradient_type: HVIFGradientType::Conic,
+        transform: Some(matrix),
+        colors: vec![
+          HVIFGradientColor { stop_offset: 0, alpha: 255, red: 255, green: 128, blue: 0 },
+          HVIFGradientColor { stop_offset: 77, alpha: 10, red: 0, green: 0, blue: 0 },
+          HVIFGradientColor { stop_offset: 255, alpha: 200, red: 1, green: 2, blue: 3 },
+        ],
+      }),
+    ],
+    paths: vec![
+      HVIFPath { closed: true, points: vec![
+        HVIFPointCommand::Line { point: point(2.5, 3.0) },
+        HVIFPointCommand::Curve { point_in: point(40.0, 2.0), point: point(44.0, 6.0), point_out: point(48.0, 10.0) },
+        HVIFPointCommand::Line { point: point(-10.125, 70.0) },
+      ] },
+      HVIFPath { closed: false, points: vec![HVIFPointCommand::Line { point: point(0.0, 0.0) }] },
+    ],
+    shapes: vec![
+      HVIFShape { style_index: 1, path_indices: vec![0, 1], modifiers: vec![
+        HVIFShapeModifier::HVIFHinting,
+        HVIFShapeModifier::HVIFTransformMatrix(matrix),
+        HVIFShapeModifier::HVIFLODScale { min: 0.25, max: 1.5 },
+        HVIFShapeModifier::HVIFTransformerList(vec![
+          HVIFTransformer::Stroke { width: 2.5, line_join: HVIFLineJoin::Round, line_cap: HVIFLineCap::Square, miter_limit: 4 },
+          HVIFTransformer::Contour { width: -1.0, line_join: HVIFLineJoin::Bevel, miter_limit: 10 },
+          HVIFTransformer::Affine(matrix),
+          HVIFTransformer::Perspective,
+        ]),
+      ] },
+      HVIFShape { style_index: 0, path_indices: vec![1], modifiers: vec![
+        HVIFShapeModifier::HVIFTranslation(point(3.0, -2.0)),
+      ] },
+    ],
+    names: HVIFNames {
+      styles: vec!["Shadow".to_string(), "Glow".to_string()],
+      paths: vec!["Outline".to_string(), String::new()],
+      shapes: vec!["Body".to_string(), "Dot".to_string()],
+    },
+  }
+
+}
+
+#[test]
+fn names_gradients_and_transformers_round_trip() {
+  let image = sample_image();
+  let read = imsg::parse(&imsg::imsg_image(&image)).unwrap();
+  assert_eq!(format!("{:?}", read.styles), format!("{:?}", image.styles));
+  assert_eq!(format!("{:?}", read.paths), format!("{:?}", image.paths));
+  assert_eq!(format!("{:?}", read.shapes), format!("{:?}", image.shapes));
+  assert_eq!(read.names, image.names);
+}
+
+#[test]
+fn rejects_other_and_cut_short_documents() {
+  assert_eq!(imsg::parse(BEE_ICON).err(), Some(IMSGError::NotIMSG));
+  let document = imsg::imsg_image(&hvif_rs::parse(BEE_ICON).unwrap());
+  match imsg::parse(&document[..document.len() / 2]) {
+    Err(IMSGError::Malformed { offset }) => assert!(offset < document.len()),
+    other => panic!("expected a malformed document, got {:?}", other),
+  }
+}
+
+#[test]
+fn reads_gradients_archived_as_haiku_archives_them() {
+  // Gradient::Archive adds the transformation, then each stop's rgb_color as an int32 with its offset
+  let mut gradient = BMessage::new(0);
+  let identity = [1.0f64, 0.0, 0.0, 1.0, 0.0, 0.0].iter().flat_map(|value| value.to_le_bytes().to_vec()).collect();
+  add(&mut gradient, "transformation", BMessageValue::Data { type_code: B_DOUBLE_TYPE, data: identity });
+  add(&mut gradient, "color", BMessageValue::Int32(i32::from_le_bytes([255, 128, 0, 255])));
+  add(&mut gradient, "offset", BMessageValue::Float(0.0));
+  add(&mut gradient, "color", BMessageValue::Int32(i32::from_le_bytes([0, 0, 255, 64])));
+  add(&mut gradient, "offset", BMessageValue::Float(1.0));
+  add(&mut gradient, "type", BMessageValue::Int32(1));
+  add(&mut gradient, "interpolation", BMessageValue::Int32(1));
+  add(&mut gradient, "inherit transformation", BMessageValue::Bool(true));
+
+  // Style::Archive keeps its own color as an rgb_color
+  let mut style = BMessage::new(0);
+  add(&mut style, "name", BMessageValue::String("Sky".to_string()));
+  add(&mut style, "color", BMessageValue::Color { red: 255, green: 128, blue: 0, alpha: 255 });
+  add(&mut style, "gradient", BMessageValue::Message(gradient));
+  let mut styles = BMessage::new(0);
+  add(&mut styles, "style", BMessageValue::Message(style));
+  let mut archive = BMessage::new(0);
+  add(&mut archive, "styles", BMessageValue::Message(styles));
+
+  let mut document = b"IMSG".to_vec();
+  document.extend_from_slice(&archive.flatten());
+  let image = imsg::parse(&document).unwrap();
+  assert_eq!(image.names.styles, vec!["Sky".to_string()]);
+  let expected = HVIFStyle::Gradient(HVIFGradient {
+    gradient_type: HVIFGradientType::Circular,
+    transform: None,
+    colors: vec![
+      HVIFGradientColor { stop_offset: 0, alpha: 255, red: 255, green: 128, blue: 0 },
+      HVIFGradientColor { stop_offset: 255, alpha: 64, red: 0, green: 0, blue: 255 },
+    ],
+  });
+  assert_eq!(format!("{:?}", image.styles), format!("{:?}", vec![expected]));
+}
+
+/// Checks the type of each named field against the type Icon-O-Matic archives it with
+fn assert_types(message: &BMessage, expected: &[(&str, u32)]) {
+  for &(name, type_code) in expected {
+    let field = message.fields().find(|&(field, _, _)| field == name);
+    assert_eq!(field.map(|(_, field_type, _)| field_type), Some(type_code), "field {}", name);
+  }
+}
+
+#[test]
+fn archives_fields_with_the_types_icon_o_matic_reads() {
+  let document = imsg::imsg_image(&sample_image());
+  let archive = BMessage::unflatten(&document[4..]).unwrap();
+  let entry = |list: &str, name: &str, index: usize| archive.find_message(list, 0).unwrap().find_message(name, index).unwrap().clone();
+
+  assert_types(&entry("paths", "path", 0), &[
+    ("name", B_STRING_TYPE), ("point", B_POINT_TYPE), ("point in", B_POINT_TYPE), ("point out", B_POINT_TYPE),
+    ("connected", B_BOOL_TYPE), ("path closed", B_BOOL_TYPE),
+  ]);
+  let style = entry("styles", "style", 1);
+  assert_types(&style, &[("name", B_STRING_TYPE), ("color", B_RGB_COLOR_TYPE), ("gradient", B_MESSAGE_TYPE)]);
+  // Gradient::Unarchive finds each stop's color with FindInt32
+  assert_types(style.find_message("gradient", 0).unwrap(), &[
+    ("transformation", B_DOUBLE_TYPE), ("color", B_INT32_TYPE), ("offset", B_FLOAT_TYPE), ("type", B_INT32_TYPE),
+    ("interpolation", B_INT32_TYPE), ("inherit transformation", B_BOOL_TYPE),
+  ]);
+  let shape = entry("shapes", "shape", 0);
+  assert_types(&shape, &[
+    ("name", B_STRING_TYPE), ("transformation", B_DOUBLE_TYPE), ("hinting", B_BOOL_TYPE),
+    ("min visibility scale", B_FLOAT_TYPE), ("max visibility scale", B_FLOAT_TYPE),
+    ("transformer", B_MESSAGE_TYPE), ("path ref", B_INT32_TYPE), ("style ref", B_INT32_TYPE),
+  ]);
+  assert_types(shape.find_message("transformer", 0).unwrap(), &[
+    ("width", B_DOUBLE_TYPE), ("line cap", B_INT32_TYPE), ("line join", B_INT32_TYPE), ("miter limit", B_DOUBLE_TYPE),
+  ]);
+}
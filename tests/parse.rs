@@ -0,0 +1,95 @@
+extern crate hvif_rs;
+
+use hvif_rs::error::{HVIFError, HVIFIndexKind};
+use hvif_rs::types::*;
+
+/// Two styles, a gray and a circular gradient, a closed path of two points,
+/// and a shape stroking the path with the gradient
+const IMAGE: [u8; 33] = [
+  b'n', b'c', b'i', b'f',
+  // The styles: a gray, then a gradient of one color without alpha
+  2, 5, 7, 2, 1, 0x04, 1, 0, 1, 2, 3,
+  // The path, closed with no curves
+  1, 0x0a, 2, 0x21, 0x22, 0x23, 0x24,
+  // The shape, with one stroke transformer, its square cap and round join sharing a byte
+  1, 0x0a, 1, 1, 0, 0x10, 1, 23, 0x81, 0x12, 4,
+];
+
+fn with(at: usize, byte: u8) -> Vec<u8> {
+  let mut input = IMAGE.to_vec();
+  input[at] = byte;
+  input
+}
+
+#[test]
+fn reports_each_error_where_it_occurs() {
+  assert!(hvif_rs::parse(&IMAGE).is_ok());
+  let cases = [
+    (0, b'N', HVIFError::BadMagic { offset: 0 }),
+    (5, 9, HVIFError::UnknownStyleType { offset: 5, style_type: 9 }),
+    (8, 6, HVIFError::UnknownGradientType { offset: 8, gradient_type: 6 }),
+    (23, 0x0b, HVIFError::BadShapeType { offset: 23, shape_type: 0x0b }),
+    (24, 2, HVIFError::IndexOutOfRange { offset: 24, kind: HVIFIndexKind::Style, index: 2, count: 2 }),
+    (26, 1, HVIFError::IndexOutOfRange { offset: 26, kind: HVIFIndexKind::Path, index: 1, count: 1 }),
+    (29, 24, HVIFError::UnknownTransformer { offset: 29, transformer_type: 24 }),
+    (31, 0x15, HVIFError::UnknownLineJoin { offset: 31, line_join: 5 }),
+    (31, 0x32, HVIFError::UnknownLineCap { offset: 31, line_cap: 3 }),
+  ];
+  for &(at, byte, error) in &cases {
+    assert_eq!(hvif_rs::parse(&with(at, byte)).err(), Some(error));
+    assert_eq!(error.offset(), at);
+  }
+  assert_eq!(cases[4].2.to_string(), "style index 2 at byte 24 is out of range, the image has 2 styles");
+  let malformed = HVIFError::Malformed { offset: 12 };
+  assert_eq!((malformed.offset(), malformed.to_string()), (12, "malformed data at byte 12".to_string()));
+}
+
+#[test]
+fn says_how_many_more_bytes_a_cut_short_image_needs() {
+  // The magic number is read whole, and everything after it in this image a byte at a time
+  assert_eq!(hvif_rs::parse(&IMAGE[..1]).err(), Some(HVIFError::Truncated { offset: 1, needed: Some(3) }));
+  for cut in 4..IMAGE.len() {
+    assert_eq!(hvif_rs::parse(&IMAGE[..cut]).err(), Some(HVIFError::Truncated { offset: cut, needed: Some(1) }));
+  }
+  assert_eq!(hvif_rs::parse(&[]).unwrap_err().to_string(), "image truncated at byte 0, 4 more bytes needed");
+}
+
+#[test]
+fn reads_gradient_matrices_after_the_stop_count_and_16_bit_colors() {
+  let mut input = b"ncif".to_vec();
+  // A linear gradient with a transformation and 16 bit colors, holding two stops
+  input.extend_from_slice(&[1, 2, 0, 0x0a, 2]);
+  // The matrix [2, 0, 0, 0.5, 16, 8] as 24 bit floats
+  input.extend_from_slice(&[0x42, 0, 0, 0, 0, 0, 0, 0, 0, 0x3e, 0, 0, 0x48, 0, 0, 0x46, 0, 0]);
+  input.extend_from_slice(&[0, 0x12, 0xff, 0xab, 0x00, 0x00, 0x00, 0xff, 0xff]);
+  input.extend_from_slice(&[255, 0x80, 0x01, 0x01, 0x02, 0x7f, 0x7f, 0x40, 0x00]);
+  input.extend_from_slice(&[0, 0]);
+
+  let image = hvif_rs::parse(&input).unwrap();
+  let gradient = match image.styles[0] {
+    HVIFStyle::Gradient(ref gradient) => gradient,
+    ref style => panic!("not a gradient: {:?}", style),
+  };
+  let matrix: [f32; 6] = gradient.transform.unwrap().into();
+  assert_eq!(matrix, [2.0, 0.0, 0.0, 0.5, 16.0, 8.0]);
+  // Only the high byte of each 16 bit channel is kept
+  let colors: Vec<_> = gradient.colors.iter().map(|color| (color.stop_offset, color.red, color.green, color.blue, color.alpha)).collect();
+  assert_eq!(colors, vec![(0, 0x12, 0xab, 0x00, 0xff), (255, 0x80, 0x01, 0x7f, 0x40)]);
+
+  // A 16 bit channel is read whole
+  assert_eq!(hvif_rs::parse(&input[..28]).err(), Some(HVIFError::Truncated { offset: 28, needed: Some(2) }));
+}
+
+#[test]
+fn reads_the_line_cap_of_a_stroke_from_the_high_four_bits() {
+  let stroke = |input: &[u8]| match hvif_rs::parse(input).unwrap().shapes[0].modifiers[..] {
+    [HVIFShapeModifier::HVIFTransformerList(ref transformers)] => match transformers[..] {
+      [HVIFTransformer::Stroke { line_join, line_cap, .. }] => (line_join, line_cap),
+      ref other => panic!("not a stroke: {:?}", other),
+    },
+    ref other => panic!("not a transformer list: {:?}", other),
+  };
+  assert_eq!(stroke(&IMAGE), (HVIFLineJoin::Round, HVIFLineCap::Square));
+  assert_eq!(stroke(&with(31, 0x21)), (HVIFLineJoin::MiterRevert, HVIFLineCap::Round));
+  assert_eq!(stroke(&with(31, 0x04)), (HVIFLineJoin::MiterRound, HVIFLineCap::Butt));
+}
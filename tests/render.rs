@@ -0,0 +1,98 @@
+extern crate hvif_rs;
+
+use hvif_rs::render::*;
+use hvif_rs::types::*;
+
+/// A closed path through the corners of a rectangle, in the order given
+fn polygon(corners: &[(f32, f32)]) -> HVIFPath {
+  HVIFPath { closed: true, points: corners.iter().map(|&(x, y)| HVIFPointCommand::Line { point: HVIFPoint { x, y } }).collect() }
+}
+
+fn square(left: f32, top: f32, right: f32, bottom: f32, clockwise: bool) -> HVIFPath {
+  match clockwise {
+    true  => polygon(&[(left, top), (right, top), (right, bottom), (left, bottom)]),
+    false => polygon(&[(left, top), (left, bottom), (right, bottom), (right, top)]),
+  }
+}
+
+fn image(styles: Vec<HVIFStyle>, paths: Vec<HVIFPath>, shapes: Vec<HVIFShape>) -> HVIFImage {
+  HVIFImage { styles, paths, shapes, names: HVIFNames::default() }
+}
+
+fn pixel(bitmap: &HVIFBitmap, x: u32, y: u32) -> [u8; 4] {
+  let at = ((y * bitmap.width + x) * 4) as usize;
+  [bitmap.pixels[at], bitmap.pixels[at + 1], bitmap.pixels[at + 2], bitmap.pixels[at + 3]]
+}
+
+#[test]
+fn fills_shapes_with_premultiplied_solid_colors() {
+  let image = image(
+    vec![HVIFStyle::SolidColor { red: 255, green: 0, blue: 0, alpha: 128 }, HVIFStyle::SolidGrayNoAlpha { value: 255 }],
+    vec![square(16.0, 16.0, 48.0, 48.0, true), square(40.0, 40.0, 56.0, 56.0, true)],
+    vec![HVIFShape { style_index: 0, path_indices: vec![0], modifiers: Vec::new() },
+         HVIFShape { style_index: 1, path_indices: vec![1], modifiers: Vec::new() }],
+  );
+  let bitmap = render(&image, 64);
+  assert_eq!((bitmap.width, bitmap.height, bitmap.pixels.len()), (64, 64, 64 * 64 * 4));
+  assert_eq!(pixel(&bitmap, 0, 0), [0, 0, 0, 0]);
+  assert_eq!(pixel(&bitmap, 16, 16), [128, 0, 0, 128]);
+  assert_eq!(pixel(&bitmap, 30, 20), [128, 0, 0, 128]);
+  assert_eq!(pixel(&bitmap, 48, 30), [0, 0, 0, 0]);
+  // Later shapes cover earlier ones
+  assert_eq!(pixel(&bitmap, 44, 44), [255, 255, 255, 255]);
+
+  // Pixels an edge runs through are covered in proportion
+  let image = HVIFImage { paths: vec![square(16.5, 16.0, 48.0, 48.0, true)], ..image };
+  let bitmap = render(&image, 64);
+  assert_eq!(pixel(&bitmap, 16, 20), [64, 0, 0, 64]);
+}
+
+#[test]
+fn fills_overlapping_paths_with_the_non_zero_rule() {
+  let shape = |path_indices: Vec<u8>| HVIFShape { style_index: 0, path_indices, modifiers: Vec::new() };
+  let image = image(
+    vec![HVIFStyle::SolidGrayNoAlpha { value: 255 }],
+    vec![square(8.0, 8.0, 56.0, 56.0, true), square(24.0, 24.0, 40.0, 40.0, true), square(24.0, 24.0, 40.0, 40.0, false)],
+    vec![shape(vec![0, 1])],
+  );
+  // Paths winding the same way add up, so where they overlap stays filled
+  let bitmap = render(&image, 64);
+  assert_eq!(pixel(&bitmap, 32, 32), [255, 255, 255, 255]);
+  assert_eq!(pixel(&bitmap, 12, 12), [255, 255, 255, 255]);
+
+  // A path winding the other way cancels out the one around it, leaving a hole
+  let image = HVIFImage { shapes: vec![shape(vec![0, 2])], ..image };
+  let bitmap = render(&image, 64);
+  assert_eq!(pixel(&bitmap, 32, 32), [0, 0, 0, 0]);
+  assert_eq!(pixel(&bitmap, 12, 12), [255, 255, 255, 255]);
+}
+
+#[test]
+fn shows_shapes_at_the_edges_of_their_level_of_detail_range() {
+  let lod = |style_index: u8, path_index: u8, min: f32, max: f32| HVIFShape {
+    style_index, path_indices: vec![path_index], modifiers: vec![HVIFShapeModifier::HVIFLODScale { min, max }],
+  };
+  let image = image(
+    vec![HVIFStyle::SolidGrayNoAlpha { value: 0 }, HVIFStyle::SolidGrayNoAlpha { value: 1 }],
+    vec![square(24.0, 24.0, 40.0, 40.0, true), square(8.0, 8.0, 56.0, 56.0, true), square(30.0, 0.0, 64.0, 34.0, true)],
+    vec![HVIFShape { style_index: 0, path_indices: vec![0], modifiers: Vec::new() }, lod(0, 1, 0.5, 1.0), lod(1, 2, 1.0, 2.0)],
+  );
+  let visible = |scale: f32| image.shapes_visible_at(scale).map(|shape| shape.path_indices[0]).collect::<Vec<_>>();
+  let bounds = |scale: f32| bounds_at(&image, scale).map(|b| (b.left, b.top, b.right, b.bottom));
+
+  // Both ends of a range are inside it
+  assert_eq!(visible(0.49), vec![0]);
+  assert_eq!(bounds(0.49), Some((24.0, 24.0, 40.0, 40.0)));
+  assert_eq!(visible(0.5), vec![0, 1]);
+  assert_eq!(bounds(0.5), Some((8.0, 8.0, 56.0, 56.0)));
+  assert_eq!(visible(1.0), vec![0, 1, 2]);
+  assert_eq!(bounds(1.0), Some((8.0, 0.0, 64.0, 56.0)));
+  assert_eq!(visible(2.0), vec![0, 2]);
+  assert_eq!(bounds(2.0), Some((24.0, 0.0, 64.0, 40.0)));
+  assert_eq!(visible(2.01), vec![0]);
+
+  // Nothing drawn has no bounds
+  let image = HVIFImage { shapes: vec![lod(0, 1, 0.5, 1.0)], ..image };
+  assert_eq!(bounds_at(&image, 0.25), None);
+  assert!(bounds_at(&image, 0.75).is_some());
+}
@@ -0,0 +1,113 @@
+extern crate hvif_rs;
+
+use hvif_rs::resources::*;
+use hvif_rs::types::HVIF_VECTOR_ICON_TYPE;
+
+const BEE_ICON: &[u8] = include_bytes!("../data/beeicon.hvif");
+
+/// Lays out a resource file in either byte order, holding resources of one type as (id, name, data)
+fn resource_file(big_endian: bool, type_code: u32, resources: &[(i32, &str, &[u8])]) -> Vec<u8> {
+  let u32_bytes = |value: u32| if big_endian { value.to_be_bytes() } else { value.to_le_bytes() };
+  let index_offset = 68;
+  let entries_offset = index_offset + 132;
+  let info_offset = entries_offset + 12 * resources.len();
+
+  let mut info = u32_bytes(type_code).to_vec();
+  for (index, &(id, name, _)) in resources.iter().enumerate() {
+    info.extend_from_slice(&u32_bytes(id as u32));
+    info.extend_from_slice(&u32_bytes(index as u32 + 1));
+    let name_size = name.len() as u16 + 1;
+    info.extend_from_slice(&if big_endian { name_size.to_be_bytes() } else { name_size.to_le_bytes() });
+    info.extend_from_slice(name.as_bytes());
+    info.push(0);
+  }
+  // The separator ending the block of this type, then the end of the table
+  info.extend_from_slice(&[0xff; 8]);
+  info.extend_from_slice(&[0; 8]);
+
+  let mut out = u32_bytes(0x444f_1000).to_vec();
+  out.extend_from_slice(&u32_bytes(resources.len() as u32));
+  out.extend_from_slice(&u32_bytes(index_offset as u32));
+  out.resize(index_offset + 120, 0);
+  out.extend_from_slice(&u32_bytes(info_offset as u32));
+  out.extend_from_slice(&u32_bytes(info.len() as u32));
+  out.resize(entries_offset, 0);
+  let mut data_offset = info_offset + info.len();
+  for &(_, _, data) in resources {
+    out.extend_from_slice(&u32_bytes(data_offset as u32));
+    out.extend_from_slice(&u32_bytes(data.len() as u32));
+    out.extend_from_slice(&[0; 4]);
+    data_offset += data.len();
+  }
+  out.extend_from_slice(&info);
+  for &(_, _, data) in resources {
+    out.extend_from_slice(data);
+  }
+  out
+}
+
+/// A little-endian 64 bit ELF binary whose only segment covers its first bytes, with no sections
+fn elf(program_offset: u64, segment_size: u64) -> Vec<u8> {
+  let mut out = b"\x7fELF\x02\x01\x01".to_vec();
+  out.resize(32, 0);
+  out.extend_from_slice(&program_offset.to_le_bytes());
+  out.extend_from_slice(&0u64.to_le_bytes());
+  out.resize(54, 0);
+  out.extend_from_slice(&56u16.to_le_bytes());
+  out.extend_from_slice(&1u16.to_le_bytes());
+  out.extend_from_slice(&64u16.to_le_bytes());
+  out.extend_from_slice(&0u16.to_le_bytes());
+  out.resize(64 + 8, 0);
+  out.extend_from_slice(&0u64.to_le_bytes());
+  out.resize(64 + 32, 0);
+  out.extend_from_slice(&segment_size.to_le_bytes());
+  out.resize(segment_size as usize, 0);
+  out
+}
+
+#[test]
+fn reads_resource_files_in_either_byte_order() {
+  let resources: [(i32, &str, &[u8]); 2] = [(101, "BEOS:ICON", BEE_ICON), (7, "", b"x")];
+  for &big_endian in &[false, true] {
+    let input = resource_file(big_endian, HVIF_VECTOR_ICON_TYPE, &resources);
+    let read = parse_resources(&input).unwrap();
+    assert_eq!(read.len(), 2);
+    assert_eq!((read[0].type_code, read[0].id, read[0].name.as_str(), read[0].data), (HVIF_VECTOR_ICON_TYPE, 101, "BEOS:ICON", BEE_ICON));
+    assert_eq!((read[1].id, read[1].name.as_str(), read[1].data), (7, "", &b"x"[..]));
+  }
+
+  let input = resource_file(false, HVIF_VECTOR_ICON_TYPE, &resources[..1]);
+  let icons = resource_icons(&input).unwrap();
+  assert_eq!(format!("{:?}", icons[0].1), format!("{:?}", hvif_rs::parse(BEE_ICON).unwrap()));
+  let input = resource_file(false, HVIF_VECTOR_ICON_TYPE, &resources);
+  assert!(matches!(resource_icons(&input), Err(HVIFResourceError::Icon { id: 7, .. })));
+}
+
+#[test]
+fn finds_resources_appended_to_binaries() {
+  let resources = resource_file(false, HVIF_VECTOR_ICON_TYPE, &[(101, "BEOS:ICON", BEE_ICON)]);
+  // Resources start at the next multiple of 32 bytes after the segment
+  let mut input = elf(64, 200);
+  input.resize(224, 0);
+  input.extend_from_slice(&resources);
+  let read = parse_resources(&input).unwrap();
+  assert_eq!((read[0].id, read[0].data), (101, BEE_ICON));
+
+  let input = elf(64, 200);
+  assert_eq!(parse_resources(&input), Err(HVIFResourceError::NotResources));
+}
+
+#[test]
+fn rejects_malformed_resources() {
+  assert_eq!(parse_resources(b"not resources"), Err(HVIFResourceError::NotResources));
+
+  // Program headers placed past the end of memory
+  let mut input = elf(u64::MAX, 200);
+  input.extend_from_slice(&resource_file(false, HVIF_VECTOR_ICON_TYPE, &[]));
+  assert_eq!(parse_resources(&input), Err(HVIFResourceError::Malformed { offset: 32 }));
+
+  let input = resource_file(true, HVIF_VECTOR_ICON_TYPE, &[(101, "BEOS:ICON", BEE_ICON)]);
+  for cut in 0..input.len() {
+    assert!(parse_resources(&input[..cut]).is_err(), "cut at {}", cut);
+  }
+}
@@ -0,0 +1,37 @@
+#![cfg(feature = "serde")]
+extern crate hvif_rs;
+extern crate serde_json;
+
+use hvif_rs::types::*;
+
+const SAMPLE: &[u8] = include_bytes!("../data/sample.hvif");
+const SAMPLE_JSON: &str = include_str!("../data/sample.json");
+
+#[test]
+fn round_trips_images_through_the_documented_schema() {
+  let image = hvif_rs::parse(SAMPLE).unwrap();
+  assert_eq!(serde_json::to_string(&image).unwrap(), SAMPLE_JSON.trim_end());
+  let read: HVIFImage = serde_json::from_str(SAMPLE_JSON).unwrap();
+  assert_eq!(format!("{:?}", read), format!("{:?}", image));
+
+  // The example in the documentation of the schema
+  let shape: HVIFShape = serde_json::from_str(
+    r#"{ "style_index": 0, "path_indices": [0], "modifiers": [{ "type": "translation", "offset": { "x": 4, "y": 8 } }] }"#
+  ).unwrap();
+  assert_eq!(format!("{:?}", shape.modifiers), format!("{:?}", image.shapes[0].modifiers));
+}
+
+#[test]
+fn writes_names_only_when_an_entry_has_one() {
+  let mut image = hvif_rs::parse(SAMPLE).unwrap();
+  assert!(serde_json::to_value(&image).unwrap().get("names").is_none());
+
+  image.names.paths = vec![String::new(), "outline".to_string()];
+  let value = serde_json::to_value(&image).unwrap();
+  assert_eq!(value["names"], serde_json::json!({ "styles": [], "paths": ["", "outline"], "shapes": [] }));
+  let read: HVIFImage = serde_json::from_value(value).unwrap();
+  assert_eq!(read.names, image.names);
+
+  let read: HVIFImage = serde_json::from_str(r#"{ "styles": [], "paths": [], "shapes": [], "names": { "shapes": ["bee"] } }"#).unwrap();
+  assert_eq!(read.names, HVIFNames { styles: Vec::new(), paths: Vec::new(), shapes: vec!["bee".to_string()] });
+}
@@ -0,0 +1,97 @@
+extern crate hvif_rs;
+
+use hvif_rs::svg::*;
+use hvif_rs::types::*;
+
+#[test]
+fn notes_what_could_not_be_carried_over() {
+  let import = parse("<svg viewBox='0 0 64 64' preserveAspectRatio='none'>\
+    <text>Hi</text><text>again</text>\
+    <path d='M0 0 L10 0 L10 10 z' fill='#ff0000' stroke='blue' stroke-dasharray='2 2' fill-rule='evenodd'/>\
+    <rect width='10' height='10' fill='nocolor' clip-path='url(#clip)'/>\
+    <path d='M0 0 L10 10 L x'/>\
+    </svg>").unwrap();
+  assert_eq!(import.notes, vec![
+    SVGImportNote::Approximated("view box alignments other than centered".to_string()),
+    SVGImportNote::Dropped("text".to_string()),
+    SVGImportNote::Dropped("dashed strokes".to_string()),
+    SVGImportNote::Dropped("clipping paths".to_string()),
+    SVGImportNote::Dropped("the unknown color nocolor".to_string()),
+    SVGImportNote::Dropped("malformed path data past the error".to_string()),
+  ]);
+  // The red fill and blue stroke of the first path, the rect in the color it inherits, then the last path up to its error
+  assert_eq!(import.image.shapes.len(), 4);
+  assert_eq!(import.image.paths[2].points.len(), 2);
+
+  let import = parse("<svg><path d='M0 0 L10 0 L10 10 z M2 2 L4 2 L4 4 z' fill-rule='evenodd'/></svg>").unwrap();
+  assert_eq!(import.notes, vec![SVGImportNote::Approximated("the even-odd fill rule with the non-zero fill rule".to_string())]);
+  assert_eq!(import.notes[0].to_string(), "approximated the even-odd fill rule with the non-zero fill rule");
+
+  // Images keep stroke widths in whole units
+  let import = parse("<svg><path d='M0 0 L10 10' fill='none' stroke='black' stroke-width='2.5'/></svg>").unwrap();
+  assert_eq!(import.notes, vec![SVGImportNote::Approximated("stroke widths with whole units".to_string())]);
+  assert!(matches!(import.image.shapes[0].modifiers[..],
+    [HVIFShapeModifier::HVIFTransformerList(ref transformers)]
+      if matches!(transformers[..], [HVIFTransformer::Stroke { width, .. }] if width == 3.0)));
+  let import = parse("<svg><path d='M0 0 L10 10' fill='none' stroke='black' stroke-width='2'/></svg>").unwrap();
+  assert!(import.notes.is_empty());
+}
+
+#[test]
+fn splits_subpaths_longer_than_a_path() {
+  let mut data = "M0 0".to_string();
+  for index in 1..600 {
+    data.push_str(&format!(" L{} {}", index % 60, index / 10));
+  }
+  let import = parse(&format!("<svg><path d='{} z' fill='none' stroke='black'/></svg>", data)).unwrap();
+  assert_eq!(import.notes, vec![SVGImportNote::Approximated("subpaths of more than 255 points with several open paths".to_string())]);
+
+  // The 600 points and the closing return to the first, in runs that each start where the last ended
+  let paths = &import.image.paths;
+  assert_eq!(paths.iter().map(|path| path.points.len()).collect::<Vec<_>>(), vec![255, 255, 93]);
+  assert!(paths.iter().all(|path| !path.closed));
+  assert_eq!(format!("{:?}", paths[0].points[254]), format!("{:?}", paths[1].points[0]));
+  assert_eq!(format!("{:?}", paths[2].points[92]), format!("{:?}", paths[0].points[0]));
+  assert_eq!(import.image.shapes[0].path_indices, vec![0, 1, 2]);
+  assert!(hvif_rs::writer::hvif_image(&import.image).is_ok());
+}
+
+#[test]
+fn rejects_documents_nested_too_deeply() {
+  let document = format!("<svg>{}{}</svg>", "<g>".repeat(10_000), "</g>".repeat(10_000));
+  assert!(matches!(parse(&document), Err(SVGError::Malformed { .. })));
+  assert_eq!(parse("<html/>").err(), Some(SVGError::NotSVG));
+}
+
+#[test]
+fn writes_strokes_and_contours_as_svg_strokes() {
+  let line = |x, y| HVIFPointCommand::Line { point: HVIFPoint { x, y } };
+  let transformers = |transformer| vec![HVIFShapeModifier::HVIFTransformerList(vec![transformer])];
+  let image = HVIFImage {
+    styles: vec![HVIFStyle::SolidColor { red: 255, green: 0, blue: 0, alpha: 128 }],
+    paths: vec![HVIFPath { closed: false, points: vec![line(8.0, 8.0), line(56.0, 8.0), line(56.0, 56.0)] }],
+    shapes: vec![
+      HVIFShape { style_index: 0, path_indices: vec![0], modifiers: transformers(HVIFTransformer::Stroke {
+        width: 2.5, line_join: HVIFLineJoin::Round, line_cap: HVIFLineCap::Square, miter_limit: 4,
+      }) },
+      HVIFShape { style_index: 0, path_indices: vec![0], modifiers: transformers(HVIFTransformer::Contour {
+        width: 1.5, line_join: HVIFLineJoin::Bevel, miter_limit: 4,
+      }) },
+      HVIFShape { style_index: 0, path_indices: vec![0], modifiers: transformers(HVIFTransformer::Contour {
+        width: -1.5, line_join: HVIFLineJoin::Bevel, miter_limit: 4,
+      }) },
+    ],
+    names: HVIFNames::default(),
+  };
+  let svg = svg_image(&image);
+  let paths: Vec<&str> = svg.lines().filter(|line| line.starts_with("  <path")).collect();
+  assert_eq!(paths, vec![
+    "  <path style=\"fill:none;stroke:#ff0000;stroke-width:2.5;stroke-linejoin:round;stroke-linecap:square;\
+     stroke-miterlimit:4;stroke-opacity:0.5019608\" d=\"M 8 8 L 56 8 L 56 56\"/>",
+    // A contour grows the shape by its width on either side, so it is a stroke of twice that width under the fill
+    "  <path style=\"fill:#ff0000;fill-opacity:0.5019608;stroke:#ff0000;stroke-width:3;stroke-linejoin:bevel;stroke-linecap:butt;\
+     stroke-miterlimit:4;stroke-opacity:0.5019608;paint-order:stroke\" d=\"M 8 8 L 56 8 L 56 56\"/>",
+    // A contour shrinking the shape has no equivalent, so only the fill is left
+    "  <path style=\"fill:#ff0000;fill-opacity:0.5019608\" d=\"M 8 8 L 56 8 L 56 56\"/>",
+  ]);
+}
@@ -0,0 +1,99 @@
+extern crate hvif_rs;
+
+use hvif_rs::error::HVIFIndexKind;
+use hvif_rs::validate::*;
+use hvif_rs::writer::{hvif_image, HVIFWriteError};
+use hvif_rs::types::*;
+
+const BEE_ICON: &[u8] = include_bytes!("../data/beeicon.hvif");
+
+fn line(x: f32, y: f32) -> HVIFPointCommand {
+  HVIFPointCommand::Line { point: HVIFPoint { x, y } }
+}
+
+fn image(styles: Vec<HVIFStyle>, paths: Vec<HVIFPath>, shapes: Vec<HVIFShape>) -> HVIFImage {
+  HVIFImage { styles, paths, shapes, names: HVIFNames::default() }
+}
+
+fn shape(style_index: u8, path_indices: Vec<u8>, modifiers: Vec<HVIFShapeModifier>) -> HVIFShape {
+  HVIFShape { style_index, path_indices, modifiers }
+}
+
+fn gradient(stops: &[u8]) -> HVIFStyle {
+  HVIFStyle::Gradient(HVIFGradient {
+    gradient_type: HVIFGradientType::Linear,
+    transform: None,
+    colors: stops.iter().map(|&stop_offset| HVIFGradientColor { stop_offset, alpha: 255, red: 0, green: 0, blue: 0 }).collect(),
+  })
+}
+
+fn problems(image: &HVIFImage) -> Vec<(HVIFSeverity, HVIFLocation, HVIFProblem)> {
+  image.validate().into_iter().map(|diagnostic| (diagnostic.severity, diagnostic.location, diagnostic.problem)).collect()
+}
+
+#[test]
+fn parsed_images_are_valid() {
+  assert_eq!(hvif_rs::parse(BEE_ICON).unwrap().validate(), Vec::new());
+}
+
+#[test]
+fn finds_errors_the_format_cannot_store() {
+  let path = HVIFPath { closed: true, points: vec![line(0.0, 0.0), line(10.0, 0.0)] };
+  let translation = HVIFShapeModifier::HVIFTranslation(HVIFPoint { x: 1.0, y: 1.0 });
+  let matrix = HVIFShapeModifier::HVIFTransformMatrix(HVIFMatrix::from([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]));
+  let image = image(vec![HVIFStyle::SolidGrayNoAlpha { value: 0 }], vec![path], vec![
+    shape(1, vec![0, 2], Vec::new()),
+    shape(0, vec![0], vec![matrix, translation]),
+    shape(0, vec![0], vec![HVIFShapeModifier::HVIFHinting, HVIFShapeModifier::HVIFHinting]),
+    shape(0, vec![0; 256], vec![HVIFShapeModifier::HVIFTransformerList(vec![HVIFTransformer::Perspective; 256])]),
+  ]);
+  let shape_at = |index: usize| HVIFLocation::Shape { index };
+  assert_eq!(problems(&image), vec![
+    (HVIFSeverity::Error, shape_at(0), HVIFProblem::IndexOutOfRange { kind: HVIFIndexKind::Style, index: 1, count: 1 }),
+    (HVIFSeverity::Error, shape_at(0), HVIFProblem::IndexOutOfRange { kind: HVIFIndexKind::Path, index: 2, count: 1 }),
+    (HVIFSeverity::Error, shape_at(1), HVIFProblem::ConflictingModifiers),
+    (HVIFSeverity::Error, shape_at(2), HVIFProblem::ConflictingModifiers),
+    (HVIFSeverity::Error, shape_at(3), HVIFProblem::TooMany { what: "paths in a shape", count: 256 }),
+    (HVIFSeverity::Error, shape_at(3), HVIFProblem::TooMany { what: "transformers", count: 256 }),
+  ]);
+
+  // The writer refuses the image with the first error
+  let first = image.validate()[0];
+  assert_eq!(hvif_image(&image), Err(HVIFWriteError::Invalid(first)));
+  assert_eq!(first.to_string(), "error: shape 0: style index 1 is out of range, the image has 1 styles");
+}
+
+#[test]
+fn warns_of_likely_mistakes() {
+  let paths = vec![
+    HVIFPath { closed: false, points: Vec::new() },
+    HVIFPath { closed: false, points: vec![line(0.0, 0.0), HVIFPointCommand::HLine { x: 300.0 }] },
+  ];
+  let image = image(vec![gradient(&[]), gradient(&[0, 128, 64]), HVIFStyle::SolidGrayNoAlpha { value: 0 }], paths, vec![
+    shape(0, Vec::new(), Vec::new()),
+    shape(1, vec![1], vec![HVIFShapeModifier::HVIFLODScale { min: 2.0, max: 1.0 }]),
+  ]);
+  assert_eq!(problems(&image), vec![
+    (HVIFSeverity::Warning, HVIFLocation::Style { index: 0 }, HVIFProblem::NoGradientStops),
+    (HVIFSeverity::Warning, HVIFLocation::Style { index: 1 }, HVIFProblem::GradientStopsOutOfOrder { stop: 2 }),
+    (HVIFSeverity::Warning, HVIFLocation::Path { index: 0 }, HVIFProblem::EmptyPath),
+    (HVIFSeverity::Warning, HVIFLocation::Path { index: 1 }, HVIFProblem::CoordinateOutOfRange { point: 1 }),
+    (HVIFSeverity::Warning, HVIFLocation::Shape { index: 0 }, HVIFProblem::ShapeWithoutPaths),
+    (HVIFSeverity::Warning, HVIFLocation::Shape { index: 1 }, HVIFProblem::EmptyLODRange),
+    (HVIFSeverity::Warning, HVIFLocation::Style { index: 2 }, HVIFProblem::UnusedStyle),
+    (HVIFSeverity::Warning, HVIFLocation::Path { index: 0 }, HVIFProblem::UnusedPath),
+  ]);
+  // Warnings leave the image writable
+  assert!(hvif_image(&image).is_ok());
+}
+
+#[test]
+fn counts_every_list_against_its_length_byte() {
+  let path = HVIFPath { closed: false, points: vec![line(0.0, 0.0); 256] };
+  let image = image((0..256).map(|_| gradient(&[0; 256])).collect(), vec![path], Vec::new());
+  let problems = problems(&image);
+  assert_eq!(problems[0], (HVIFSeverity::Error, HVIFLocation::Image, HVIFProblem::TooMany { what: "styles", count: 256 }));
+  assert_eq!(problems[1], (HVIFSeverity::Error, HVIFLocation::Style { index: 0 }, HVIFProblem::TooMany { what: "gradient colors", count: 256 }));
+  assert!(problems.contains(&(HVIFSeverity::Error, HVIFLocation::Path { index: 0 }, HVIFProblem::TooMany { what: "points", count: 256 })));
+  assert!(matches!(hvif_image(&image), Err(HVIFWriteError::Invalid(HVIFDiagnostic { problem: HVIFProblem::TooMany { .. }, .. }))));
+}
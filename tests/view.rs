@@ -0,0 +1,79 @@
+extern crate hvif_rs;
+
+use hvif_rs::{HVIFError, HVIFView};
+use hvif_rs::types::*;
+
+const BEE_ICON: &[u8] = include_bytes!("../data/beeicon.hvif");
+
+#[test]
+fn bee_icon_decodes_as_parsed() {
+  let image = hvif_rs::parse(BEE_ICON).unwrap();
+  let view = HVIFView::new(BEE_ICON).unwrap();
+  assert_eq!((view.style_count(), view.path_count(), view.shape_count()),
+    (image.styles.len(), image.paths.len(), image.shapes.len()));
+  assert_eq!(format!("{:?}", view.to_image()), format!("{:?}", image));
+
+  for (path, parsed) in view.paths().zip(&image.paths) {
+    assert_eq!(path.len(), parsed.points.len());
+    assert_eq!(format!("{:?}", path.points().collect::<Vec<_>>()), format!("{:?}", parsed.points));
+  }
+  for (shape, parsed) in view.shapes().zip(&image.shapes) {
+    assert_eq!(shape.path_indices(), &parsed.path_indices[..]);
+  }
+}
+
+#[test]
+fn modifiers_and_gradients_decode_on_demand() {
+  let matrix = HVIFMatrix::from([0.5, 0.25, -0.25, 0.5, 12.0, -4.0]);
+  let image = HVIFImage {
+    styles: vec![HVIFStyle::Gradient(HVIFGradient {
+      gradient_type: HVIFGradientType::Diamond,
+      transform: Some(matrix),
+      colors: vec![
+        HVIFGradientColor { stop_offset: 0, alpha: 255, red: 255, green: 128, blue: 0 },
+        HVIFGradientColor { stop_offset: 255, alpha: 64, red: 1, green: 2, blue: 3 },
+      ],
+    })],
+    paths: vec![HVIFPath { closed: true, points: vec![
+      HVIFPointCommand::Line { point: HVIFPoint { x: 2.5, y: 3.0 } },
+      HVIFPointCommand::VLine { y: 60.0 },
+    ] }],
+    shapes: vec![HVIFShape { style_index: 0, path_indices: vec![0, 0], modifiers: vec![
+      HVIFShapeModifier::HVIFTranslation(HVIFPoint { x: 3.0, y: -2.0 }),
+      HVIFShapeModifier::HVIFLODScale { min: 0.0, max: 4.0 },
+      HVIFShapeModifier::HVIFTransformerList(vec![HVIFTransformer::Perspective, HVIFTransformer::Affine(matrix)]),
+    ] }],
+    names: HVIFNames::default(),
+  };
+  let bytes = hvif_rs::writer::hvif_image(&image).unwrap();
+  let view = HVIFView::new(&bytes).unwrap();
+
+  let style = view.styles().next().unwrap();
+  assert!(style.is_gradient());
+  assert_eq!(format!("{:?}", style.gradient_transform()), format!("{:?}", Some(matrix)));
+  assert_eq!(style.gradient_colors().map(|color| color.alpha).collect::<Vec<_>>(), vec![255, 64]);
+
+  let shape = view.shapes().next().unwrap();
+  assert!(shape.transform().is_none());
+  assert_eq!(format!("{:?}", shape.translation()), format!("{:?}", Some(HVIFPoint { x: 3.0, y: -2.0 })));
+  assert_eq!(shape.lod_scale(), Some((0.0, 4.0)));
+  assert!(shape.visible_at(0.25) && !shape.visible_at(4.5));
+  assert_eq!(shape.transformers().len(), 2);
+  assert_eq!(format!("{:?}", view.to_image()), format!("{:?}", image));
+}
+
+#[test]
+fn fails_as_parse_does() {
+  let mut cut = BEE_ICON[..BEE_ICON.len() - 3].to_vec();
+  assert!(HVIFView::new(&cut).is_err());
+  assert_eq!(HVIFView::new(&cut).err(), hvif_rs::parse(&cut).err());
+  // A style of an unknown type
+  cut[5] = 0xff;
+  assert_eq!(HVIFView::new(&cut).err(), Some(HVIFError::UnknownStyleType { offset: 5, style_type: 0xff }));
+  assert_eq!(HVIFView::new(b"icon").err(), Some(HVIFError::BadMagic { offset: 0 }));
+
+  // Bytes following the image are left out of its view
+  let mut followed = BEE_ICON.to_vec();
+  followed.extend_from_slice(b"trailing");
+  assert_eq!(HVIFView::new(&followed).unwrap().bytes(), BEE_ICON);
+}